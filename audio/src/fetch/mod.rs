@@ -1,4 +1,5 @@
 mod receive;
+mod throttle;
 
 use std::{
     cmp::min,
@@ -6,7 +7,7 @@ use std::{
     io::{self, Read, Seek, SeekFrom},
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc, OnceLock,
+        Arc,
     },
     time::Duration,
 };
@@ -19,14 +20,22 @@ use tempfile::NamedTempFile;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot, Semaphore};
 
+pub use spotipi_core::config::AudioFetchParams;
 use spotipi_core::{cdn_url::CdnUrl, Error, FileId, Session};
 
-use self::receive::audio_file_fetch;
+use self::{receive::audio_file_fetch, throttle::Throttle};
 
 use crate::range_set::{Range, RangeSet};
 
 pub type AudioFileResult = Result<(), spotipi_core::Error>;
 
+/// Limits all audio downloads of the process together to `rate` bytes per second, or not at
+/// all for `None`, whichever session they belong to. Downloads of the files being played take
+/// precedence over those in the background, like prefetches.
+pub fn set_bandwidth_limit(rate: Option<usize>) {
+    Throttle::global().set_rate(rate);
+}
+
 #[derive(Error, Debug)]
 pub enum AudioFileError {
     #[error("other end of channel disconnected")]
@@ -56,76 +65,6 @@ impl From<AudioFileError> for Error {
     }
 }
 
-#[derive(Clone)]
-pub struct AudioFetchParams {
-    /// The minimum size of a block that is requested from the Spotify servers in one request.
-    /// This is the block size that is typically requested while doing a `seek()` on a file.
-    /// The Symphonia decoder requires this to be a power of 2 and > 32 kB.
-    /// Note: smaller requests can happen if part of the block is downloaded already.
-    pub minimum_download_size: usize,
-
-    /// The minimum network throughput that we expect. Together with the minimum download size,
-    /// this will determine the time we will wait for a response.
-    pub minimum_throughput: usize,
-
-    /// The ping time that is used for calculations before a ping time was actually measured.
-    pub initial_ping_time_estimate: Duration,
-
-    /// If the measured ping time to the Spotify server is larger than this value, it is capped
-    /// to avoid run-away block sizes and pre-fetching.
-    pub maximum_assumed_ping_time: Duration,
-
-    /// Before playback starts, this many seconds of data must be present.
-    /// Note: the calculations are done using the nominal bitrate of the file. The actual amount
-    /// of audio data may be larger or smaller.
-    pub read_ahead_before_playback: Duration,
-
-    /// While playing back, this many seconds of data ahead of the current read position are
-    /// requested.
-    /// Note: the calculations are done using the nominal bitrate of the file. The actual amount
-    /// of audio data may be larger or smaller.
-    pub read_ahead_during_playback: Duration,
-
-    /// If the amount of data that is pending (requested but not received) is less than a certain amount,
-    /// data is pre-fetched in addition to the read ahead settings above. The threshold for requesting more
-    /// data is calculated as `<pending bytes> < PREFETCH_THRESHOLD_FACTOR * <ping time> * <nominal data rate>`
-    pub prefetch_threshold_factor: f32,
-
-    /// The time we will wait to obtain status updates on downloading.
-    pub download_timeout: Duration,
-}
-
-impl Default for AudioFetchParams {
-    fn default() -> Self {
-        let minimum_download_size = 64 * 1024;
-        let minimum_throughput = 8 * 1024;
-        Self {
-            minimum_download_size,
-            minimum_throughput,
-            initial_ping_time_estimate: Duration::from_millis(500),
-            maximum_assumed_ping_time: Duration::from_millis(1500),
-            read_ahead_before_playback: Duration::from_secs(1),
-            read_ahead_during_playback: Duration::from_secs(5),
-            prefetch_threshold_factor: 4.0,
-            download_timeout: Duration::from_secs(
-                (minimum_download_size / minimum_throughput) as u64,
-            ),
-        }
-    }
-}
-
-static AUDIO_FETCH_PARAMS: OnceLock<AudioFetchParams> = OnceLock::new();
-
-impl AudioFetchParams {
-    pub fn set(params: AudioFetchParams) -> Result<(), AudioFetchParams> {
-        AUDIO_FETCH_PARAMS.set(params)
-    }
-
-    pub fn get() -> &'static AudioFetchParams {
-        AUDIO_FETCH_PARAMS.get_or_init(AudioFetchParams::default)
-    }
-}
-
 pub enum AudioFile {
     Cached(fs::File),
     Streaming(AudioFileStreaming),
@@ -217,7 +156,7 @@ impl StreamLoaderController {
 
         if let Some(ref shared) = self.stream_shared {
             let mut download_status = shared.download_status.lock();
-            let download_timeout = AudioFetchParams::get().download_timeout;
+            let download_timeout = shared.params.download_timeout;
 
             while range.length
                 > download_status
@@ -229,6 +168,11 @@ impl StreamLoaderController {
                     .wait_for(&mut download_status, download_timeout)
                     .timed_out()
                 {
+                    // a download in the background receives nothing while others are in
+                    // progress, which is no reason to give up on it
+                    if shared.is_background() && Throttle::global().holds_back_background() {
+                        continue;
+                    }
                     return Err(AudioFileError::WaitTimeout.into());
                 }
 
@@ -280,6 +224,13 @@ impl StreamLoaderController {
         }
     }
 
+    pub fn set_background_mode(&self, background: bool) {
+        // when bandwidth is limited, only download while no other file is being downloaded
+        if let Some(ref shared) = self.stream_shared {
+            shared.set_background(background)
+        }
+    }
+
     pub fn set_stream_mode(&self) {
         // optimise download strategy for streaming
         if let Some(ref shared) = self.stream_shared {
@@ -307,11 +258,13 @@ struct AudioFileDownloadStatus {
 
 struct AudioFileShared {
     cdn_url: CdnUrl,
+    params: AudioFetchParams,
     file_size: usize,
     bytes_per_second: usize,
     cond: Condvar,
     download_status: Mutex<AudioFileDownloadStatus>,
     download_streaming: AtomicBool,
+    background: AtomicBool,
     download_slots: Semaphore,
     ping_time_ms: AtomicUsize,
     read_position: AtomicUsize,
@@ -327,12 +280,20 @@ impl AudioFileShared {
         self.download_streaming.store(streaming, Ordering::Release)
     }
 
+    fn is_background(&self) -> bool {
+        self.background.load(Ordering::Acquire)
+    }
+
+    fn set_background(&self, background: bool) {
+        self.background.store(background, Ordering::Release)
+    }
+
     fn ping_time(&self) -> Duration {
         let ping_time_ms = self.ping_time_ms.load(Ordering::Acquire);
         if ping_time_ms > 0 {
             Duration::from_millis(ping_time_ms as u64)
         } else {
-            AudioFetchParams::get().initial_ping_time_estimate
+            self.params.initial_ping_time_estimate
         }
     }
 
@@ -430,7 +391,8 @@ impl AudioFileStreaming {
             trace!("Streaming from {}", url);
         }
 
-        let minimum_download_size = AudioFetchParams::get().minimum_download_size;
+        let params = session.config().audio_fetch_params.clone();
+        let minimum_download_size = params.minimum_download_size;
 
         // When the audio file is really small, this `download_size` may turn out to be
         // larger than the audio file we're going to stream later on. This is OK; requesting
@@ -474,6 +436,7 @@ impl AudioFileStreaming {
 
         let shared = Arc::new(AudioFileShared {
             cdn_url,
+            params,
             file_size,
             bytes_per_second,
             cond: Condvar::new(),
@@ -482,6 +445,7 @@ impl AudioFileStreaming {
                 downloaded: RangeSet::new(),
             }),
             download_streaming: AtomicBool::new(false),
+            background: AtomicBool::new(false),
             download_slots: Semaphore::new(1),
             ping_time_ms: AtomicUsize::new(0),
             read_position: AtomicUsize::new(0),
@@ -527,7 +491,7 @@ impl Read for AudioFileStreaming {
             return Ok(0);
         }
 
-        let read_ahead_during_playback = AudioFetchParams::get().read_ahead_during_playback;
+        let read_ahead_during_playback = self.shared.params.read_ahead_during_playback;
         let length_to_request = if self.shared.is_download_streaming() {
             let length_to_request = length
                 + (read_ahead_during_playback.as_secs_f32() * self.shared.bytes_per_second as f32)
@@ -553,7 +517,7 @@ impl Read for AudioFileStreaming {
                 .map_err(|err| io::Error::new(io::ErrorKind::BrokenPipe, err))?;
         }
 
-        let download_timeout = AudioFetchParams::get().download_timeout;
+        let download_timeout = self.shared.params.download_timeout;
         while !download_status.downloaded.contains(offset) {
             if self
                 .shared
@@ -17,8 +17,8 @@ use spotipi_core::{http_client::HttpClient, session::Session, Error};
 use crate::range_set::{Range, RangeSet};
 
 use super::{
-    AudioFetchParams, AudioFileError, AudioFileResult, AudioFileShared, StreamLoaderCommand,
-    StreamingRequest,
+    throttle::Throttle, AudioFetchParams, AudioFileError, AudioFileResult, AudioFileShared,
+    StreamLoaderCommand, StreamingRequest,
 };
 
 struct PartialFileData {
@@ -44,11 +44,14 @@ async fn receive_data(
 
     let permit = shared.download_slots.acquire().await?;
 
+    let throttle = Throttle::global();
+    let foreground_download = throttle.start_download(shared.is_background());
+
     let request_time = Instant::now();
     let mut measure_ping_time = true;
     let mut measure_throughput = true;
 
-    let result: Result<_, Error> = loop {
+    let result: Result<_, Error> = 'receive: loop {
         let response = match request.initial_response.take() {
             Some(data) => {
                 // the request was already made outside of this function
@@ -97,20 +100,30 @@ async fn receive_data(
             break Err(AudioFileError::StatusCode(code).into());
         }
 
-        let body = response.into_body();
-        let data = match body.collect().await.map(|b| b.to_bytes()) {
-            Ok(bytes) => bytes,
-            Err(e) => break Err(e.into()),
-        };
+        // Pass the data on as it comes in, so that the download can be throttled.
+        let mut body = response.into_body();
+        loop {
+            let data = match body.frame().await {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => data,
+                    Err(_) => continue, // trailers
+                },
+                Some(Err(e)) => break 'receive Err(e.into()),
+                None => break,
+            };
 
-        let data_size = data.len();
-        file_data_tx.send(ReceivedData::Data(PartialFileData { offset, data }))?;
+            let data_size = data.len();
+            file_data_tx.send(ReceivedData::Data(PartialFileData { offset, data }))?;
 
-        actual_length += data_size;
-        offset += data_size;
+            actual_length += data_size;
+            offset += data_size;
+
+            throttle.consume(data_size, shared.is_background()).await;
+        }
     };
 
     drop(request.streamer);
+    drop(foreground_download);
 
     if measure_throughput {
         let duration = Instant::now().duration_since(request_time).as_millis();
@@ -425,7 +438,7 @@ pub(super) async fn audio_file_fetch(
         initial_request,
     ));
 
-    let params = AudioFetchParams::get();
+    let params = shared.params.clone();
 
     let mut fetch = AudioFileFetch {
         session: session.clone(),
@@ -436,7 +449,7 @@ pub(super) async fn audio_file_fetch(
         complete_tx: Some(complete_tx),
         network_response_times: Vec::with_capacity(3),
 
-        params: params.clone(),
+        params,
     };
 
     loop {
@@ -478,7 +491,7 @@ pub(super) async fn audio_file_fetch(
             let throughput = fetch.shared.throughput();
 
             let desired_pending_bytes = max(
-                (params.prefetch_threshold_factor
+                (fetch.params.prefetch_threshold_factor
                     * ping_time_seconds
                     * fetch.shared.bytes_per_second as f32) as usize,
                 (ping_time_seconds * throughput as f32) as usize,
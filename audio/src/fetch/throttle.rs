@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use parking_lot::{const_mutex, Mutex};
+use tokio::time::Instant;
+
+// Background downloads check this often whether the foreground downloads are done.
+const BACKGROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Limits the rate at which all audio downloads of the process receive data together, as they
+// share the same uplink. Downloads in the background, such as prefetches, only receive data
+// while no other download is in progress.
+pub(super) struct Throttle {
+    state: Mutex<ThrottleState>,
+}
+
+struct ThrottleState {
+    // bytes per second, or unlimited
+    rate: Option<usize>,
+    // when the data received so far will have been received at the rate
+    next_free: Option<Instant>,
+    foreground_downloads: usize,
+}
+
+static THROTTLE: Throttle = Throttle::new();
+
+// Counts a foreground download as in progress while it lives.
+pub(super) struct ForegroundDownload<'a>(&'a Throttle);
+
+impl Drop for ForegroundDownload<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().foreground_downloads -= 1;
+    }
+}
+
+impl Throttle {
+    const fn new() -> Self {
+        Self {
+            state: const_mutex(ThrottleState {
+                rate: None,
+                next_free: None,
+                foreground_downloads: 0,
+            }),
+        }
+    }
+
+    pub(super) fn global() -> &'static Self {
+        &THROTTLE
+    }
+
+    // Set once at startup, but a change takes effect for the data received from then on.
+    pub(super) fn set_rate(&self, rate: Option<usize>) {
+        let mut state = self.state.lock();
+        if state.rate != rate {
+            match rate {
+                Some(rate) => debug!("Limiting audio downloads to {} bytes/s", rate),
+                None => debug!("Not limiting audio downloads"),
+            }
+            state.rate = rate;
+            state.next_free = None;
+        }
+    }
+
+    pub(super) fn start_download(&self, background: bool) -> Option<ForegroundDownload<'_>> {
+        if background {
+            None
+        } else {
+            self.state.lock().foreground_downloads += 1;
+            Some(ForegroundDownload(self))
+        }
+    }
+
+    // Whether downloads in the background are waiting for those in the foreground.
+    pub(super) fn holds_back_background(&self) -> bool {
+        let state = self.state.lock();
+        state.rate.is_some() && state.foreground_downloads > 0
+    }
+
+    // Accounts for data that was received, and waits until more may be received.
+    pub(super) async fn consume(&self, bytes: usize, background: bool) {
+        loop {
+            let delay = {
+                let mut state = self.state.lock();
+
+                let Some(rate) = state.rate else {
+                    return;
+                };
+
+                if background && state.foreground_downloads > 0 {
+                    None
+                } else {
+                    let now = Instant::now();
+                    let start = state.next_free.map_or(now, |next_free| next_free.max(now));
+                    let next_free = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+                    state.next_free = Some(next_free);
+
+                    Some(next_free - now)
+                }
+            };
+
+            match delay {
+                Some(delay) => {
+                    tokio::time::sleep(delay).await;
+                    return;
+                }
+                None => tokio::time::sleep(BACKGROUND_POLL_INTERVAL).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test has its own, the global one is shared by every download of the process.
+    fn throttle(rate: Option<usize>) -> &'static Throttle {
+        let throttle = Box::leak(Box::new(Throttle::new()));
+        throttle.set_rate(rate);
+        throttle
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn passes_everything_without_a_rate() {
+        let throttle = throttle(None);
+        let start = Instant::now();
+
+        let _download = throttle.start_download(false);
+        for _ in 0..100 {
+            throttle.consume(1_000_000, false).await;
+            throttle.consume(1_000_000, true).await;
+        }
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_the_rate() {
+        let throttle = throttle(Some(1000));
+        let start = Instant::now();
+
+        for _ in 0..4 {
+            throttle.consume(500, false).await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        // the data of all downloads together is limited
+        tokio::join!(throttle.consume(1000, false), async {
+            throttle.consume(1000, false).await;
+            throttle.consume(1000, true).await;
+        });
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn takes_up_the_rate_afresh_after_a_pause() {
+        let throttle = throttle(Some(1000));
+        throttle.consume(1000, false).await;
+
+        // no data for a while doesn't save up for a burst
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let start = Instant::now();
+        throttle.consume(1000, false).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_downloads_yield_to_foreground_downloads() {
+        let throttle = throttle(Some(1000));
+
+        let download = throttle.start_download(false);
+        assert!(throttle.start_download(true).is_none());
+        assert!(throttle.holds_back_background());
+
+        let background = tokio::spawn(throttle.consume(100, true));
+
+        throttle.consume(1000, false).await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert!(!background.is_finished());
+
+        drop(download);
+        assert!(!throttle.holds_back_background());
+
+        let start = Instant::now();
+        background.await.unwrap();
+        assert!(start.elapsed() <= BACKGROUND_POLL_INTERVAL + Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_downloads_are_not_held_back_without_a_rate() {
+        let throttle = throttle(None);
+        let _download = throttle.start_download(false);
+        assert!(!throttle.holds_back_background());
+    }
+}
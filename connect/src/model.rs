@@ -1,8 +1,16 @@
 use crate::{
-    core::dealer::protocol::SkipTo, protocol::context_player_options::ContextPlayerOptionOverrides,
+    core::{config::DeviceType, dealer::protocol::SkipTo},
+    playback::player::SleepTimer,
+    protocol::{
+        connect::{Capabilities, Cluster, DeviceInfo},
+        context_player_options::ContextPlayerOptionOverrides,
+    },
 };
 
-use std::ops::Deref;
+use std::{
+    ops::Deref,
+    time::{Duration, Instant},
+};
 
 /// Request for loading playback
 #[derive(Debug)]
@@ -127,6 +135,120 @@ impl TryFrom<SkipTo> for PlayingTrack {
     }
 }
 
+/// A track before or after the current one, as shown in "Up next"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueTrack {
+    /// The uri of the track or episode
+    pub uri: String,
+    /// Identifies the item within the queue, e.g. to remove or move it
+    pub uid: String,
+    /// Whether the item was added to the queue, instead of coming from the context or autoplay
+    pub queued: bool,
+}
+
+/// The tracks around the current one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Queue {
+    /// The tracks played before the current one, the most recent one last
+    pub prev_tracks: Vec<QueueTrack>,
+    /// The tracks up next, starting with the queued ones
+    pub next_tracks: Vec<QueueTrack>,
+}
+
+/// The devices of the user that are visible to spotify connect
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectCluster {
+    /// The id of the device that is playing, `None` if none is
+    pub active_device_id: Option<String>,
+    /// The visible devices, including this one, sorted by name
+    pub devices: Vec<ConnectDevice>,
+}
+
+impl ConnectCluster {
+    pub(super) fn new(cluster: &Cluster) -> Self {
+        let active_device_id = Some(cluster.active_device_id.clone()).filter(|id| !id.is_empty());
+
+        let mut devices = cluster
+            .device
+            .iter()
+            .map(|(id, info)| ConnectDevice::new(id, info, active_device_id.as_ref() == Some(id)))
+            .collect::<Vec<_>>();
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            active_device_id,
+            devices,
+        }
+    }
+
+    /// The device that is playing
+    pub fn active_device(&self) -> Option<&ConnectDevice> {
+        self.devices.iter().find(|d| d.is_active)
+    }
+
+    /// Finds a device by its id
+    pub fn device(&self, id: &str) -> Option<&ConnectDevice> {
+        self.devices.iter().find(|d| d.id == id)
+    }
+}
+
+/// A device in the connect cluster of the user, which the playback can be transferred to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectDevice {
+    /// The id to pass to [`Spirc::transfer_to`](crate::Spirc::transfer_to)
+    pub id: String,
+    /// The name as shown in the device picker of the spotify clients
+    pub name: String,
+    /// The icon type of the device
+    pub device_type: DeviceType,
+    /// The volume, from 0 to [`u16::MAX`]
+    pub volume: u16,
+    /// Whether the device is the one playing
+    pub is_active: bool,
+    /// What the device supports
+    pub capabilities: ConnectDeviceCapabilities,
+}
+
+impl ConnectDevice {
+    fn new(id: &str, info: &DeviceInfo, is_active: bool) -> Self {
+        Self {
+            id: id.to_string(),
+            name: info.name.clone(),
+            device_type: info.device_type.enum_value_or_default().into(),
+            volume: u16::try_from(info.volume).unwrap_or(u16::MAX),
+            is_active,
+            capabilities: ConnectDeviceCapabilities::from(&*info.capabilities),
+        }
+    }
+}
+
+/// The capabilities a device announces to the connect cluster
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectDeviceCapabilities {
+    /// Whether the device can play, otherwise it only controls the playback of others
+    pub can_be_player: bool,
+    /// Whether the device can be controlled by other devices
+    pub is_controllable: bool,
+    /// Whether the volume can be changed by other devices
+    pub can_change_volume: bool,
+    /// The number of steps in which the volume is changed
+    pub volume_steps: u32,
+    /// Whether the device is hidden in the device picker of the spotify clients
+    pub hidden: bool,
+}
+
+impl From<&Capabilities> for ConnectDeviceCapabilities {
+    fn from(value: &Capabilities) -> Self {
+        Self {
+            can_be_player: value.can_be_player,
+            is_controllable: value.is_controllable,
+            can_change_volume: !value.disable_volume,
+            volume_steps: u32::try_from(value.volume_steps).unwrap_or_default(),
+            hidden: value.hidden,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(super) enum SpircPlayStatus {
     Stopped,
@@ -145,3 +267,131 @@ pub(super) enum SpircPlayStatus {
         preloading_of_next_track_triggered: bool,
     },
 }
+
+/// The state of an active sleep timer
+#[derive(Debug, Clone, Copy)]
+pub struct SleepTimerStatus {
+    /// The mode the sleep timer was set with
+    pub sleep_timer: SleepTimer,
+    /// The time until the playback is paused
+    ///
+    /// Is `None` while the end can't be determined yet, for example because
+    /// the playback is paused or the last track of the context isn't reached.
+    pub remaining: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub(super) struct SpircSleepTimer {
+    pub sleep_timer: SleepTimer,
+    /// only set for [SleepTimer::Duration]
+    pub expires_at: Option<Instant>,
+    /// set when the fade out started, after which the playback is paused
+    pub pause_at: Option<Instant>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::devices::DeviceType as ProtoDeviceType;
+
+    fn device_info(name: &str, capabilities: Capabilities) -> DeviceInfo {
+        DeviceInfo {
+            name: name.to_string(),
+            device_type: ProtoDeviceType::SPEAKER.into(),
+            volume: 0x8000,
+            capabilities: Some(capabilities).into(),
+            ..Default::default()
+        }
+    }
+
+    fn proto_cluster(active_device_id: &str) -> Cluster {
+        let mut cluster = Cluster {
+            active_device_id: active_device_id.to_string(),
+            ..Default::default()
+        };
+
+        let player = Capabilities {
+            can_be_player: true,
+            is_controllable: true,
+            volume_steps: 64,
+            ..Default::default()
+        };
+        cluster.device.insert(
+            "kitchen".to_string(),
+            device_info("Kitchen", player.clone()),
+        );
+        cluster
+            .device
+            .insert("bedroom".to_string(), device_info("Bedroom", player));
+        cluster.device.insert(
+            "phone".to_string(),
+            device_info(
+                "Phone",
+                Capabilities {
+                    is_controllable: true,
+                    disable_volume: true,
+                    volume_steps: -1,
+                    hidden: true,
+                    ..Default::default()
+                },
+            ),
+        );
+
+        cluster
+    }
+
+    #[test]
+    fn sorts_the_devices_by_name() {
+        let cluster = ConnectCluster::new(&proto_cluster("kitchen"));
+
+        let names = cluster.devices.iter().map(|d| d.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), ["Bedroom", "Kitchen", "Phone"]);
+        assert_eq!(cluster.device("phone").unwrap().name, "Phone");
+        assert!(cluster.device("garage").is_none());
+    }
+
+    #[test]
+    fn detects_the_active_device() {
+        let cluster = ConnectCluster::new(&proto_cluster("kitchen"));
+        assert_eq!(cluster.active_device_id.as_deref(), Some("kitchen"));
+        assert_eq!(cluster.active_device().unwrap().id, "kitchen");
+        assert_eq!(cluster.devices.iter().filter(|d| d.is_active).count(), 1);
+
+        // nothing is playing
+        let cluster = ConnectCluster::new(&proto_cluster(""));
+        assert_eq!(cluster.active_device_id, None);
+        assert!(cluster.active_device().is_none());
+        assert!(cluster.devices.iter().all(|d| !d.is_active));
+    }
+
+    #[test]
+    fn maps_the_device_info_and_capabilities() {
+        let cluster = ConnectCluster::new(&proto_cluster("kitchen"));
+
+        let kitchen = cluster.device("kitchen").unwrap();
+        assert_eq!(kitchen.device_type, DeviceType::Speaker);
+        assert_eq!(kitchen.volume, 0x8000);
+        assert_eq!(
+            kitchen.capabilities,
+            ConnectDeviceCapabilities {
+                can_be_player: true,
+                is_controllable: true,
+                can_change_volume: true,
+                volume_steps: 64,
+                hidden: false,
+            }
+        );
+
+        let phone = cluster.device("phone").unwrap();
+        assert_eq!(
+            phone.capabilities,
+            ConnectDeviceCapabilities {
+                can_be_player: false,
+                is_controllable: true,
+                can_change_volume: false,
+                volume_steps: 0,
+                hidden: true,
+            }
+        );
+    }
+}
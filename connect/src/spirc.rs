@@ -9,11 +9,14 @@ use crate::{
         session::UserAttributes,
         Error, Session, SpotifyId,
     },
-    model::{LoadRequest, PlayingTrack, SpircPlayStatus},
+    model::{
+        ConnectCluster, LoadRequest, PlayingTrack, Queue, SleepTimerStatus, SpircPlayStatus,
+        SpircSleepTimer,
+    },
     playback::{
         cec::{CecClient, CecEvent},
         mixer::Mixer,
-        player::{Player, PlayerEvent, PlayerEventChannel},
+        player::{Player, PlayerEvent, PlayerEventChannel, SleepTimer},
     },
     protocol::{
         connect::{Cluster, ClusterUpdate, LogoutCommand, SetVolumeCommand},
@@ -27,7 +30,7 @@ use crate::{
     state::{
         context::{ContextType, ResetContext},
         provider::IsProvider,
-        {ConnectConfig, ConnectState},
+        {ConnectConfig, ConnectState, RestoreSession},
     },
     LoadContextOptions, LoadRequestOptions,
 };
@@ -35,12 +38,16 @@ use futures_util::StreamExt;
 use protobuf::MessageField;
 use std::{
     future::Future,
-    sync::atomic::{AtomicUsize, Ordering},
-    sync::Arc,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
-use tokio::{sync::mpsc, task::JoinSet, time::sleep};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::{self, JoinSet},
+    time::sleep,
+};
 
 #[derive(Debug, Error)]
 enum SpircError {
@@ -54,6 +61,10 @@ enum SpircError {
     FailedDealerSetup,
     #[error("unknown endpoint: {0:#?}")]
     UnknownEndpoint(serde_json::Value),
+    #[error("can't transfer to <{0}>, it is this device")]
+    TransferToSelf(String),
+    #[error("device <{0}> is not in the cluster")]
+    UnknownDevice(String),
 }
 
 impl From<SpircError> for Error {
@@ -63,6 +74,8 @@ impl From<SpircError> for Error {
             NoData | NoUri(_) => Error::unavailable(err),
             InvalidUri(_) | FailedDealerSetup => Error::aborted(err),
             UnknownEndpoint(_) => Error::unimplemented(err),
+            TransferToSelf(_) => Error::invalid_argument(err),
+            UnknownDevice(_) => Error::not_found(err),
         }
     }
 }
@@ -103,16 +116,53 @@ struct SpircTask {
     /// when set to true, it will update the volume after [VOLUME_UPDATE_DELAY],
     /// when no other future resolves, otherwise resets the delay
     update_volume: bool,
+    /// the volume that is yet to be saved to the cache, saved along with the delayed
+    /// volume update so that changing the volume doesn't write the cache each step
+    unsaved_volume: Option<u16>,
 
     /// when set to true, it will update the volume after [UPDATE_STATE_DELAY],
     /// when no other future resolves, otherwise resets the delay
     update_state: bool,
 
+    /// every volume is kept within these limits
+    min_volume: u16,
+    max_volume: u16,
+    /// restores and saves the volume of the connected user
+    volume_per_user: bool,
+
+    /// pauses the playback when it runs out, survives track changes
+    sleep_timer: Option<SpircSleepTimer>,
+    sleep_timer_fade: Duration,
+    sleep_timer_disconnect: bool,
+
+    /// saves the playback to the cache and decides when to restore it
+    restore_session: RestoreSession,
+    /// set until the first connection if the playback should be restored automatically,
+    /// see [Spirc::new]
+    restore_pending: bool,
+    /// the playback last saved by [SpircTask::save_session], without its position
+    saved_session: Option<TransferState>,
+    session_writer: Arc<SessionWriter>,
+
+    /// the speed at which the position advances, as reported by the player
+    playback_speed: f64,
+
+    /// the devices of the last received cluster
+    cluster: ConnectCluster,
+
     spirc_id: usize,
 }
 
 static SPIRC_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
+/// Writes the saved playback to the cache in the background, where a later save wins over
+/// an earlier one that is still waiting for its turn.
+#[derive(Default)]
+struct SessionWriter {
+    latest: AtomicU64,
+    write: Mutex<()>,
+}
+
 #[derive(Debug)]
 enum SpircCommand {
     Play,
@@ -131,6 +181,15 @@ enum SpircCommand {
     SetVolume(u16),
     Activate,
     Load(LoadRequest),
+    SetSleepTimer(Option<SleepTimer>),
+    GetSleepTimer(oneshot::Sender<Option<SleepTimerStatus>>),
+    AddToQueue(String),
+    RemoveFromQueue(String),
+    MoveInQueue { uid: String, position: usize },
+    ClearQueue,
+    GetQueue(oneshot::Sender<Queue>),
+    TransferTo(String),
+    GetCluster(oneshot::Sender<ConnectCluster>),
 }
 
 const CONTEXT_FETCH_THRESHOLD: usize = 2;
@@ -154,6 +213,10 @@ impl Spirc {
     /// can control the local connect device when active. And a [`Future`]
     /// which represents the [`Spirc`] event loop that processes the whole
     /// connect device logic.
+    ///
+    /// With `restore_pending` and [RestoreSession::Auto], the last playback is restored once
+    /// connected. Only pass it for the first [`Spirc`] after starting up, so that reconnecting
+    /// doesn't bring back the playback of an earlier session.
     pub async fn new(
         config: ConnectConfig,
         session: Session,
@@ -161,6 +224,7 @@ impl Spirc {
         player: Arc<Player>,
         mixer: Arc<dyn Mixer>,
         cec_client: Arc<CecClient>,
+        restore_pending: bool,
     ) -> Result<(Spirc, impl Future<Output = ()>), Error> {
         fn extract_connection_id(msg: Message) -> Result<String, Error> {
             let connection_id = msg
@@ -173,6 +237,12 @@ impl Spirc {
         let spirc_id = SPIRC_COUNTER.fetch_add(1, Ordering::AcqRel);
         debug!("new Spirc[{}]", spirc_id);
 
+        let sleep_timer_fade = config.sleep_timer_fade;
+        let sleep_timer_disconnect = config.sleep_timer_disconnect;
+        let restore_session = config.restore_session;
+        let min_volume = config.min_volume;
+        let max_volume = config.max_volume.max(min_volume);
+        let volume_per_user = config.volume_per_user;
         let connect_state = ConnectState::new(config, &session);
 
         let connection_id_update = session
@@ -254,8 +324,26 @@ impl Spirc {
 
             transfer_state: None,
             update_volume: false,
+            unsaved_volume: None,
             update_state: false,
 
+            min_volume,
+            max_volume,
+            volume_per_user,
+
+            sleep_timer: None,
+            sleep_timer_fade,
+            sleep_timer_disconnect,
+
+            restore_session,
+            restore_pending: restore_pending && restore_session == RestoreSession::Auto,
+            saved_session: None,
+            session_writer: Arc::default(),
+
+            playback_speed: 1.,
+
+            cluster: ConnectCluster::default(),
+
             spirc_id,
         };
 
@@ -274,6 +362,9 @@ impl Spirc {
             }
             debug!("Retrieved CEC volume");
             task.cec_client.get_volume() as u32
+        } else if let Some(volume) = task.user_volume() {
+            debug!("Restoring the volume of {}", task.session.username());
+            volume as u32
         } else {
             task.connect_state.device_info().volume
         };
@@ -418,6 +509,89 @@ impl Spirc {
     pub fn activate(&self) -> Result<(), Error> {
         Ok(self.commands.send(SpircCommand::Activate)?)
     }
+
+    /// Sets a sleep timer, replacing any previous one.
+    ///
+    /// When the timer runs out the playback fades out over the time configured
+    /// in [ConnectConfig], pauses and optionally disconnects the device.
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn set_sleep_timer(&self, sleep_timer: SleepTimer) -> Result<(), Error> {
+        Ok(self
+            .commands
+            .send(SpircCommand::SetSleepTimer(Some(sleep_timer)))?)
+    }
+
+    /// Cancels the sleep timer, resuming the playback if it is already fading out.
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn cancel_sleep_timer(&self) -> Result<(), Error> {
+        Ok(self.commands.send(SpircCommand::SetSleepTimer(None))?)
+    }
+
+    /// Returns the current sleep timer and the time remaining until it pauses the playback.
+    pub async fn sleep_timer(&self) -> Result<Option<SleepTimerStatus>, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.commands.send(SpircCommand::GetSleepTimer(tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Adds a track or episode to the queue, after the already queued ones.
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn add_to_queue(&self, uri: String) -> Result<(), Error> {
+        Ok(self.commands.send(SpircCommand::AddToQueue(uri))?)
+    }
+
+    /// Removes a queued track, identified by its [uid](crate::QueueTrack::uid).
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn remove_from_queue(&self, uid: String) -> Result<(), Error> {
+        Ok(self.commands.send(SpircCommand::RemoveFromQueue(uid))?)
+    }
+
+    /// Moves a queued track, identified by its [uid](crate::QueueTrack::uid), to the given
+    /// position among the queued tracks.
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn move_in_queue(&self, uid: String, position: usize) -> Result<(), Error> {
+        Ok(self
+            .commands
+            .send(SpircCommand::MoveInQueue { uid, position })?)
+    }
+
+    /// Removes all queued tracks, leaving the tracks of the context.
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn clear_queue(&self) -> Result<(), Error> {
+        Ok(self.commands.send(SpircCommand::ClearQueue)?)
+    }
+
+    /// Returns the tracks before and after the current one, as shown in "Up next".
+    ///
+    /// The queue is empty if we are not the active device.
+    pub async fn queue(&self) -> Result<Queue, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.commands.send(SpircCommand::GetQueue(tx))?;
+        Ok(rx.await?)
+    }
+
+    /// Transfers the playback to another device of the cluster, see [`Spirc::devices`].
+    ///
+    /// Does nothing if we are not the active device.
+    pub fn transfer_to(&self, device_id: String) -> Result<(), Error> {
+        Ok(self.commands.send(SpircCommand::TransferTo(device_id))?)
+    }
+
+    /// Returns the devices in the connect cluster of the user, including this one, and
+    /// which of them is playing.
+    ///
+    /// The cluster is empty until the first one is received from spotify.
+    pub async fn cluster(&self) -> Result<ConnectCluster, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.commands.send(SpircCommand::GetCluster(tx))?;
+        Ok(rx.await?)
+    }
 }
 
 impl SpircTask {
@@ -450,6 +624,10 @@ impl SpircTask {
         self.cec_handles = Some(CecClient::run(self.cec_client.clone(), cec_sender));
 
         while !self.session.is_invalid() && !self.shutdown {
+            let sleep_timer_delay = self
+                .sleep_timer_deadline()
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
             let commands = self.commands.as_mut();
             let player_events = self.player_events.as_mut();
 
@@ -536,6 +714,11 @@ impl SpircTask {
                         },
                         CecEvent::VolumeChange(old, new) => {
                             if old != new {
+                                // keep the device itself within the limits as well
+                                let limited_volume = self.limit_volume(new);
+                                if limited_volume != new {
+                                    self.cec_client.set_volume(limited_volume);
+                                }
                                 self.set_volume(new);
                                 if let Err(why) = self.connect_state.notify_volume_changed(&self.session).await {
                                     error!("error updating connect state for volume update: {why}")
@@ -551,8 +734,14 @@ impl SpircTask {
                         error!("state update: {why}")
                     }
                 },
+                _ = async { sleep(sleep_timer_delay.unwrap_or_default()).await }, if sleep_timer_delay.is_some() => {
+                    if let Err(why) = self.handle_sleep_timer_deadline().await {
+                        error!("sleep timer: {why}")
+                    }
+                },
                 _ = async { sleep(VOLUME_UPDATE_DELAY).await }, if self.update_volume => {
                     self.update_volume = false;
+                    self.save_volume();
                     
                     info!("delayed volume update for all devices: volume is now {}", self.connect_state.device_info().volume);
                     if let Err(why) = self.connect_state.notify_volume_changed(&self.session).await {
@@ -590,6 +779,8 @@ impl SpircTask {
             }
         }
 
+        self.save_volume();
+
         if !self.shutdown && self.connect_state.is_active() {
             warn!("unexpected shutdown");
             if let Err(why) = self.handle_disconnect().await {
@@ -670,6 +861,11 @@ impl SpircTask {
             }
             SpircCommand::Activate if !self.connect_state.is_active() => {
                 trace!("Received SpircCommand::{:?}", cmd);
+                if self.restore_session == RestoreSession::OnActivate {
+                    if let Err(why) = self.handle_restore_session() {
+                        warn!("couldn't restore the last playback: {why}")
+                    }
+                }
                 self.handle_activate();
                 return self.notify().await;
             }
@@ -677,6 +873,23 @@ impl SpircTask {
                 "SpircCommand::{:?} will be ignored while already active",
                 cmd
             ),
+            SpircCommand::GetSleepTimer(tx) => {
+                let _ = tx.send(self.sleep_timer_status());
+                return Ok(());
+            }
+            SpircCommand::GetQueue(tx) => {
+                let queue = if self.connect_state.is_active() {
+                    self.connect_state.queue()
+                } else {
+                    Queue::default()
+                };
+                let _ = tx.send(queue);
+                return Ok(());
+            }
+            SpircCommand::GetCluster(tx) => {
+                let _ = tx.send(self.cluster.clone());
+                return Ok(());
+            }
             _ if !self.connect_state.is_active() => {
                 warn!("SpircCommand::{:?} will be ignored while Not Active", cmd)
             }
@@ -699,6 +912,19 @@ impl SpircTask {
             SpircCommand::SetPosition(position) => self.handle_seek(position),
             SpircCommand::SetVolume(volume) => self.set_volume(volume),
             SpircCommand::Load(command) => self.handle_load(command, None).await?,
+            SpircCommand::SetSleepTimer(sleep_timer) => {
+                self.handle_set_sleep_timer(sleep_timer);
+                return Ok(());
+            }
+            SpircCommand::AddToQueue(uri) => self.connect_state.add_uri_to_queue(uri)?,
+            SpircCommand::RemoveFromQueue(uid) => self.connect_state.remove_from_queue(&uid)?,
+            SpircCommand::MoveInQueue { uid, position } => {
+                self.connect_state.move_in_queue(&uid, position)?
+            }
+            SpircCommand::ClearQueue => self.connect_state.clear_queue()?,
+            SpircCommand::TransferTo(device_id) => {
+                return self.handle_transfer_to(device_id).await
+            }
         };
 
         self.notify().await
@@ -737,7 +963,23 @@ impl SpircTask {
                     .repeat_track()
                     .then(|| self.connect_state.current_track(|t| t.uri.clone()));
 
-                self.handle_next(next_track)?
+                let sleep_timer_expired = self.sleep_timer_ends_with_current_track()
+                    || matches!(
+                        self.sleep_timer,
+                        Some(SpircSleepTimer {
+                            pause_at: Some(_),
+                            ..
+                        })
+                    );
+
+                // load the next track paused, the sleep timer expires on the next loop
+                self.skip_to_next(next_track, !sleep_timer_expired)?;
+
+                if sleep_timer_expired {
+                    if let Some(sleep_timer) = self.sleep_timer.as_mut() {
+                        sleep_timer.pause_at = Some(Instant::now());
+                    }
+                }
             }
             PlayerEvent::Loading { .. } => match self.play_status {
                 SpircPlayStatus::LoadingPlay { position_ms } => {
@@ -760,10 +1002,26 @@ impl SpircTask {
                 self.connect_state
                     .update_position(position_ms, self.now_ms())
             }
+            PlayerEvent::PlaybackSpeedChanged {
+                position_ms, speed, ..
+            } => {
+                trace!("==> PlaybackSpeedChanged");
+                self.playback_speed = speed;
+                let new_nominal_start_time = self.nominal_start_time(position_ms);
+                if let SpircPlayStatus::Playing {
+                    ref mut nominal_start_time,
+                    ..
+                } = self.play_status
+                {
+                    *nominal_start_time = new_nominal_start_time;
+                }
+                self.connect_state
+                    .update_position(position_ms, self.now_ms());
+            }
             PlayerEvent::Playing { position_ms, .. }
             | PlayerEvent::PositionCorrection { position_ms, .. } => {
                 trace!("==> Playing");
-                let new_nominal_start_time = self.now_ms() - position_ms as i64;
+                let new_nominal_start_time = self.nominal_start_time(position_ms);
                 match self.play_status {
                     SpircPlayStatus::Playing {
                         ref mut nominal_start_time,
@@ -859,6 +1117,8 @@ impl SpircTask {
             self.session.device_id()
         );
 
+        self.update_cluster(&cluster);
+
         let same_session = cluster.player_state.session_id == self.session.session_id()
             || cluster.player_state.session_id.is_empty();
         if !cluster.active_device_id.is_empty() || !same_session {
@@ -868,6 +1128,16 @@ impl SpircTask {
             );
             return Ok(());
         } else if cluster.transfer_data.is_empty() {
+            if std::mem::take(&mut self.restore_pending) {
+                match self.handle_restore_session() {
+                    Ok(true) => {
+                        self.handle_activate();
+                        return self.notify().await;
+                    }
+                    Ok(false) => (),
+                    Err(why) => warn!("couldn't restore the last playback: {why}"),
+                }
+            }
             debug!("got empty transfer state, do nothing");
             return Ok(());
         } else {
@@ -954,10 +1224,13 @@ impl SpircTask {
         );
 
         if let Some(cluster) = cluster_update.cluster.take() {
+            self.update_cluster(&cluster);
+
             let became_inactive = self.connect_state.is_active()
                 && cluster.active_device_id != self.session.device_id();
             if became_inactive {
                 info!("device became inactive");
+                self.clear_sleep_timer();
                 self.connect_state.became_inactive(&self.session).await?;
                 self.cec_client.deactivate_source();
                 self.handle_stop()
@@ -974,6 +1247,42 @@ impl SpircTask {
         Ok(())
     }
 
+    fn update_cluster(&mut self, cluster: &Cluster) {
+        let cluster = ConnectCluster::new(cluster);
+
+        if cluster.active_device_id != self.cluster.active_device_id {
+            let device_name = cluster.active_device().map(|d| d.name.clone());
+            debug!(
+                "active device changed to <{}>",
+                device_name.as_deref().unwrap_or_default()
+            );
+            self.player
+                .emit_active_device_changed_event(cluster.active_device_id.clone(), device_name);
+        }
+
+        self.cluster = cluster;
+    }
+
+    async fn handle_transfer_to(&mut self, device_id: String) -> Result<(), Error> {
+        if device_id == self.session.device_id() {
+            return Err(SpircError::TransferToSelf(device_id).into());
+        } else if self.cluster.device(&device_id).is_none() {
+            return Err(SpircError::UnknownDevice(device_id).into());
+        }
+
+        // the other device picks up the state we put last, so it has to be up to date
+        self.notify().await?;
+
+        info!("transferring playback to <{device_id}>");
+        self.session
+            .spclient()
+            .transfer_connect_state(&device_id)
+            .await?;
+
+        // we become inactive with the cluster update that follows the transfer
+        Ok(())
+    }
+
     async fn handle_connect_state_request(
         &mut self,
         (request, sender): RequestReply,
@@ -1100,18 +1409,9 @@ impl SpircTask {
             Some(ref uri) => uri.clone(),
         };
 
-        self.connect_state
-            .reset_context(ResetContext::WhenDifferent(&ctx_uri));
-
-        match self.connect_state.current_track_from_transfer(&transfer) {
-            Err(why) => warn!("didn't find initial track: {why}"),
-            Ok(track) => {
-                debug!("found initial track <{}>", track.uri);
-                self.connect_state.set_track(track)
-            }
-        };
-
-        let autoplay = self.connect_state.current_track(|t| t.is_autoplay());
+        let autoplay = self
+            .connect_state
+            .start_transfer(&ctx_uri, &mut transfer);
         if autoplay {
             ctx_uri = ctx_uri.replace("station:", "");
         }
@@ -1126,17 +1426,6 @@ impl SpircTask {
         ));
 
         let timestamp = self.now_ms();
-        let state = &mut self.connect_state;
-
-        state.set_active(true);
-        state.handle_initial_transfer(&mut transfer);
-
-        // adjust active context, so resolve knows for which context it should set up the state
-        state.active_context = if autoplay {
-            ContextType::Autoplay
-        } else {
-            ContextType::Default
-        };
 
         // update position if the track continued playing
         let transfer_timestamp = transfer.playback.timestamp.unwrap_or_default();
@@ -1170,12 +1459,73 @@ impl SpircTask {
         self.load_track(is_playing, position.try_into()?)
     }
 
+    /// continues with the playback saved by [SpircTask::save_session], returns whether there was one
+    fn handle_restore_session(&mut self) -> Result<bool, Error> {
+        let Some(transfer) = self
+            .session
+            .cache()
+            .and_then(|cache| cache.transfer_state(&self.session.username()))
+        else {
+            debug!("no playback to restore");
+            return Ok(false);
+        };
+
+        info!(
+            "restoring the last playback of <{}>",
+            transfer.current_session.context.uri()
+        );
+        self.handle_transfer(transfer)?;
+        Ok(true)
+    }
+
+    /// saves the playback when the track, context, options or queue changed, or with the
+    /// current position when `with_position` is set, e.g. on pausing, seeking or disconnecting
+    fn save_session(&mut self, with_position: bool) {
+        // a transfer is only complete after the context is resolved
+        if self.restore_session == RestoreSession::Off
+            || !self.connect_state.is_active()
+            || self.transfer_state.is_some()
+        {
+            return;
+        }
+
+        let (Some(cache), Some(state)) = (
+            self.session.cache().cloned(),
+            self.connect_state.to_transfer_state(),
+        ) else {
+            return;
+        };
+
+        let mut saved_session = state.clone();
+        if let Some(playback) = saved_session.playback.as_mut() {
+            playback.timestamp = None;
+            playback.position_as_of_timestamp = None;
+        }
+        if !with_position && self.saved_session.as_ref() == Some(&saved_session) {
+            return;
+        }
+        self.saved_session = Some(saved_session);
+
+        let username = self.session.username();
+        let writer = self.session_writer.clone();
+        let save = writer.latest.fetch_add(1, Ordering::AcqRel) + 1;
+        task::spawn_blocking(move || {
+            let _write = writer.write.lock().unwrap_or_else(PoisonError::into_inner);
+            if writer.latest.load(Ordering::Acquire) == save {
+                cache.save_transfer_state(&username, &state);
+            }
+        });
+    }
+
     async fn handle_disconnect(&mut self) -> Result<(), Error> {        
         self.context_resolver.clear();
+        self.clear_sleep_timer();
 
         self.play_status = SpircPlayStatus::Stopped {};
         self.connect_state
             .update_position_in_relation(self.now_ms());
+        self.save_volume();
+        self.save_session(true);
         self.notify().await?;
 
         self.connect_state.became_inactive(&self.session).await?;
@@ -1194,6 +1544,124 @@ impl SpircTask {
         Ok(())
     }
 
+    fn handle_set_sleep_timer(&mut self, sleep_timer: Option<SleepTimer>) {
+        // the playback is already fading out, so bring the volume back up again
+        let is_fading = matches!(
+            self.sleep_timer,
+            Some(SpircSleepTimer {
+                pause_at: Some(_),
+                ..
+            })
+        );
+        if is_fading && matches!(self.play_status, SpircPlayStatus::Playing { .. }) {
+            self.player.play();
+        }
+
+        self.sleep_timer = sleep_timer.map(|sleep_timer| SpircSleepTimer {
+            sleep_timer,
+            expires_at: match sleep_timer {
+                SleepTimer::Duration(duration) => Some(Instant::now() + duration),
+                SleepTimer::EndOfTrack | SleepTimer::EndOfContext => None,
+            },
+            pause_at: None,
+        });
+
+        match sleep_timer {
+            Some(sleep_timer) => info!("sleep timer set to {sleep_timer}"),
+            None => info!("sleep timer cancelled"),
+        }
+        self.player.emit_sleep_timer_changed_event(sleep_timer);
+    }
+
+    fn clear_sleep_timer(&mut self) {
+        if self.sleep_timer.take().is_some() {
+            self.player.emit_sleep_timer_changed_event(None);
+        }
+    }
+
+    fn sleep_timer_ends_with_current_track(&self) -> bool {
+        match self.sleep_timer {
+            Some(SpircSleepTimer {
+                sleep_timer: SleepTimer::EndOfTrack,
+                ..
+            }) => true,
+            Some(SpircSleepTimer {
+                sleep_timer: SleepTimer::EndOfContext,
+                ..
+            }) => {
+                !self.connect_state.repeat_context()
+                    && !self.connect_state.repeat_track()
+                    && !self.connect_state.has_next_tracks(None)
+            }
+            _ => false,
+        }
+    }
+
+    fn sleep_timer_remaining(&mut self) -> Option<Duration> {
+        if let Some(expires_at) = self.sleep_timer.as_ref()?.expires_at {
+            return Some(expires_at.saturating_duration_since(Instant::now()));
+        }
+
+        // the end of a track is only known while it is playing
+        if !self.sleep_timer_ends_with_current_track()
+            || !matches!(self.play_status, SpircPlayStatus::Playing { .. })
+        {
+            return None;
+        }
+
+        let duration = u32::try_from(self.connect_state.player().duration).ok()?;
+        let remaining = duration.saturating_sub(self.position());
+        Some(Duration::from_millis(remaining.into()).div_f64(self.playback_speed))
+    }
+
+    fn sleep_timer_status(&mut self) -> Option<SleepTimerStatus> {
+        let sleep_timer = self.sleep_timer.as_ref()?.sleep_timer;
+        let remaining = match self.sleep_timer.as_ref()?.pause_at {
+            Some(pause_at) => Some(pause_at.saturating_duration_since(Instant::now())),
+            None => self.sleep_timer_remaining(),
+        };
+
+        Some(SleepTimerStatus {
+            sleep_timer,
+            remaining,
+        })
+    }
+
+    /// the fade out starts ahead of the remaining time, so that the pause happens on time
+    fn sleep_timer_deadline(&mut self) -> Option<Instant> {
+        if let Some(pause_at) = self.sleep_timer.as_ref()?.pause_at {
+            return Some(pause_at);
+        }
+
+        let remaining = self.sleep_timer_remaining()?;
+        Some(Instant::now() + remaining.saturating_sub(self.sleep_timer_fade))
+    }
+
+    async fn handle_sleep_timer_deadline(&mut self) -> Result<(), Error> {
+        let fade = self.sleep_timer_fade;
+        let Some(sleep_timer) = self.sleep_timer.as_mut() else {
+            return Ok(());
+        };
+
+        let is_playing = matches!(self.play_status, SpircPlayStatus::Playing { .. });
+        if sleep_timer.pause_at.is_none() && is_playing && !fade.is_zero() {
+            debug!("sleep timer fading out the playback over {fade:?}");
+            sleep_timer.pause_at = Some(Instant::now() + fade);
+            self.player.pause_with_fade(fade);
+            return Ok(());
+        }
+
+        info!("sleep timer expired");
+        self.clear_sleep_timer();
+        self.handle_pause();
+
+        if self.sleep_timer_disconnect {
+            self.handle_disconnect().await
+        } else {
+            self.notify().await
+        }
+    }
+
     fn handle_stop(&mut self) {
         self.player.stop();
         self.connect_state.update_position(0, self.now_ms());
@@ -1359,7 +1827,7 @@ impl SpircTask {
                 self.connect_state
                     .update_position(position_ms, self.now_ms());
                 self.play_status = SpircPlayStatus::Playing {
-                    nominal_start_time: self.now_ms() - position_ms as i64,
+                    nominal_start_time: self.nominal_start_time(position_ms),
                     preloading_of_next_track_triggered,
                 };
             }
@@ -1395,13 +1863,14 @@ impl SpircTask {
                 preloading_of_next_track_triggered,
             } => {
                 self.player.pause();
-                let position_ms = (self.now_ms() - nominal_start_time) as u32;
+                let position_ms = self.position_since(nominal_start_time);
                 self.connect_state
                     .update_position(position_ms, self.now_ms());
                 self.play_status = SpircPlayStatus::Paused {
                     position_ms,
                     preloading_of_next_track_triggered,
                 };
+                self.save_session(true);
             }
             SpircPlayStatus::LoadingPlay { position_ms } => {
                 self.player.pause();
@@ -1421,7 +1890,7 @@ impl SpircTask {
         self.connect_state
             .update_position(position_ms, self.now_ms());
         self.player.seek(position_ms);
-        let now = self.now_ms();
+        let new_nominal_start_time = self.nominal_start_time(position_ms);
         match self.play_status {
             SpircPlayStatus::Stopped => (),
             SpircPlayStatus::LoadingPause {
@@ -1437,8 +1906,9 @@ impl SpircTask {
             SpircPlayStatus::Playing {
                 ref mut nominal_start_time,
                 ..
-            } => *nominal_start_time = now - position_ms as i64,
+            } => *nominal_start_time = new_nominal_start_time,
         };
+        self.save_session(true);
     }
 
     fn handle_preload_next_track(&mut self) {
@@ -1504,8 +1974,14 @@ impl SpircTask {
     }
 
     fn handle_next(&mut self, track_uri: Option<String>) -> Result<(), Error> {
-        let continue_playing = self.connect_state.is_playing();
+        self.skip_to_next(track_uri, self.connect_state.is_playing())
+    }
 
+    fn skip_to_next(
+        &mut self,
+        track_uri: Option<String>,
+        continue_playing: bool,
+    ) -> Result<(), Error> {
         let current_uri = self.connect_state.current_track(|t| &t.uri);
         let mut has_next_track =
             matches!(track_uri, Some(ref track_uri) if current_uri == track_uri);
@@ -1639,10 +2115,19 @@ impl SpircTask {
             | SpircPlayStatus::Paused { position_ms, .. } => position_ms,
             SpircPlayStatus::Playing {
                 nominal_start_time, ..
-            } => (self.now_ms() - nominal_start_time) as u32,
+            } => self.position_since(nominal_start_time),
         }
     }
 
+    /// the time playback would have started at to be at `position_ms` now
+    fn nominal_start_time(&self, position_ms: u32) -> i64 {
+        self.now_ms() - (position_ms as f64 / self.playback_speed) as i64
+    }
+
+    fn position_since(&self, nominal_start_time: i64) -> u32 {
+        ((self.now_ms() - nominal_start_time) as f64 * self.playback_speed) as u32
+    }
+
     fn load_track(&mut self, start_playing: bool, position_ms: u32) -> Result<(), Error> {
         if self.connect_state.current_track(MessageField::is_none) {
             debug!("current track is none, stopping playback");
@@ -1653,6 +2138,7 @@ impl SpircTask {
         let current_uri = self.connect_state.current_track(|t| &t.uri);
         let id = SpotifyId::from_uri(current_uri)?;
         self.player.load(id, start_playing, position_ms);
+        self.player.prefetch(self.connect_state.preview_next_tracks());
 
         self.connect_state
             .update_position(position_ms, self.now_ms());
@@ -1661,13 +2147,15 @@ impl SpircTask {
         } else {
             self.play_status = SpircPlayStatus::LoadingPause { position_ms };
         }
-        self.connect_state.set_status(&self.play_status);
+        self.connect_state
+            .set_status(&self.play_status, self.playback_speed);
 
         Ok(())
     }
 
     async fn notify(&mut self) -> Result<(), Error> {
-        self.connect_state.set_status(&self.play_status);
+        self.connect_state
+            .set_status(&self.play_status, self.playback_speed);
 
         if self.connect_state.is_playing() {
             self.connect_state
@@ -1675,6 +2163,7 @@ impl SpircTask {
         }
 
         self.connect_state.set_now(self.now_ms() as u64);
+        self.save_session(false);
 
         self.connect_state
             .send_state(&self.session)
@@ -1682,7 +2171,29 @@ impl SpircTask {
             .map(|_| ())
     }
 
+    fn limit_volume(&self, volume: u16) -> u16 {
+        volume.clamp(self.min_volume, self.max_volume)
+    }
+
+    fn user_volume(&self) -> Option<u16> {
+        if !self.volume_per_user {
+            return None;
+        }
+
+        self.session
+            .cache()?
+            .user_volume(&self.session.username())
+    }
+
     fn set_volume(&mut self, volume: u16) {
+        let limited_volume = self.limit_volume(volume);
+        if limited_volume != volume {
+            debug!("volume {volume} is limited to {limited_volume}");
+            // let the remote know that its volume wasn't applied as is
+            self.update_volume = true;
+        }
+        let volume = limited_volume;
+
         let old_volume = self.connect_state.device_info().volume;
         let new_volume = volume as u32;
         if old_volume != new_volume || self.mixer.volume() != volume {
@@ -1690,16 +2201,28 @@ impl SpircTask {
 
             self.connect_state.set_volume(new_volume);
             self.mixer.set_volume(volume);
-            if let Some(cache) = self.session.cache() {
-                cache.save_volume(volume)
-            }
+            self.unsaved_volume = Some(volume);
             if self.connect_state.is_active() {
                 self.player.emit_volume_changed_event(volume);
             }
         }
     }
 
+    fn save_volume(&mut self) {
+        let Some(volume) = self.unsaved_volume.take() else {
+            return;
+        };
+
+        if let Some(cache) = self.session.cache() {
+            cache.save_volume(volume);
+            if self.volume_per_user {
+                cache.save_user_volume(&self.session.username(), volume);
+            }
+        }
+    }
+
     fn update_volume(&mut self, volume: u16) {
+        let volume = self.limit_volume(volume);
         self.cec_client.set_volume(volume);
         self.set_volume(volume);
     }
@@ -31,6 +31,7 @@ use protobuf::{EnumOrUnknown, MessageField};
 use std::{
     collections::hash_map::DefaultHasher,
     hash::{Hash, Hasher},
+    str::FromStr,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
@@ -58,6 +59,8 @@ pub(super) enum StateError {
     UnsupportedLocalPlayBack,
     #[error("track uri <{0:?}> contains invalid characters")]
     InvalidTrackUri(Option<String>),
+    #[error("no queued track with the uid <{0}>")]
+    NotInQueue(String),
 }
 
 impl From<StateError> for Error {
@@ -70,6 +73,7 @@ impl From<StateError> for Error {
             | ContextHasNoTracks
             | InvalidTrackUri(_) => Error::failed_precondition(err),
             CurrentlyDisallowed { .. } | UnsupportedLocalPlayBack => Error::unavailable(err),
+            NotInQueue(_) => Error::not_found(err),
         }
     }
 }
@@ -89,6 +93,47 @@ pub struct ConnectConfig {
     pub disable_volume: bool,
     /// The steps in which the volume is incremented (default: 1024)
     pub volume_steps: u16,
+    /// The lowest volume that can be set, from any source (default: 0)
+    pub min_volume: u16,
+    /// The highest volume that can be set, from any source (default: 100%)
+    pub max_volume: u16,
+    /// Remembers the volume of each user in the cache and restores it when they connect,
+    /// instead of starting with the volume of whoever played last (default: false)
+    pub volume_per_user: bool,
+    /// The time over which the playback fades out before the sleep timer pauses it (default: 10s)
+    pub sleep_timer_fade: Duration,
+    /// Disconnects the connect device after the sleep timer paused the playback (default: false)
+    pub sleep_timer_disconnect: bool,
+    /// Whether the last playback is kept in the cache and restored later on
+    /// (default: [RestoreSession::Off])
+    pub restore_session: RestoreSession,
+}
+
+/// When the last playback of the user is restored from the cache
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreSession {
+    /// Nothing is saved or restored
+    #[default]
+    Off,
+    /// Becomes the active device with the last playback, paused, as soon as the device is
+    /// connected and no other device is playing, see [Spirc::new](crate::Spirc::new)
+    Auto,
+    /// Continues with the last playback, paused, when the device is activated locally,
+    /// see [Spirc::activate](crate::Spirc::activate)
+    OnActivate,
+}
+
+impl FromStr for RestoreSession {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "off" => Ok(Self::Off),
+            "auto" => Ok(Self::Auto),
+            "on-activate" => Ok(Self::OnActivate),
+            _ => Err(()),
+        }
+    }
 }
 
 impl Default for ConnectConfig {
@@ -100,6 +145,12 @@ impl Default for ConnectConfig {
             initial_volume: u16::MAX / 2,
             disable_volume: false,
             volume_steps: 1024,
+            min_volume: 0,
+            max_volume: u16::MAX,
+            volume_per_user: false,
+            sleep_timer_fade: Duration::from_secs(10),
+            sleep_timer_disconnect: false,
+            restore_session: RestoreSession::Off,
         }
     }
 }
@@ -312,7 +363,7 @@ impl ConnectState {
         self.player_mut().session_id = session_id;
     }
 
-    pub(crate) fn set_status(&mut self, status: &SpircPlayStatus) {
+    pub(crate) fn set_status(&mut self, status: &SpircPlayStatus, playback_speed: f64) {
         let player = self.player_mut();
         player.is_paused = matches!(
             status,
@@ -324,7 +375,7 @@ impl ConnectState {
         if player.is_paused {
             player.playback_speed = 0.;
         } else {
-            player.playback_speed = 1.;
+            player.playback_speed = playback_speed;
         }
 
         // desktop and mobile require all 'states' set to true, when we are paused,
@@ -429,7 +480,12 @@ impl ConnectState {
         let player = self.player_mut();
 
         let diff = timestamp - player.timestamp;
-        player.position_as_of_timestamp += diff;
+        let playback_speed = if player.playback_speed > 0. {
+            player.playback_speed
+        } else {
+            1.
+        };
+        player.position_as_of_timestamp += (diff as f64 * playback_speed) as i64;
 
         if log::max_level() >= LevelFilter::Debug {
             let pos = Duration::from_millis(player.position_as_of_timestamp as u64);
@@ -1,6 +1,7 @@
 use crate::{
     core::{Error, SpotifyId},
-    protocol::player::ProvidedTrack,
+    model::{Queue, QueueTrack},
+    protocol::{context_track::ContextTrack, player::ProvidedTrack},
     state::{
         context::ContextType,
         metadata::Metadata,
@@ -358,6 +359,19 @@ impl<'ct> ConnectState {
         SpotifyId::from_uri(next).ok()
     }
 
+    /// The upcoming tracks that can be played, in order, e.g. to download them ahead of time.
+    pub fn preview_next_tracks(&self) -> Vec<SpotifyId> {
+        if self.repeat_track() {
+            return Vec::new();
+        }
+
+        self.next_tracks()
+            .iter()
+            .filter(|t| !t.uid.starts_with(IDENTIFIER_DELIMITER) && !t.is_unavailable())
+            .filter_map(|t| SpotifyId::from_uri(&t.uri).ok())
+            .collect()
+    }
+
     pub fn has_next_tracks(&self, min: Option<usize>) -> bool {
         if let Some(min) = min {
             self.next_tracks().len() >= min
@@ -432,4 +446,217 @@ impl<'ct> ConnectState {
         }
         self.update_restrictions();
     }
+
+    pub fn add_uri_to_queue(&mut self, uri: String) -> Result<(), Error> {
+        let ctx_track = ContextTrack {
+            uri: Some(uri),
+            ..Default::default()
+        };
+
+        let track =
+            self.context_to_provided_track(&ctx_track, None, None, None, Some(Provider::Queue))?;
+        self.add_to_queue(track, true);
+
+        Ok(())
+    }
+
+    fn queue_position(&self, uid: &str) -> Result<usize, StateError> {
+        self.next_tracks()
+            .iter()
+            .position(|t| t.is_queue() && t.uid == uid)
+            .ok_or_else(|| StateError::NotInQueue(uid.to_string()))
+    }
+
+    pub fn remove_from_queue(&mut self, uid: &str) -> Result<(), Error> {
+        let pos = self.queue_position(uid)?;
+        let _ = self.next_tracks_mut().remove(pos);
+
+        // takes the place of the removed track from the context
+        self.fill_up_next_tracks()?;
+        self.update_restrictions();
+
+        Ok(())
+    }
+
+    /// moves a queued track to the given position among the queued tracks
+    pub fn move_in_queue(&mut self, uid: &str, position: usize) -> Result<(), Error> {
+        let pos = self.queue_position(uid)?;
+        let next_tracks = self.next_tracks_mut();
+        let track = next_tracks.remove(pos);
+
+        // queued tracks always come first
+        let queued = next_tracks.iter().take_while(|t| t.is_queue()).count();
+        next_tracks.insert(position.min(queued), track);
+
+        self.update_queue_revision();
+        self.update_restrictions();
+
+        Ok(())
+    }
+
+    pub fn clear_queue(&mut self) -> Result<(), Error> {
+        self.next_tracks_mut().retain(|t| !t.is_queue());
+
+        self.fill_up_next_tracks()?;
+        self.update_restrictions();
+
+        Ok(())
+    }
+
+    pub fn queue(&self) -> Queue {
+        let queue_tracks = |tracks: &[ProvidedTrack]| {
+            tracks
+                .iter()
+                .filter(|t| !t.uid.starts_with(IDENTIFIER_DELIMITER))
+                .map(|t| QueueTrack {
+                    uri: t.uri.clone(),
+                    uid: t.uid.clone(),
+                    queued: t.is_queue(),
+                })
+                .collect()
+        };
+
+        Queue {
+            prev_tracks: queue_tracks(self.prev_tracks()),
+            next_tracks: queue_tracks(self.next_tracks()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{spotify_id::SpotifyItemType, SessionConfig},
+        protocol::{context::Context, context_page::ContextPage},
+        state::ConnectConfig,
+    };
+    use spotipi_core::Session;
+
+    fn track_uri(n: u128) -> String {
+        SpotifyId {
+            id: n,
+            item_type: SpotifyItemType::Track,
+        }
+        .to_uri()
+        .unwrap()
+    }
+
+    // Playing the first of 100 tracks of a context, so that the next tracks are full.
+    fn playing_state() -> ConnectState {
+        let session = Session::new(SessionConfig::default(), None);
+        let mut state = ConnectState::new(ConnectConfig::default(), &session);
+
+        let context = Context {
+            uri: Some("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".to_string()),
+            pages: vec![ContextPage {
+                tracks: (0..100)
+                    .map(|n| ContextTrack {
+                        uri: Some(track_uri(n)),
+                        uid: Some(format!("c{n}")),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        state.update_context(context, ContextType::Default).unwrap();
+        state.reset_playback_to_position(Some(0)).unwrap();
+        assert_eq!(state.next_tracks().len(), SPOTIFY_MAX_NEXT_TRACKS_SIZE);
+
+        state
+    }
+
+    // The uids of the queued tracks, in the order they are played.
+    fn queued(state: &ConnectState) -> Vec<String> {
+        state
+            .queue()
+            .next_tracks
+            .into_iter()
+            .filter(|track| track.queued)
+            .map(|track| track.uid)
+            .collect()
+    }
+
+    fn assert_not_in_queue(result: Result<(), Error>, uid: &str) {
+        let err = result.unwrap_err();
+        assert!(matches!(
+            err.error.downcast_ref::<StateError>(),
+            Some(StateError::NotInQueue(not_queued)) if not_queued == uid
+        ));
+    }
+
+    #[tokio::test]
+    async fn queues_tracks_before_the_context() {
+        let mut state = playing_state();
+
+        for n in 100..103 {
+            state.add_uri_to_queue(track_uri(n)).unwrap();
+        }
+
+        assert_eq!(queued(&state), ["q0", "q1", "q2"]);
+        let queue = state.queue();
+        assert!(queue.next_tracks[..3].iter().all(|track| track.queued));
+        assert_eq!(queue.next_tracks[0].uri, track_uri(100));
+        assert_eq!(queue.next_tracks[3].uri, track_uri(1));
+        assert!(queue.prev_tracks.is_empty());
+        assert_eq!(state.next_tracks().len(), SPOTIFY_MAX_NEXT_TRACKS_SIZE);
+    }
+
+    #[tokio::test]
+    async fn removes_from_the_queue() {
+        let mut state = playing_state();
+        for n in 100..103 {
+            state.add_uri_to_queue(track_uri(n)).unwrap();
+        }
+
+        state.remove_from_queue("q1").unwrap();
+        assert_eq!(queued(&state), ["q0", "q2"]);
+        // the context fills up the next tracks again
+        assert_eq!(state.next_tracks().len(), SPOTIFY_MAX_NEXT_TRACKS_SIZE);
+
+        assert_not_in_queue(state.remove_from_queue("q1"), "q1");
+        // context tracks can't be removed through the queue
+        assert_not_in_queue(state.remove_from_queue("c1"), "c1");
+        assert_eq!(queued(&state), ["q0", "q2"]);
+    }
+
+    #[tokio::test]
+    async fn moves_within_the_queue() {
+        let mut state = playing_state();
+        for n in 100..103 {
+            state.add_uri_to_queue(track_uri(n)).unwrap();
+        }
+
+        state.move_in_queue("q2", 0).unwrap();
+        assert_eq!(queued(&state), ["q2", "q0", "q1"]);
+
+        state.move_in_queue("q2", 1).unwrap();
+        assert_eq!(queued(&state), ["q0", "q2", "q1"]);
+
+        // positions past the queue move to its end, never into the context
+        state.move_in_queue("q0", 50).unwrap();
+        assert_eq!(queued(&state), ["q2", "q1", "q0"]);
+        assert_eq!(state.queue().next_tracks[3].uid, "c1");
+
+        assert_not_in_queue(state.move_in_queue("q3", 0), "q3");
+        assert_not_in_queue(state.move_in_queue("c1", 0), "c1");
+        assert_eq!(queued(&state), ["q2", "q1", "q0"]);
+    }
+
+    #[tokio::test]
+    async fn clears_the_queue() {
+        let mut state = playing_state();
+        for n in 100..103 {
+            state.add_uri_to_queue(track_uri(n)).unwrap();
+        }
+
+        state.clear_queue().unwrap();
+        assert!(queued(&state).is_empty());
+        assert_eq!(state.queue().next_tracks[0].uid, "c1");
+        assert_eq!(state.next_tracks().len(), SPOTIFY_MAX_NEXT_TRACKS_SIZE);
+
+        assert_not_in_queue(state.remove_from_queue("q0"), "q0");
+    }
 }
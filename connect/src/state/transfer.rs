@@ -1,8 +1,12 @@
 use crate::{
     core::Error,
-    protocol::{player::ProvidedTrack, transfer_state::TransferState},
+    protocol::{
+        context::Context, context_player_options::ContextPlayerOptions,
+        context_track::ContextTrack, playback::Playback, player::ProvidedTrack, queue::Queue,
+        session::Session, transfer_state::TransferState,
+    },
     state::{
-        context::ContextType,
+        context::{ContextType, ResetContext},
         metadata::Metadata,
         provider::{IsProvider, Provider},
         {ConnectState, StateError},
@@ -35,6 +39,34 @@ impl ConnectState {
         )
     }
 
+    /// sets up the transferred playback until [ConnectState::finish_transfer] completes it with
+    /// the resolved context, returns whether the transferred track is from autoplay
+    pub fn start_transfer(&mut self, context_uri: &str, transfer: &mut TransferState) -> bool {
+        self.reset_context(ResetContext::WhenDifferent(context_uri));
+
+        match self.current_track_from_transfer(transfer) {
+            Err(why) => warn!("didn't find initial track: {why}"),
+            Ok(track) => {
+                debug!("found initial track <{}>", track.uri);
+                self.set_track(track)
+            }
+        };
+
+        let autoplay = self.current_track(|t| t.is_autoplay());
+
+        self.set_active(true);
+        self.handle_initial_transfer(transfer);
+
+        // adjust active context, so resolve knows for which context it should set up the state
+        self.active_context = if autoplay {
+            ContextType::Autoplay
+        } else {
+            ContextType::Default
+        };
+
+        autoplay
+    }
+
     /// handles the initially transferable data
     pub fn handle_initial_transfer(&mut self, transfer: &mut TransferState) {
         let current_context_metadata = self.context.as_ref().map(|c| c.metadata.clone());
@@ -173,4 +205,198 @@ impl ConnectState {
 
         Ok(())
     }
+
+    /// the essentials of the current playback, so that it can be continued later on,
+    /// paused, like a transfer from another device
+    pub fn to_transfer_state(&self) -> Option<TransferState> {
+        let player = self.player();
+        let track = player.track.as_ref()?;
+        if player.context_uri.is_empty() {
+            return None;
+        }
+
+        let to_context_track = |track: &ProvidedTrack| ContextTrack {
+            uri: Some(track.uri.clone()),
+            uid: Some(track.uid.clone()),
+            metadata: track.metadata.clone(),
+            ..Default::default()
+        };
+
+        let mut context = Context {
+            uri: Some(player.context_uri.clone()),
+            ..Default::default()
+        };
+        // keeps the order of the shuffled tracks, see handle_initial_transfer
+        if let Some(seed) = self.context.as_ref().and_then(|c| c.get_shuffle_seed()) {
+            context.set_shuffle_seed(seed);
+        }
+
+        let is_playing_queue = track.is_queue();
+        let (queued, next_tracks): (Vec<_>, Vec<_>) =
+            player.next_tracks.iter().partition(|t| t.is_queue());
+
+        // while playing the queue, the uid of the context track after it is expected
+        let current_uid = if is_playing_queue {
+            next_tracks.first().map(|t| t.uid.clone())
+        } else {
+            Some(track.uid.clone())
+        };
+
+        Some(TransferState {
+            options: MessageField::some(ContextPlayerOptions {
+                shuffling_context: Some(player.options.shuffling_context),
+                repeating_context: Some(player.options.repeating_context),
+                repeating_track: Some(player.options.repeating_track),
+                ..Default::default()
+            }),
+            playback: MessageField::some(Playback {
+                timestamp: Some(player.timestamp),
+                position_as_of_timestamp: Some(
+                    player
+                        .position_as_of_timestamp
+                        .try_into()
+                        .unwrap_or_default(),
+                ),
+                is_paused: Some(true),
+                current_track: MessageField::some(to_context_track(track)),
+                ..Default::default()
+            }),
+            current_session: MessageField::some(Session {
+                context: MessageField::some(context),
+                current_uid,
+                ..Default::default()
+            }),
+            queue: MessageField::some(Queue {
+                tracks: is_playing_queue
+                    .then_some(track)
+                    .into_iter()
+                    .chain(queued)
+                    .map(to_context_track)
+                    .collect(),
+                is_playing_queue: Some(is_playing_queue),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{spotify_id::SpotifyItemType, Session as CoreSession, SessionConfig, SpotifyId},
+        protocol::context_page::ContextPage,
+        state::ConnectConfig,
+    };
+
+    const CONTEXT_URI: &str = "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M";
+
+    fn track_uri(n: u128) -> String {
+        SpotifyId {
+            id: n,
+            item_type: SpotifyItemType::Track,
+        }
+        .to_uri()
+        .unwrap()
+    }
+
+    fn context() -> Context {
+        Context {
+            uri: Some(CONTEXT_URI.to_string()),
+            pages: vec![ContextPage {
+                tracks: (0..20)
+                    .map(|n| ContextTrack {
+                        uri: Some(track_uri(n)),
+                        uid: Some(format!("c{n}")),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn state() -> ConnectState {
+        let session = CoreSession::new(SessionConfig::default(), None);
+        ConnectState::new(ConnectConfig::default(), &session)
+    }
+
+    // Transfers the playback from `from` to a new state the way SpircTask::handle_transfer
+    // and the context resolver do.
+    fn transfer(from: &ConnectState) -> (ConnectState, TransferState) {
+        let saved = from.to_transfer_state().expect("something is playing");
+
+        let mut state = state();
+        let mut transfer = saved.clone();
+        assert!(!state.start_transfer(CONTEXT_URI, &mut transfer));
+        state
+            .update_context(context(), ContextType::Default)
+            .unwrap();
+        state.finish_transfer(transfer).unwrap();
+
+        (state, saved)
+    }
+
+    fn next_uids(state: &ConnectState, n: usize) -> Vec<String> {
+        state.next_tracks()[..n]
+            .iter()
+            .map(|track| track.uid.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn restores_the_track_options_and_queue() {
+        let mut from = state();
+        from.update_context(context(), ContextType::Default)
+            .unwrap();
+        from.reset_playback_to_position(Some(5)).unwrap();
+        from.add_uri_to_queue(track_uri(100)).unwrap();
+        from.add_uri_to_queue(track_uri(101)).unwrap();
+        from.set_repeat_context(true);
+        from.update_position(30_000, 1_000);
+
+        let (state, saved) = transfer(&from);
+
+        assert_eq!(saved.playback.position_as_of_timestamp, Some(30_000));
+        assert!(saved.playback.is_paused());
+        assert!(state.player().is_paused);
+
+        assert!(state.is_active());
+        assert_eq!(state.context_uri(), CONTEXT_URI);
+        assert_eq!(state.current_track(|t| t.uid.clone()), "c5");
+        assert!(state.repeat_context());
+        assert!(!state.shuffling_context());
+
+        let next = state.next_tracks();
+        assert!(next[..2].iter().all(|track| track.is_queue()));
+        assert_eq!(next[0].uri, track_uri(100));
+        assert_eq!(next[1].uri, track_uri(101));
+        assert_eq!(next_uids(&state, 4)[2..], ["c6", "c7"]);
+    }
+
+    #[tokio::test]
+    async fn restores_while_playing_the_queue() {
+        let mut from = state();
+        from.update_context(context(), ContextType::Default)
+            .unwrap();
+        from.reset_playback_to_position(Some(5)).unwrap();
+        from.add_uri_to_queue(track_uri(100)).unwrap();
+        from.add_uri_to_queue(track_uri(101)).unwrap();
+        from.next_track().unwrap();
+        assert!(from.current_track(|t| t.is_queue()));
+
+        let (state, saved) = transfer(&from);
+
+        assert_eq!(saved.queue.is_playing_queue, Some(true));
+        assert_eq!(state.current_track(|t| t.uri.clone()), track_uri(100));
+        assert!(state.current_track(|t| t.is_queue()));
+
+        let next = state.next_tracks();
+        assert!(next[0].is_queue());
+        assert_eq!(next[0].uri, track_uri(101));
+        // the context continues after the track that was interrupted by the queue
+        assert_eq!(next[1].uid, "c6");
+    }
 }
@@ -20,6 +20,13 @@ elif player_event == 'session_client_changed':
     json_dict['client_brand_name'] = os.environ['CLIENT_BRAND_NAME']
     json_dict['client_model_name'] = os.environ['CLIENT_MODEL_NAME']
 
+elif player_event == 'active_device_changed':
+    json_dict['device_id'] = os.environ['DEVICE_ID']
+    json_dict['device_name'] = os.environ['DEVICE_NAME']
+
+elif player_event == 'user_rejected':
+    json_dict['user_name'] = os.environ['USER_NAME']
+
 elif player_event == 'shuffle_changed':
     json_dict['shuffle'] = os.environ['SHUFFLE']
 
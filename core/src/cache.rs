@@ -10,9 +10,13 @@ use std::{
 
 use parking_lot::Mutex;
 use priority_queue::PriorityQueue;
+use protobuf::Message;
 use thiserror::Error;
 
-use crate::{authentication::Credentials, error::ErrorKind, Error, FileId};
+use crate::{
+    authentication::Credentials, error::ErrorKind, protocol::transfer_state::TransferState, Error,
+    FileId,
+};
 
 #[derive(Debug, Error)]
 pub enum CacheError {
@@ -200,6 +204,10 @@ impl FsSizeLimiter {
         self.limiter.lock().remove(file)
     }
 
+    fn size_limit(&self) -> u64 {
+        self.limiter.lock().size_limit
+    }
+
     fn prune_internal<F: FnMut() -> Option<PathBuf>>(mut pop: F) -> Result<(), Error> {
         let mut first = true;
         let mut count = 0;
@@ -247,11 +255,13 @@ impl FsSizeLimiter {
     }
 }
 
-/// A cache for volume, credentials and audio files.
+/// A cache for volume, credentials, playback states and audio files.
 #[derive(Clone)]
 pub struct Cache {
     credentials_location: Option<PathBuf>,
     volume_location: Option<PathBuf>,
+    user_volumes_location: Option<PathBuf>,
+    transfer_states_location: Option<PathBuf>,
     audio_location: Option<PathBuf>,
     size_limiter: Option<Arc<FsSizeLimiter>>,
 }
@@ -278,6 +288,12 @@ impl Cache {
         }
 
         let volume_location = volume_path.as_ref().map(|p| p.as_ref().join("volume"));
+        let user_volumes_location = volume_path
+            .as_ref()
+            .map(|p| p.as_ref().join("user_volumes.json"));
+        let transfer_states_location = volume_path
+            .as_ref()
+            .map(|p| p.as_ref().join("transfer_states"));
 
         if let Some(location) = &audio_path {
             fs::create_dir_all(location)?;
@@ -293,6 +309,8 @@ impl Cache {
         let cache = Cache {
             credentials_location,
             volume_location,
+            user_volumes_location,
+            transfer_states_location,
             audio_location,
             size_limiter,
         };
@@ -367,6 +385,104 @@ impl Cache {
         }
     }
 
+    fn user_volumes(&self) -> Result<HashMap<String, u16>, Error> {
+        let location = self
+            .user_volumes_location
+            .as_ref()
+            .ok_or(io::Error::from(io::ErrorKind::NotFound))?;
+
+        let mut file = File::open(location)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn user_volume(&self, username: &str) -> Option<u16> {
+        match self.user_volumes() {
+            Ok(volumes) => volumes.get(username).copied(),
+            Err(e) => {
+                if e.kind != ErrorKind::NotFound {
+                    warn!("Error reading user volumes from cache: {}", e);
+                }
+                None
+            }
+        }
+    }
+
+    pub fn save_user_volume(&self, username: &str, volume: u16) {
+        if let Some(ref location) = self.user_volumes_location {
+            // start over if the file got corrupted somehow
+            let mut volumes = self.user_volumes().unwrap_or_default();
+            volumes.insert(username.to_owned(), volume);
+
+            // replace the file as a whole, a crash halfway through writing it would
+            // otherwise lose the volumes of every user
+            let temp_location = location.with_extension("json.tmp");
+            let result = serde_json::to_string(&volumes)
+                .map_err(io::Error::from)
+                .and_then(|data| fs::write(&temp_location, data))
+                .and_then(|_| fs::rename(&temp_location, location));
+
+            if let Err(e) = result {
+                warn!("Cannot save user volume to cache: {}", e);
+            }
+        }
+    }
+
+    fn transfer_state_path(&self, username: &str) -> Option<PathBuf> {
+        // usernames are mostly alphanumeric, but they end up in a path after all
+        let file_name: String = username
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+
+        Some(self.transfer_states_location.as_ref()?.join(file_name))
+    }
+
+    /// Returns the last playback state of the user, as saved by [`Cache::save_transfer_state`].
+    pub fn transfer_state(&self, username: &str) -> Option<TransferState> {
+        let location = self.transfer_state_path(username)?;
+
+        let read = || -> Result<TransferState, Error> {
+            let contents = fs::read(&location)?;
+            Ok(TransferState::parse_from_bytes(&contents)?)
+        };
+
+        match read() {
+            Ok(state) => Some(state),
+            Err(e) => {
+                if e.kind != ErrorKind::NotFound {
+                    warn!("Error reading playback state from cache: {}", e);
+                }
+                None
+            }
+        }
+    }
+
+    pub fn save_transfer_state(&self, username: &str, state: &TransferState) {
+        if let Some(location) = self.transfer_state_path(username) {
+            let write = || -> Result<(), Error> {
+                if let Some(dir) = location.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&location, state.write_to_bytes()?)?;
+                Ok(())
+            };
+
+            if let Err(e) = write() {
+                warn!("Cannot save playback state to cache: {}", e);
+            }
+        }
+    }
+
+    /// Returns the size limit of the audio files, if any.
+    pub fn audio_size_limit(&self) -> Option<u64> {
+        self.size_limiter.as_deref().map(FsSizeLimiter::size_limit)
+    }
+
     pub fn file_path(&self, file: FileId) -> Option<PathBuf> {
         match file.to_base16() {
             Ok(name) => self.audio_location.as_ref().map(|location| {
@@ -481,4 +597,47 @@ mod test {
         assert!(limiter.remove(Path::new("c")));
         assert!(!limiter.exceeds_limit());
     }
+
+    #[test]
+    fn test_transfer_state() {
+        let dir = std::env::temp_dir().join(format!("spotipi-cache-test-{}", std::process::id()));
+        let cache = Cache::new(None, Some(&dir), None, None).unwrap();
+
+        assert!(cache.transfer_state("user").is_none());
+
+        let mut state = TransferState::new();
+        state.current_session.mut_or_insert_default().current_uid = Some("uid".to_string());
+        cache.save_transfer_state("../user", &state);
+
+        assert!(dir.join("transfer_states").join("___user").exists());
+        assert_eq!(cache.transfer_state("../user"), Some(state));
+        assert!(cache.transfer_state("user").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_user_volumes() {
+        let dir =
+            std::env::temp_dir().join(format!("spotipi-cache-volume-test-{}", std::process::id()));
+        let cache = Cache::new(None, Some(&dir), None, None).unwrap();
+
+        assert_eq!(cache.user_volume("user"), None);
+
+        cache.save_user_volume("user", 100);
+        cache.save_user_volume("other", 200);
+        cache.save_user_volume("user", 300);
+
+        assert_eq!(cache.user_volume("user"), Some(300));
+        assert_eq!(cache.user_volume("other"), Some(200));
+        assert!(!dir.join("user_volumes.json.tmp").exists());
+
+        // a corrupted file is started over
+        fs::write(dir.join("user_volumes.json"), "{\"user\": 1").unwrap();
+        assert_eq!(cache.user_volume("user"), None);
+        cache.save_user_volume("other", 400);
+        assert_eq!(cache.user_volume("other"), Some(400));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
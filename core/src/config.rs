@@ -1,4 +1,4 @@
-use std::{fmt, path::PathBuf, str::FromStr};
+use std::{fmt, path::PathBuf, str::FromStr, time::Duration};
 
 use spotipi_protocol::devices::DeviceType as ProtoDeviceType;
 use url::Url;
@@ -29,6 +29,7 @@ pub struct SessionConfig {
     pub ap_port: Option<u16>,
     pub tmp_dir: PathBuf,
     pub autoplay: Option<bool>,
+    pub audio_fetch_params: AudioFetchParams,
 }
 
 impl SessionConfig {
@@ -48,6 +49,7 @@ impl SessionConfig {
             ap_port: None,
             tmp_dir: std::env::temp_dir(),
             autoplay: None,
+            audio_fetch_params: AudioFetchParams::default(),
         }
     }
 }
@@ -58,6 +60,66 @@ impl Default for SessionConfig {
     }
 }
 
+/// How audio files are downloaded, set per session so that sessions on different networks
+/// can buffer differently.
+#[derive(Clone, Debug)]
+pub struct AudioFetchParams {
+    /// The minimum size of a block that is requested from the Spotify servers in one request.
+    /// This is the block size that is typically requested while doing a `seek()` on a file.
+    /// The Symphonia decoder requires this to be a power of 2 and > 32 kB.
+    /// Note: smaller requests can happen if part of the block is downloaded already.
+    pub minimum_download_size: usize,
+
+    /// The minimum network throughput that we expect. Together with the minimum download size,
+    /// this will determine the time we will wait for a response.
+    pub minimum_throughput: usize,
+
+    /// The ping time that is used for calculations before a ping time was actually measured.
+    pub initial_ping_time_estimate: Duration,
+
+    /// If the measured ping time to the Spotify server is larger than this value, it is capped
+    /// to avoid run-away block sizes and pre-fetching.
+    pub maximum_assumed_ping_time: Duration,
+
+    /// Before playback starts, this many seconds of data must be present.
+    /// Note: the calculations are done using the nominal bitrate of the file. The actual amount
+    /// of audio data may be larger or smaller.
+    pub read_ahead_before_playback: Duration,
+
+    /// While playing back, this many seconds of data ahead of the current read position are
+    /// requested.
+    /// Note: the calculations are done using the nominal bitrate of the file. The actual amount
+    /// of audio data may be larger or smaller.
+    pub read_ahead_during_playback: Duration,
+
+    /// If the amount of data that is pending (requested but not received) is less than a certain amount,
+    /// data is pre-fetched in addition to the read ahead settings above. The threshold for requesting more
+    /// data is calculated as `<pending bytes> < PREFETCH_THRESHOLD_FACTOR * <ping time> * <nominal data rate>`
+    pub prefetch_threshold_factor: f32,
+
+    /// The time we will wait to obtain status updates on downloading.
+    pub download_timeout: Duration,
+}
+
+impl Default for AudioFetchParams {
+    fn default() -> Self {
+        let minimum_download_size = 64 * 1024;
+        let minimum_throughput = 8 * 1024;
+        Self {
+            minimum_download_size,
+            minimum_throughput,
+            initial_ping_time_estimate: Duration::from_millis(500),
+            maximum_assumed_ping_time: Duration::from_millis(1500),
+            read_ahead_before_playback: Duration::from_secs(1),
+            read_ahead_during_playback: Duration::from_secs(5),
+            prefetch_threshold_factor: 4.0,
+            download_timeout: Duration::from_secs(
+                (minimum_download_size / minimum_throughput) as u64,
+            ),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Hash, PartialOrd, Ord, PartialEq, Eq, Default)]
 pub enum DeviceType {
     Unknown = 0,
@@ -173,3 +235,29 @@ impl From<DeviceType> for ProtoDeviceType {
         }
     }
 }
+
+impl From<ProtoDeviceType> for DeviceType {
+    fn from(value: ProtoDeviceType) -> Self {
+        match value {
+            ProtoDeviceType::UNKNOWN => DeviceType::Unknown,
+            ProtoDeviceType::COMPUTER => DeviceType::Computer,
+            ProtoDeviceType::TABLET => DeviceType::Tablet,
+            ProtoDeviceType::SMARTPHONE => DeviceType::Smartphone,
+            ProtoDeviceType::SPEAKER => DeviceType::Speaker,
+            ProtoDeviceType::TV => DeviceType::Tv,
+            ProtoDeviceType::AVR => DeviceType::Avr,
+            ProtoDeviceType::STB => DeviceType::Stb,
+            ProtoDeviceType::AUDIO_DONGLE => DeviceType::AudioDongle,
+            ProtoDeviceType::GAME_CONSOLE => DeviceType::GameConsole,
+            ProtoDeviceType::CAST_VIDEO => DeviceType::CastVideo,
+            ProtoDeviceType::CAST_AUDIO => DeviceType::CastAudio,
+            ProtoDeviceType::AUTOMOBILE => DeviceType::Automobile,
+            ProtoDeviceType::SMARTWATCH => DeviceType::Smartwatch,
+            ProtoDeviceType::CHROMEBOOK => DeviceType::Chromebook,
+            ProtoDeviceType::UNKNOWN_SPOTIFY => DeviceType::UnknownSpotify,
+            ProtoDeviceType::CAR_THING => DeviceType::CarThing,
+            ProtoDeviceType::OBSERVER => DeviceType::Observer,
+            ProtoDeviceType::HOME_THING => DeviceType::HomeThing,
+        }
+    }
+}
@@ -565,6 +565,18 @@ impl SpClient {
             .await
     }
 
+    pub async fn transfer_connect_state(&self, to_device_id: &str) -> SpClientResult {
+        let endpoint = transfer_endpoint(self.session().device_id(), to_device_id)?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION_ID, self.session().connection_id().parse()?);
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let body = r#"{"transfer_options":{"restore_paused":"restore"}}"#;
+        self.request_as_json(&Method::POST, &endpoint, Some(headers), Some(body))
+            .await
+    }
+
     pub async fn get_metadata(&self, scope: &str, id: &SpotifyId) -> SpClientResult {
         let endpoint = format!("/metadata/4/{}/{}", scope, id.to_base16()?);
         self.request(&Method::GET, &endpoint, None, None).await
@@ -881,3 +893,65 @@ impl SpClient {
         self.request(&Method::GET, &endpoint, None, None).await
     }
 }
+
+// The id to transfer to comes from other devices of the cluster, and ends up in the path.
+fn transfer_endpoint(from_device_id: &str, to_device_id: &str) -> Result<String, Error> {
+    if to_device_id.is_empty() || to_device_id.bytes().all(|byte| byte == b'.') {
+        return Err(Error::invalid_argument(format!(
+            "invalid device id to transfer to: \"{to_device_id}\""
+        )));
+    }
+
+    Ok(format!(
+        "/connect-state/v1/connect/transfer/from/{}/to/{}",
+        path_segment(from_device_id),
+        path_segment(to_device_id)
+    ))
+}
+
+// Percent-encodes everything but the unreserved characters, so that the value stays within
+// one segment of a path.
+fn path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_segments_are_percent_encoded() {
+        let device_id = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(path_segment(device_id), device_id);
+        assert_eq!(path_segment("a-b.c_d~e"), "a-b.c_d~e");
+
+        assert_eq!(path_segment("../../me"), "..%2F..%2Fme");
+        assert_eq!(path_segment("id?notify=false#x"), "id%3Fnotify%3Dfalse%23x");
+        assert_eq!(path_segment("a b%"), "a%20b%25");
+        assert_eq!(path_segment("ü"), "%C3%BC");
+    }
+
+    #[test]
+    fn transfers_to_the_device_only() {
+        assert_eq!(
+            transfer_endpoint("from", "to").unwrap(),
+            "/connect-state/v1/connect/transfer/from/from/to/to"
+        );
+        assert_eq!(
+            transfer_endpoint("from", "../../devices/x").unwrap(),
+            "/connect-state/v1/connect/transfer/from/from/to/..%2F..%2Fdevices%2Fx"
+        );
+
+        for to_device_id in ["", ".", ".."] {
+            assert!(transfer_endpoint("from", to_device_id).is_err());
+        }
+    }
+}
@@ -14,6 +14,7 @@ use std::{
     borrow::Cow,
     error::Error as StdError,
     pin::Pin,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 
@@ -114,6 +115,55 @@ pub fn find(name: Option<&str>) -> Result<DnsSdServiceBuilder, Error> {
     }
 }
 
+/// Keeps other users from taking over the device through discovery while a user holds it.
+///
+/// Enabled by [`Builder::lock`]. Users that select the device while it is locked are rejected
+/// with a failed login, unless they are allowed or hold the lock themselves. Transfers through
+/// Spotify Connect can only come from devices of the user that is logged in, so they aren't
+/// affected.
+#[derive(Clone, Default)]
+pub struct UserLock {
+    holder: Arc<Mutex<Option<String>>>,
+}
+
+impl UserLock {
+    /// Locks the device to the given user, until [`UserLock::unlock`] is called.
+    pub fn lock(&self, username: impl Into<String>) {
+        let username = username.into();
+        match self.holder.lock() {
+            Ok(mut holder) if holder.as_ref() != Some(&username) => {
+                log::info!("Device locked by user {:?}", username);
+                *holder = Some(username);
+            }
+            Ok(_) => (),
+            Err(_) => log::warn!("user lock corrupted; write failed"),
+        }
+    }
+
+    /// Lets any user connect again.
+    pub fn unlock(&self) {
+        match self.holder.lock() {
+            Ok(mut holder) => {
+                if let Some(username) = holder.take() {
+                    log::info!("Device unlocked by user {:?}", username);
+                }
+            }
+            Err(_) => log::warn!("user lock corrupted; write failed"),
+        }
+    }
+
+    /// The user that holds the lock, if any.
+    pub fn holder(&self) -> Option<String> {
+        match self.holder.lock() {
+            Ok(holder) => holder.clone(),
+            Err(_) => {
+                log::warn!("user lock corrupted; read failed");
+                None
+            }
+        }
+    }
+}
+
 /// Makes this device visible to Spotify clients in the local network.
 ///
 /// `Discovery` implements the [`Stream`] trait. Every time this device
@@ -126,6 +176,10 @@ pub struct Discovery {
     svc: DnsSdHandle,
 
     event_rx: mpsc::UnboundedReceiver<DiscoveryEvent>,
+
+    rejected_rx: Option<mpsc::UnboundedReceiver<String>>,
+
+    user_lock: Option<UserLock>,
 }
 
 /// A builder for [`Discovery`].
@@ -442,6 +496,9 @@ impl Builder {
                 is_group: false,
                 device_id: device_id.into(),
                 client_id: client_id.into(),
+                lock_allowed_users: None,
+                allowed_users: None,
+                denied_users: vec![],
             },
             port: 0,
             zeroconf_ip: vec![],
@@ -486,6 +543,26 @@ impl Builder {
         self
     }
 
+    /// Enables the [`UserLock`], with the users that may connect even while it is held by
+    /// another one. Disabled by default.
+    pub fn lock(mut self, allowed_users: Vec<String>) -> Self {
+        self.server_config.lock_allowed_users = Some(allowed_users);
+        self
+    }
+
+    /// Sets the only users that may connect, by their username. Any user may by default.
+    pub fn allowed_users(mut self, allowed_users: Vec<String>) -> Self {
+        self.server_config.allowed_users = Some(allowed_users);
+        self
+    }
+
+    /// Sets the users that may never connect, by their username. This takes precedence
+    /// over [`Builder::allowed_users`]. Empty by default.
+    pub fn denied_users(mut self, denied_users: Vec<String>) -> Self {
+        self.server_config.denied_users = denied_users;
+        self
+    }
+
     /// Sets up the [`Discovery`] instance.
     ///
     /// # Errors
@@ -495,9 +572,19 @@ impl Builder {
         let zeroconf_ip = self.zeroconf_ip;
 
         let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (rejected_tx, rejected_rx) = mpsc::unbounded_channel();
+
+        let user_lock = UserLock::default();
+        let is_locking = self.server_config.lock_allowed_users.is_some();
 
         let mut port = self.port;
-        let server = DiscoveryServer::new(self.server_config, &mut port, event_tx.clone())?;
+        let server = DiscoveryServer::new(
+            self.server_config,
+            &mut port,
+            event_tx.clone(),
+            rejected_tx,
+            user_lock.clone(),
+        )?;
 
         let launch_svc = self.zeroconf_backend.unwrap_or(find(None)?);
         let svc = launch_svc(name, zeroconf_ip, port, event_tx)?;
@@ -505,6 +592,8 @@ impl Builder {
             server,
             svc,
             event_rx,
+            rejected_rx: Some(rejected_rx),
+            user_lock: is_locking.then_some(user_lock),
         })
     }
 }
@@ -520,6 +609,18 @@ impl Discovery {
         Self::builder(device_id, client_id).launch()
     }
 
+    /// The lock that keeps other users out, if enabled by [`Builder::lock`].
+    pub fn user_lock(&self) -> Option<UserLock> {
+        self.user_lock.clone()
+    }
+
+    /// Yields the username of every user that was rejected, because they aren't permitted by
+    /// [`Builder::allowed_users`] and [`Builder::denied_users`] or are locked out by the
+    /// [`UserLock`]. Can only be taken once.
+    pub fn rejected_users(&mut self) -> Option<mpsc::UnboundedReceiver<String>> {
+        self.rejected_rx.take()
+    }
+
     pub async fn shutdown(self) {
         tokio::join!(self.server.shutdown(), self.svc.shutdown(),);
     }
@@ -15,12 +15,12 @@ use http_body_util::{BodyExt, Full};
 use hyper::{body::Incoming, Method, Request, Response, StatusCode};
 
 use hyper_util::{rt::TokioIo, server::graceful::GracefulShutdown};
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
 use serde_json::json;
 use sha1::{Digest, Sha1};
 use tokio::sync::{mpsc, oneshot};
 
-use super::{DiscoveryError, DiscoveryEvent};
+use super::{DiscoveryError, DiscoveryEvent, UserLock};
 
 use crate::{
     core::config::DeviceType,
@@ -37,6 +37,12 @@ pub struct Config {
     pub device_id: String,
     pub is_group: bool,
     pub client_id: String,
+    /// the users that may connect while the lock is held, `None` if locking is disabled
+    pub lock_allowed_users: Option<Vec<String>>,
+    /// the only users that may connect, `None` if any user may
+    pub allowed_users: Option<Vec<String>>,
+    /// the users that may never connect, even if they are allowed
+    pub denied_users: Vec<String>,
 }
 
 struct RequestHandler {
@@ -44,19 +50,63 @@ struct RequestHandler {
     username: Mutex<Option<String>>,
     keys: DhLocalKeys,
     event_tx: mpsc::UnboundedSender<DiscoveryEvent>,
+    rejected_tx: mpsc::UnboundedSender<String>,
+    user_lock: UserLock,
 }
 
 impl RequestHandler {
-    fn new(config: Config, event_tx: mpsc::UnboundedSender<DiscoveryEvent>) -> Self {
+    fn new(
+        config: Config,
+        event_tx: mpsc::UnboundedSender<DiscoveryEvent>,
+        rejected_tx: mpsc::UnboundedSender<String>,
+        user_lock: UserLock,
+    ) -> Self {
         Self {
             config,
             username: Mutex::new(None),
             keys: DhLocalKeys::random(&mut rand::thread_rng()),
             event_tx,
+            rejected_tx,
+            user_lock,
+        }
+    }
+
+    /// whether the user may connect at all, according to the allowed and denied users
+    fn is_permitted(&self, username: &str) -> bool {
+        if self.config.denied_users.iter().any(|u| u == username) {
+            return false;
+        }
+
+        match self.config.allowed_users.as_ref() {
+            Some(allowed_users) => allowed_users.iter().any(|u| u == username),
+            None => true,
+        }
+    }
+
+    /// the user that holds the lock, `None` if locking is disabled
+    fn lock_holder(&self) -> Option<String> {
+        self.config.lock_allowed_users.as_ref()?;
+        self.user_lock.holder()
+    }
+
+    /// whether the user is kept from connecting, because another one holds the lock
+    fn is_locked_out(&self, username: &str) -> bool {
+        let Some(allowed_users) = self.config.lock_allowed_users.as_ref() else {
+            return false;
+        };
+
+        match self.lock_holder() {
+            Some(holder) => holder != username && !allowed_users.iter().any(|u| u == username),
+            None => false,
         }
     }
 
     fn active_user(&self) -> String {
+        // the lock is also held by users that didn't connect through discovery
+        if let Some(holder) = self.user_lock.holder() {
+            return holder;
+        }
+
         if let Ok(maybe_username) = self.username.lock() {
             maybe_username.clone().unwrap_or(String::new())
         } else {
@@ -192,6 +242,22 @@ impl RequestHandler {
 
         let credentials = Credentials::with_blob(username, decrypted, &self.config.device_id)?;
 
+        if !self.is_permitted(username) {
+            warn!(
+                "Rejected user {:?}, the user is not permitted to use this device",
+                username
+            );
+            return Ok(self.reject_user(username));
+        }
+
+        if self.is_locked_out(username) {
+            info!(
+                "Rejected user {:?}, the device is locked by another user",
+                username
+            );
+            return Ok(self.reject_user(username));
+        }
+
         {
             let maybe_username = self.username.lock();
             self.event_tx
@@ -214,6 +280,22 @@ impl RequestHandler {
         Ok(Response::new(Full::new(body)))
     }
 
+    fn reject_user(&self, username: &str) -> Response<Full<Bytes>> {
+        // nobody might be listening for rejected users
+        let _ = self.rejected_tx.send(String::from(username));
+
+        // there is no status for a refused user, so clients show a failed login
+        let result = json!({
+            "status": 105,
+            "spotifyError": 0,
+            "statusString": "ERROR-LOGIN-FAILED"
+        });
+
+        let body = result.to_string();
+        let body = Bytes::from(body);
+        Response::new(Full::new(body))
+    }
+
     fn not_found(&self) -> Response<Full<Bytes>> {
         let mut res = Response::default();
         *res.status_mut() = StatusCode::NOT_FOUND;
@@ -265,8 +347,10 @@ impl DiscoveryServer {
         config: Config,
         port: &mut u16,
         event_tx: mpsc::UnboundedSender<DiscoveryEvent>,
+        rejected_tx: mpsc::UnboundedSender<String>,
+        user_lock: UserLock,
     ) -> Result<Self, Error> {
-        let discovery = RequestHandler::new(config, event_tx);
+        let discovery = RequestHandler::new(config, event_tx, rejected_tx, user_lock);
         let address = if cfg!(windows) {
             SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), *port)
         } else {
@@ -356,3 +440,125 @@ impl DiscoveryServer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_handler(config: Config) -> (RequestHandler, mpsc::UnboundedReceiver<String>) {
+        let (event_tx, _) = mpsc::unbounded_channel();
+        let (rejected_tx, rejected_rx) = mpsc::unbounded_channel();
+        let handler = RequestHandler::new(config, event_tx, rejected_tx, UserLock::default());
+        (handler, rejected_rx)
+    }
+
+    fn config() -> Config {
+        Config {
+            name: "spotipi".into(),
+            device_type: DeviceType::default(),
+            device_id: String::from("device"),
+            is_group: false,
+            client_id: String::from("client"),
+            lock_allowed_users: None,
+            allowed_users: None,
+            denied_users: Vec::new(),
+        }
+    }
+
+    fn locking_config() -> Config {
+        Config {
+            lock_allowed_users: Some(vec![String::from("guest")]),
+            ..config()
+        }
+    }
+
+    async fn get_info(handler: &RequestHandler) -> serde_json::Value {
+        let body = handler
+            .handle_get_info()
+            .into_body()
+            .collect()
+            .await
+            .unwrap();
+        serde_json::from_slice(&body.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn locks_out_other_users() {
+        let (handler, _) = request_handler(locking_config());
+        handler.user_lock.lock("owner");
+
+        assert!(handler.is_locked_out("other"));
+        assert!(!handler.is_locked_out("owner"));
+        assert!(!handler.is_locked_out("guest"));
+    }
+
+    #[test]
+    fn locks_out_nobody_while_unlocked() {
+        let (handler, _) = request_handler(locking_config());
+        assert!(!handler.is_locked_out("other"));
+
+        handler.user_lock.lock("owner");
+        handler.user_lock.unlock();
+        assert!(!handler.is_locked_out("other"));
+
+        // nor while locking is disabled, even if the lock is taken
+        let (handler, _) = request_handler(config());
+        handler.user_lock.lock("owner");
+        assert!(!handler.is_locked_out("other"));
+    }
+
+    #[test]
+    fn permits_the_allowed_users_only() {
+        let (handler, _) = request_handler(Config {
+            allowed_users: Some(vec![String::from("friend"), String::from("foe")]),
+            denied_users: vec![String::from("foe")],
+            ..config()
+        });
+
+        assert!(handler.is_permitted("friend"));
+        assert!(!handler.is_permitted("stranger"));
+        // denied wins over allowed
+        assert!(!handler.is_permitted("foe"));
+    }
+
+    #[test]
+    fn permits_anyone_not_denied_without_allowed_users() {
+        let (handler, _) = request_handler(Config {
+            denied_users: vec![String::from("foe")],
+            ..config()
+        });
+
+        assert!(handler.is_permitted("stranger"));
+        assert!(!handler.is_permitted("foe"));
+    }
+
+    #[test]
+    fn reports_rejected_users() {
+        let (handler, mut rejected_rx) = request_handler(config());
+
+        handler.reject_user("foe");
+        assert_eq!(rejected_rx.try_recv().unwrap(), "foe");
+        assert!(rejected_rx.try_recv().is_err());
+
+        // and keeps rejecting when nobody is listening
+        drop(rejected_rx);
+        handler.reject_user("foe");
+    }
+
+    #[tokio::test]
+    async fn reports_the_lock() {
+        let (handler, _) = request_handler(locking_config());
+
+        let info = get_info(&handler).await;
+        assert_eq!(info["status"], 101);
+        assert_eq!(info["statusString"], "OK");
+        assert_eq!(info["activeUser"], "");
+
+        // the zeroconf spec knows no status for it, the lock shows as the active user
+        handler.user_lock.lock("owner");
+        let info = get_info(&handler).await;
+        assert_eq!(info["status"], 101);
+        assert_eq!(info["statusString"], "OK");
+        assert_eq!(info["activeUser"], "owner");
+    }
+}
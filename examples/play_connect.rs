@@ -63,8 +63,16 @@ async fn main() -> Result<(), Error> {
         move || sink_builder(None, audio_format),
     );
 
-    let (spirc, spirc_task) =
-        Spirc::new(connect_config, session.clone(), credentials, player, mixer, cec_client).await?;
+    let (spirc, spirc_task) = Spirc::new(
+        connect_config,
+        session.clone(),
+        credentials,
+        player,
+        mixer,
+        cec_client,
+        true,
+    )
+    .await?;
 
     // these calls can be seen as "queued"
     spirc.activate()?;
@@ -102,6 +102,7 @@ impl Default for NormalisationType {
 pub enum NormalisationMethod {
     Basic,
     Dynamic,
+    Limiter,
 }
 
 impl FromStr for NormalisationMethod {
@@ -110,6 +111,7 @@ impl FromStr for NormalisationMethod {
         match s.to_lowercase().as_ref() {
             "basic" => Ok(Self::Basic),
             "dynamic" => Ok(Self::Dynamic),
+            "limiter" => Ok(Self::Limiter),
             _ => Err(()),
         }
     }
@@ -135,6 +137,30 @@ pub struct PlayerConfig {
     pub normalisation_attack_cf: f64,
     pub normalisation_release_cf: f64,
     pub normalisation_knee_db: f64,
+    // the true peak the limiter method doesn't let the output exceed
+    pub normalisation_ceiling_dbtp: f64,
+
+    // fades applied when playback starts, resumes, pauses, stops or is interrupted by a new track,
+    // a zero duration disables the fade
+    pub fade_in: Duration,
+    pub fade_out: Duration,
+
+    // pitch preserving playback speed from 0.5 to 3.0, by default only applied to episodes
+    pub playback_speed: f64,
+    pub playback_speed_episodes_only: bool,
+
+    // how often the levels are published to the subscribers of `Player::get_level_meter_channel`,
+    // and as `PlayerEvent::Levels` if enabled
+    pub level_meter_interval: Duration,
+    pub level_meter_events: bool,
+
+    // skip leading and trailing silence below the threshold, except in between the tracks
+    // of an album that flow into each other
+    pub trim_silence: bool,
+    pub trim_silence_threshold_db: f64,
+
+    // how many of the upcoming tracks are downloaded completely into the cache
+    pub prefetch_tracks: usize,
 
     // pass function pointers so they can be lazily instantiated *after* spawning a thread
     // (thereby circumventing Send bounds that they might not satisfy)
@@ -154,6 +180,16 @@ impl Default for PlayerConfig {
             normalisation_attack_cf: duration_to_coefficient(Duration::from_millis(5)),
             normalisation_release_cf: duration_to_coefficient(Duration::from_millis(100)),
             normalisation_knee_db: 5.0,
+            normalisation_ceiling_dbtp: -1.0,
+            fade_in: Duration::ZERO,
+            fade_out: Duration::ZERO,
+            playback_speed: 1.0,
+            playback_speed_episodes_only: true,
+            level_meter_interval: Duration::from_millis(100),
+            level_meter_events: false,
+            trim_silence: false,
+            trim_silence_threshold_db: -60.0,
+            prefetch_tracks: 0,
             passthrough: false,
             ditherer: Some(mk_ditherer::<TriangularDitherer>),
         }
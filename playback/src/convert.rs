@@ -1,4 +1,5 @@
 use crate::dither::{Ditherer, DithererBuilder};
+use crate::NUM_CHANNELS;
 use zerocopy::{Immutable, IntoBytes};
 
 #[derive(Immutable, IntoBytes, Copy, Clone, Debug)]
@@ -19,6 +20,8 @@ impl i24 {
 
 pub struct Converter {
     ditherer: Option<Box<dyn Ditherer>>,
+    // channel of the next sample, as packets hold whole frames every conversion starts at 0
+    channel: usize,
 }
 
 impl Converter {
@@ -29,9 +32,13 @@ impl Converter {
                 info!("Converting with ditherer: {}", ditherer.name());
                 Self {
                     ditherer: Some(ditherer),
+                    channel: 0,
                 }
             }
-            None => Self { ditherer: None },
+            None => Self {
+                ditherer: None,
+                channel: 0,
+            },
         }
     }
 
@@ -58,8 +65,16 @@ impl Converter {
 
         // Casting float to integer rounds towards zero by default, i.e. it
         // truncates, and that generates larger error than rounding to nearest.
+        let channel = self.channel;
+        self.channel = (channel + 1) % NUM_CHANNELS as usize;
+
         match self.ditherer.as_mut() {
-            Some(d) => (sample * factor + d.noise()).round(),
+            Some(d) => {
+                let dithered = sample * factor + d.noise(channel);
+                let rounded = dithered.round();
+                d.feedback(channel, rounded - dithered);
+                rounded
+            }
             None => (sample * factor).round(),
         }
     }
@@ -84,6 +99,7 @@ impl Converter {
     }
 
     pub fn f64_to_s32(&mut self, samples: &[f64]) -> Vec<i32> {
+        self.channel = 0;
         samples
             .iter()
             .map(|sample| self.scale(*sample, Self::SCALE_S32) as i32)
@@ -92,6 +108,7 @@ impl Converter {
 
     // S24 is 24-bit PCM packed in an upper 32-bit word
     pub fn f64_to_s24(&mut self, samples: &[f64]) -> Vec<i32> {
+        self.channel = 0;
         samples
             .iter()
             .map(|sample| self.clamping_scale(*sample, Self::SCALE_S24) as i32)
@@ -100,6 +117,7 @@ impl Converter {
 
     // S24_3 is 24-bit PCM in a 3-byte array
     pub fn f64_to_s24_3(&mut self, samples: &[f64]) -> Vec<i24> {
+        self.channel = 0;
         samples
             .iter()
             .map(|sample| i24::from_s24(self.clamping_scale(*sample, Self::SCALE_S24) as i32))
@@ -107,6 +125,7 @@ impl Converter {
     }
 
     pub fn f64_to_s16(&mut self, samples: &[f64]) -> Vec<i16> {
+        self.channel = 0;
         samples
             .iter()
             .map(|sample| self.scale(*sample, Self::SCALE_S16) as i16)
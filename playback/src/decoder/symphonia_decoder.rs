@@ -31,13 +31,12 @@ pub struct SymphoniaDecoder {
 }
 
 impl SymphoniaDecoder {
-    pub fn new<R>(input: R, file_format: AudioFileFormat) -> DecoderResult<Self>
+    // The buffer length must be a power of 2 and > 32 kB, such as the minimum download size.
+    pub fn new<R>(input: R, file_format: AudioFileFormat, buffer_len: usize) -> DecoderResult<Self>
     where
         R: MediaSource + 'static,
     {
-        let mss_opts = MediaSourceStreamOptions {
-            buffer_len: spotipi_audio::AudioFetchParams::get().minimum_download_size,
-        };
+        let mss_opts = MediaSourceStreamOptions { buffer_len };
         let mss = MediaSourceStream::new(Box::new(input), mss_opts);
 
         let format_opts = FormatOptions {
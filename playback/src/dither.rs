@@ -23,6 +23,13 @@ use crate::NUM_CHANNELS;
 //    so unless you have a multibit / R2R DAC, or otherwise know what you are
 //    doing, this is not for you.
 //
+//  * For S16 there are noise shaping ditherers, which feed the requantization
+//    error back through a filter that moves the noise to where the ear is
+//    least sensitive. The Lipshitz curve is the gentler one, the F-weighted
+//    curve lowers the noise in the most sensitive range further but puts more
+//    of it above 15 kHz. They are designed for 44.1 kHz, the same DAC caveat
+//    as for high-passed dithering applies.
+//
 //  * Don't dither or shape noise on S32 or F32. On F32 it's not supported
 //    anyway (there are no integer conversions and so no rounding errors) and
 //    on S32 the noise level is so far down that it is simply inaudible even
@@ -33,7 +40,11 @@ pub trait Ditherer {
     where
         Self: Sized;
     fn name(&self) -> &'static str;
-    fn noise(&mut self) -> f64;
+    // The noise to add to the next sample of `channel` before rounding it.
+    fn noise(&mut self, channel: usize) -> f64;
+    // The error of rounding that sample, i.e. the rounded value minus the sample
+    // plus noise, for ditherers that shape their noise through error feedback.
+    fn feedback(&mut self, _channel: usize, _error: f64) {}
 }
 
 impl fmt::Display for dyn Ditherer {
@@ -64,7 +75,7 @@ impl Ditherer for TriangularDitherer {
         Self::NAME
     }
 
-    fn noise(&mut self) -> f64 {
+    fn noise(&mut self, _channel: usize) -> f64 {
         self.distribution.sample(&mut self.cached_rng)
     }
 }
@@ -91,7 +102,7 @@ impl Ditherer for GaussianDitherer {
         Self::NAME
     }
 
-    fn noise(&mut self) -> f64 {
+    fn noise(&mut self, _channel: usize) -> f64 {
         self.distribution.sample(&mut self.cached_rng)
     }
 }
@@ -101,7 +112,6 @@ impl GaussianDitherer {
 }
 
 pub struct HighPassDitherer {
-    active_channel: usize,
     previous_noises: [f64; NUM_CHANNELS as usize],
     cached_rng: SmallRng,
     distribution: Uniform<f64>,
@@ -110,7 +120,6 @@ pub struct HighPassDitherer {
 impl Ditherer for HighPassDitherer {
     fn new() -> Self {
         Self {
-            active_channel: 0,
             previous_noises: [0.0; NUM_CHANNELS as usize],
             cached_rng: create_rng(),
             distribution: Uniform::new_inclusive(-0.5, 0.5), // 1 LSB +/- 1 LSB (previous) = 2 LSB
@@ -121,11 +130,10 @@ impl Ditherer for HighPassDitherer {
         Self::NAME
     }
 
-    fn noise(&mut self) -> f64 {
+    fn noise(&mut self, channel: usize) -> f64 {
         let new_noise = self.distribution.sample(&mut self.cached_rng);
-        let high_passed_noise = new_noise - self.previous_noises[self.active_channel];
-        self.previous_noises[self.active_channel] = new_noise;
-        self.active_channel ^= 1;
+        let high_passed_noise = new_noise - self.previous_noises[channel];
+        self.previous_noises[channel] = new_noise;
         high_passed_noise
     }
 }
@@ -134,6 +142,108 @@ impl HighPassDitherer {
     pub const NAME: &'static str = "tpdf_hp";
 }
 
+// Error feedback filters for 44.1 kHz, as in SoX.
+//
+// After: Wannamaker, R.A. (1992). Psychoacoustically Optimal Noise Shaping.
+// Journal of the Audio Engineering Society, 40, 611-620.
+const LIPSHITZ_44100: &[f64] = &[2.033, -2.165, 1.959, -1.590, 0.6149];
+const F_WEIGHTED_44100: &[f64] = &[
+    2.412, -3.370, 3.937, -4.174, 3.353, -2.205, 1.281, -0.569, 0.0847,
+];
+const MAX_ORDER: usize = 9;
+
+// Triangular dither and the filtered requantization errors of the previous samples,
+// such that the total error is the requantization error shaped by `1 - H(z)`.
+struct NoiseShaper {
+    coefficients: &'static [f64],
+    // the errors of each channel, most recent first
+    errors: [[f64; MAX_ORDER]; NUM_CHANNELS as usize],
+    dithers: [f64; NUM_CHANNELS as usize],
+    cached_rng: SmallRng,
+    distribution: Triangular<f64>,
+}
+
+impl NoiseShaper {
+    fn new(coefficients: &'static [f64]) -> Self {
+        Self {
+            coefficients,
+            errors: [[0.0; MAX_ORDER]; NUM_CHANNELS as usize],
+            dithers: [0.0; NUM_CHANNELS as usize],
+            cached_rng: create_rng(),
+            distribution: Triangular::new(-1.0, 1.0, 0.0).unwrap(),
+        }
+    }
+
+    fn noise(&mut self, channel: usize) -> f64 {
+        let dither = self.distribution.sample(&mut self.cached_rng);
+        self.dithers[channel] = dither;
+
+        let shaped_error: f64 = self
+            .coefficients
+            .iter()
+            .zip(&self.errors[channel])
+            .map(|(coefficient, error)| coefficient * error)
+            .sum();
+
+        dither - shaped_error
+    }
+
+    fn feedback(&mut self, channel: usize, error: f64) {
+        // the requantization error includes the dither
+        let errors = &mut self.errors[channel];
+        errors.rotate_right(1);
+        errors[0] = error + self.dithers[channel];
+    }
+}
+
+pub struct LipshitzDitherer(NoiseShaper);
+
+impl Ditherer for LipshitzDitherer {
+    fn new() -> Self {
+        Self(NoiseShaper::new(LIPSHITZ_44100))
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn noise(&mut self, channel: usize) -> f64 {
+        self.0.noise(channel)
+    }
+
+    fn feedback(&mut self, channel: usize, error: f64) {
+        self.0.feedback(channel, error)
+    }
+}
+
+impl LipshitzDitherer {
+    pub const NAME: &'static str = "tpdf_lipshitz";
+}
+
+pub struct FWeightedDitherer(NoiseShaper);
+
+impl Ditherer for FWeightedDitherer {
+    fn new() -> Self {
+        Self(NoiseShaper::new(F_WEIGHTED_44100))
+    }
+
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn noise(&mut self, channel: usize) -> f64 {
+        self.0.noise(channel)
+    }
+
+    fn feedback(&mut self, channel: usize, error: f64) {
+        self.0.feedback(channel, error)
+    }
+}
+
+impl FWeightedDitherer {
+    pub const NAME: &'static str = "tpdf_fweighted";
+}
+
 pub fn mk_ditherer<D: Ditherer + 'static>() -> Box<dyn Ditherer> {
     Box::new(D::new())
 }
@@ -145,6 +255,83 @@ pub fn find_ditherer(name: Option<String>) -> Option<DithererBuilder> {
         Some(TriangularDitherer::NAME) => Some(mk_ditherer::<TriangularDitherer>),
         Some(GaussianDitherer::NAME) => Some(mk_ditherer::<GaussianDitherer>),
         Some(HighPassDitherer::NAME) => Some(mk_ditherer::<HighPassDitherer>),
+        Some(LipshitzDitherer::NAME) => Some(mk_ditherer::<LipshitzDitherer>),
+        Some(FWeightedDitherer::NAME) => Some(mk_ditherer::<FWeightedDitherer>),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rounds a sample scaled to the target bit depth, as `Converter::scale` does.
+    fn requantize(shaper: &mut NoiseShaper, channel: usize, sample: f64) -> f64 {
+        let dithered = sample + shaper.noise(channel);
+        let rounded = dithered.round();
+        shaper.feedback(channel, rounded - dithered);
+        rounded
+    }
+
+    fn sine(i: usize) -> f64 {
+        1000.0 * (i as f64 * 0.0123).sin() + 0.3
+    }
+
+    #[test]
+    fn shapes_the_requantization_error() {
+        for coefficients in [LIPSHITZ_44100, F_WEIGHTED_44100] {
+            let mut shaper = NoiseShaper::new(coefficients);
+
+            // the requantization error including the dither is at most half a step away
+            // from the dither, so the total error is bounded by the filter
+            let bound = 1.5 * (1.0 + coefficients.iter().map(|c| c.abs()).sum::<f64>());
+
+            let mut errors = Vec::new();
+            for i in 0..100_000 {
+                let sample = sine(i);
+                let total = requantize(&mut shaper, 0, sample) - sample;
+                assert!(total.abs() <= bound, "{total} out of {bound}");
+
+                // the total error is that of requantization, filtered by `1 - H(z)`
+                let error = shaper.errors[0][0];
+                assert!(error.abs() <= 1.5);
+                let shaped: f64 = coefficients
+                    .iter()
+                    .zip(errors.iter().rev())
+                    .map(|(coefficient, error)| coefficient * error)
+                    .sum();
+                assert!((total - (error - shaped)).abs() < 1e-9);
+                errors.push(error);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_channels_apart() {
+        let mut shaper = NoiseShaper::new(F_WEIGHTED_44100);
+
+        // a loud left channel and digital silence on the right, interleaved
+        let mut errors = [Vec::new(), Vec::new()];
+        for i in 0..1000 {
+            for (channel, sample) in [sine(i), 0.0].into_iter().enumerate() {
+                requantize(&mut shaper, channel, sample);
+                errors[channel].push(shaper.errors[channel][0]);
+            }
+        }
+
+        // each channel has its own errors, most recent first
+        for (channel, errors) in errors.iter().enumerate() {
+            let recent: Vec<f64> = errors.iter().rev().take(MAX_ORDER).copied().collect();
+            assert_eq!(shaper.errors[channel].to_vec(), recent);
+        }
+
+        // and the silent channel never gets the shaped error of the loud one
+        let noise = shaper.noise(1) - shaper.dithers[1];
+        let shaped: f64 = F_WEIGHTED_44100
+            .iter()
+            .zip(errors[1].iter().rev())
+            .map(|(coefficient, error)| coefficient * error)
+            .sum();
+        assert!((noise + shaped).abs() < 1e-9);
+    }
+}
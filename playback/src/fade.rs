@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use crate::{NUM_CHANNELS, SAMPLE_RATE};
+
+// Linear fades of the output, frame by frame, and what to do once a fade-out has completed.
+//
+// A fade-in starts from wherever the gain is, e.g. from silence after a pause or from
+// halfway through a fade-out that was cancelled, and a fade-out likewise.
+pub struct Fader<A> {
+    gain: f64,
+    // gain change per frame, 0 while not fading
+    step: f64,
+    // deferred until the fade-out has completed
+    pub action: Option<A>,
+}
+
+impl<A> Default for Fader<A> {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            step: 0.0,
+            action: None,
+        }
+    }
+}
+
+impl<A> Fader<A> {
+    fn frames(fade: Duration) -> f64 {
+        (fade.as_secs_f64() * SAMPLE_RATE as f64).max(1.0)
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.step != 0.0
+    }
+
+    // Ramps up the gain over `fade`, from silence if `from_silence` is set.
+    pub fn fade_in(&mut self, fade: Duration, from_silence: bool) {
+        if fade.is_zero() {
+            self.gain = 1.0;
+            self.step = 0.0;
+            return;
+        }
+
+        if from_silence {
+            self.gain = 0.0;
+        }
+
+        if self.gain < 1.0 {
+            self.step = 1.0 / Self::frames(fade);
+        }
+    }
+
+    // Starts ramping down the gain over `fade` and defers `action` until it is silent.
+    // Returns `false` if there is nothing to fade, in which case the caller should
+    // carry out the action immediately.
+    pub fn fade_out(&mut self, action: A, fade: Duration) -> bool {
+        if fade.is_zero() || self.gain <= 0.0 {
+            return false;
+        }
+
+        self.step = -self.gain / Self::frames(fade);
+        self.action = Some(action);
+        true
+    }
+
+    // Applies the fade to the interleaved samples. Returns `true` when a fade-out has
+    // run its course and its action is due.
+    pub fn apply(&mut self, samples: &mut [f64]) -> bool {
+        for frame in samples.chunks_mut(NUM_CHANNELS as usize) {
+            self.gain = (self.gain + self.step).clamp(0.0, 1.0);
+
+            for sample in frame.iter_mut() {
+                *sample *= self.gain;
+            }
+        }
+
+        if self.gain <= 0.0 || self.gain >= 1.0 {
+            self.step = 0.0;
+        }
+
+        self.gain <= 0.0 && self.action.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNELS: usize = NUM_CHANNELS as usize;
+    // 100 frames
+    const FADE: Duration = Duration::from_micros(100_000_000 / SAMPLE_RATE as u64 + 1);
+
+    #[derive(Debug, PartialEq)]
+    enum Action {
+        Pause,
+        Stop,
+    }
+
+    // The gains of `frames` frames of a constant signal, after checking that both
+    // channels of each frame got the same.
+    fn gains(fader: &mut Fader<Action>, frames: usize) -> (Vec<f64>, bool) {
+        let mut samples = vec![1.0; frames * CHANNELS];
+        let completed = fader.apply(&mut samples);
+
+        let gains = samples
+            .chunks(CHANNELS)
+            .map(|frame| {
+                assert!(frame.iter().all(|gain| *gain == frame[0]));
+                frame[0]
+            })
+            .collect();
+        (gains, completed)
+    }
+
+    #[test]
+    fn fades_in_from_silence() {
+        let mut fader = Fader::default();
+        fader.fade_in(FADE, true);
+        assert!(fader.is_fading());
+
+        let (gains, completed) = gains(&mut fader, 150);
+        assert!(!completed);
+        assert!(gains
+            .windows(2)
+            .all(|gains| gains[1] > gains[0] || gains[1] == 1.0));
+        assert!((gains[0] - 0.01).abs() < 1e-3);
+        assert!(gains[98] < 1.0);
+        assert!(gains[100..].iter().all(|gain| *gain == 1.0));
+        assert!(!fader.is_fading());
+    }
+
+    #[test]
+    fn fades_in_immediately_without_a_duration() {
+        let mut fader = Fader::<Action>::default();
+        fader.fade_out(Action::Pause, FADE);
+        gains(&mut fader, 50);
+
+        fader.fade_in(Duration::ZERO, true);
+        assert!(!fader.is_fading());
+        assert!(gains(&mut fader, 10).0.iter().all(|gain| *gain == 1.0));
+    }
+
+    #[test]
+    fn fades_out_and_then_acts() {
+        let mut fader = Fader::default();
+        assert!(fader.fade_out(Action::Stop, FADE));
+
+        let (gains, completed) = gains(&mut fader, 99);
+        assert!(!completed);
+        assert!(gains.windows(2).all(|gains| gains[1] < gains[0]));
+        assert_eq!(fader.action, Some(Action::Stop));
+
+        let (gains, completed) = self::gains(&mut fader, 10);
+        assert!(completed);
+        assert!(gains[0] < 0.01);
+        assert!(gains[1..].iter().all(|gain| *gain == 0.0));
+        assert_eq!(fader.action.take(), Some(Action::Stop));
+    }
+
+    #[test]
+    fn acts_immediately_when_there_is_nothing_to_fade() {
+        let mut fader = Fader::default();
+        assert!(!fader.fade_out(Action::Pause, Duration::ZERO));
+        assert_eq!(fader.action, None);
+
+        assert!(fader.fade_out(Action::Pause, FADE));
+        assert!(gains(&mut fader, 200).1);
+        fader.action = None;
+
+        // already silent
+        assert!(!fader.fade_out(Action::Stop, FADE));
+        assert_eq!(fader.action, None);
+    }
+
+    #[test]
+    fn resumes_a_cancelled_fade_out_from_where_it_got_to() {
+        let mut fader = Fader::default();
+        fader.fade_out(Action::Pause, FADE);
+        let (gains, _) = gains(&mut fader, 50);
+        let halfway = gains[49];
+        assert!((halfway - 0.5).abs() < 0.02);
+
+        fader.action = None;
+        fader.fade_in(FADE, false);
+        let (gains, completed) = self::gains(&mut fader, 100);
+        assert!(!completed);
+        assert!(gains[0] > halfway);
+        assert!(gains.windows(2).all(|gains| gains[1] >= gains[0]));
+        assert_eq!(gains[99], 1.0);
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::VecDeque, f64::consts::PI, ops::RangeInclusive, time::Duration};
+
+use crate::{player::ratio_to_db, NUM_CHANNELS, SAMPLE_RATE};
+
+pub const VALID_LEVEL_METER_INTERVAL_RANGE: RangeInclusive<u64> = 20..=10_000;
+
+// Levels below this are reported as this, so that silence stays a finite number.
+pub const LEVEL_METER_FLOOR_DB: f64 = -120.0;
+
+const CHANNELS: usize = NUM_CHANNELS as usize;
+
+// Loudness is integrated over non-overlapping blocks of 100 ms, of which the momentary
+// loudness averages the last 4 (400 ms) and the short-term loudness the last 30 (3 s).
+const BLOCK_FRAMES: usize = SAMPLE_RATE as usize / 10;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+// Offset of the loudness scale, so that a full scale 997 Hz sine in one channel reads
+// -3.01 LUFS.
+const LOUDNESS_OFFSET: f64 = -0.691;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelLevels {
+    // unweighted levels since the previous reading, in dBFS
+    pub rms_db: f64,
+    pub peak_db: f64,
+    // K-weighted loudness of this channel alone
+    pub momentary_lufs: f64,
+    pub short_term_lufs: f64,
+}
+
+impl Default for ChannelLevels {
+    fn default() -> Self {
+        Self {
+            rms_db: LEVEL_METER_FLOOR_DB,
+            peak_db: LEVEL_METER_FLOOR_DB,
+            momentary_lufs: LEVEL_METER_FLOOR_DB,
+            short_term_lufs: LEVEL_METER_FLOOR_DB,
+        }
+    }
+}
+
+// A reading of the levels of what is being sent to the sink, after volume and fades.
+// A default reading is silence, which is what is published when playback pauses or stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelMeterReading {
+    pub channels: [ChannelLevels; CHANNELS],
+    // K-weighted loudness of all channels combined, as per ITU-R BS.1770
+    pub momentary_lufs: f64,
+    pub short_term_lufs: f64,
+}
+
+impl Default for LevelMeterReading {
+    fn default() -> Self {
+        Self {
+            channels: [ChannelLevels::default(); CHANNELS],
+            momentary_lufs: LEVEL_METER_FLOOR_DB,
+            short_term_lufs: LEVEL_METER_FLOOR_DB,
+        }
+    }
+}
+
+// Second order IIR filter, transposed direct form II.
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        let output = self.b[0] * sample + self.z[0];
+        self.z[0] = self.b[1] * sample - self.a[0] * output + self.z[1];
+        self.z[1] = self.b[2] * sample - self.a[1] * output;
+        output
+    }
+}
+
+// The K-weighting of ITU-R BS.1770: a high shelf modelling the acoustic effect of the
+// head, followed by a high pass. The coefficients are derived for the sample rate from
+// the analog prototypes, as the standard only lists them for 48 kHz.
+#[derive(Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl Default for KWeighting {
+    fn default() -> Self {
+        let sample_rate = SAMPLE_RATE as f64;
+
+        let f0 = 1_681.974_450_955_533;
+        let gain_db = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10.0_f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self { shelf, high_pass }
+    }
+}
+
+impl KWeighting {
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+pub struct LevelMeter {
+    interval_frames: usize,
+
+    // unweighted, since the previous reading
+    frames: usize,
+    sum_squares: [f64; CHANNELS],
+    peak: [f64; CHANNELS],
+
+    // K-weighted, per 100 ms block
+    weighting: [KWeighting; CHANNELS],
+    block_frames: usize,
+    block_sum_squares: [f64; CHANNELS],
+    // mean squares of the last `SHORT_TERM_BLOCKS` completed blocks, newest last
+    blocks: VecDeque<[f64; CHANNELS]>,
+}
+
+impl LevelMeter {
+    pub fn new(interval: Duration) -> Self {
+        let interval_frames = (interval.as_secs_f64() * SAMPLE_RATE as f64).round() as usize;
+
+        Self {
+            interval_frames: interval_frames.max(1),
+            frames: 0,
+            sum_squares: [0.0; CHANNELS],
+            peak: [0.0; CHANNELS],
+            weighting: [KWeighting::default(); CHANNELS],
+            block_frames: 0,
+            block_sum_squares: [0.0; CHANNELS],
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+        }
+    }
+
+    // Forgets everything measured so far, e.g. when playback pauses or stops.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.sum_squares = [0.0; CHANNELS];
+        self.peak = [0.0; CHANNELS];
+        self.weighting = [KWeighting::default(); CHANNELS];
+        self.block_frames = 0;
+        self.block_sum_squares = [0.0; CHANNELS];
+        self.blocks.clear();
+    }
+
+    // Measures interleaved samples and returns a reading whenever an interval has
+    // elapsed, the latest one if the samples span several intervals.
+    pub fn process(&mut self, samples: &[f64]) -> Option<LevelMeterReading> {
+        let mut reading = None;
+
+        for frame in samples.chunks_exact(CHANNELS) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                self.sum_squares[channel] += sample * sample;
+                self.peak[channel] = self.peak[channel].max(sample.abs());
+
+                let weighted = self.weighting[channel].process(sample);
+                self.block_sum_squares[channel] += weighted * weighted;
+            }
+
+            self.block_frames += 1;
+            if self.block_frames == BLOCK_FRAMES {
+                if self.blocks.len() == SHORT_TERM_BLOCKS {
+                    self.blocks.pop_front();
+                }
+                self.blocks
+                    .push_back(self.block_sum_squares.map(|sum| sum / BLOCK_FRAMES as f64));
+
+                self.block_frames = 0;
+                self.block_sum_squares = [0.0; CHANNELS];
+            }
+
+            self.frames += 1;
+            if self.frames == self.interval_frames {
+                reading = Some(self.reading());
+
+                self.frames = 0;
+                self.sum_squares = [0.0; CHANNELS];
+                self.peak = [0.0; CHANNELS];
+            }
+        }
+
+        reading
+    }
+
+    // Mean squares of the last `count` blocks, or of as many as there are.
+    fn mean_squares(&self, count: usize) -> [f64; CHANNELS] {
+        let count = count.min(self.blocks.len());
+        let mut mean_squares = [0.0; CHANNELS];
+
+        if count > 0 {
+            for block in self.blocks.iter().rev().take(count) {
+                for (mean_square, block_mean_square) in mean_squares.iter_mut().zip(block) {
+                    *mean_square += block_mean_square / count as f64;
+                }
+            }
+        }
+
+        mean_squares
+    }
+
+    fn reading(&self) -> LevelMeterReading {
+        let momentary = self.mean_squares(MOMENTARY_BLOCKS);
+        let short_term = self.mean_squares(SHORT_TERM_BLOCKS);
+
+        let mut channels = [ChannelLevels::default(); CHANNELS];
+        for (channel, levels) in channels.iter_mut().enumerate() {
+            *levels = ChannelLevels {
+                rms_db: to_db((self.sum_squares[channel] / self.frames as f64).sqrt()),
+                peak_db: to_db(self.peak[channel]),
+                momentary_lufs: to_lufs(momentary[channel]),
+                short_term_lufs: to_lufs(short_term[channel]),
+            };
+        }
+
+        // The channel weights of BS.1770 are all 1.0 for left and right.
+        LevelMeterReading {
+            channels,
+            momentary_lufs: to_lufs(momentary.iter().sum()),
+            short_term_lufs: to_lufs(short_term.iter().sum()),
+        }
+    }
+}
+
+fn to_db(ratio: f64) -> f64 {
+    if ratio > 0.0 {
+        ratio_to_db(ratio).max(LEVEL_METER_FLOOR_DB)
+    } else {
+        LEVEL_METER_FLOOR_DB
+    }
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    if mean_square > 0.0 {
+        (LOUDNESS_OFFSET + 10.0 * mean_square.log10()).max(LEVEL_METER_FLOOR_DB)
+    } else {
+        LEVEL_METER_FLOOR_DB
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Interleaved stereo with a sine of the given amplitude on the left and silence on the right.
+    fn sine(frequency: f64, amplitude: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .flat_map(|i| {
+                let phase = 2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64;
+                [amplitude * phase.sin(), 0.0]
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn measures_a_full_scale_sine() {
+        let mut meter = LevelMeter::new(Duration::from_secs(3));
+        let reading = meter
+            .process(&sine(997.0, 1.0, 3 * SAMPLE_RATE as usize))
+            .unwrap();
+
+        let left = reading.channels[0];
+        assert_close(left.rms_db, -3.01, 0.01);
+        assert_close(left.peak_db, 0.0, 0.01);
+        // the reference point of the loudness scale
+        assert_close(left.momentary_lufs, -3.01, 0.05);
+        assert_close(left.short_term_lufs, -3.01, 0.05);
+        assert_close(reading.momentary_lufs, -3.01, 0.05);
+        assert_close(reading.short_term_lufs, -3.01, 0.05);
+
+        assert_eq!(reading.channels[1], ChannelLevels::default());
+    }
+
+    #[test]
+    fn levels_follow_the_amplitude() {
+        let mut meter = LevelMeter::new(Duration::from_secs(1));
+        let reading = meter
+            .process(&sine(997.0, 0.5, SAMPLE_RATE as usize))
+            .unwrap();
+
+        let left = reading.channels[0];
+        assert_close(left.rms_db, -9.03, 0.01);
+        assert_close(left.peak_db, -6.02, 0.01);
+        assert_close(left.momentary_lufs, -9.03, 0.05);
+    }
+
+    #[test]
+    fn loudness_is_k_weighted() {
+        // the high pass of the K-weighting all but removes a 20 Hz sine, while the
+        // unweighted levels stay the same
+        let mut meter = LevelMeter::new(Duration::from_secs(1));
+        let reading = meter
+            .process(&sine(20.0, 1.0, SAMPLE_RATE as usize))
+            .unwrap();
+
+        let left = reading.channels[0];
+        assert_close(left.rms_db, -3.01, 0.05);
+        assert!(left.momentary_lufs < -15.0);
+    }
+
+    #[test]
+    fn reads_once_per_interval() {
+        let mut meter = LevelMeter::new(Duration::from_millis(100));
+        let samples = sine(997.0, 1.0, BLOCK_FRAMES);
+
+        assert!(meter.process(&samples[..samples.len() / 2]).is_none());
+        assert!(meter.process(&samples[samples.len() / 2..]).is_some());
+
+        meter.reset();
+        assert!(meter.process(&samples[..samples.len() / 2]).is_none());
+    }
+
+    #[test]
+    fn silence_reads_the_floor() {
+        let mut meter = LevelMeter::new(Duration::from_millis(100));
+        let reading = meter.process(&vec![0.0; BLOCK_FRAMES * CHANNELS]).unwrap();
+
+        assert_eq!(reading, LevelMeterReading::default());
+    }
+}
@@ -11,8 +11,13 @@ pub mod config;
 pub mod convert;
 pub mod decoder;
 pub mod dither;
+pub mod fade;
+pub mod level_meter;
+pub mod limiter;
 pub mod mixer;
 pub mod player;
+pub mod silence;
+pub mod time_stretch;
 
 pub const SAMPLE_RATE: u32 = 44100;
 pub const NUM_CHANNELS: u8 = 2;
@@ -0,0 +1,306 @@
+use std::{collections::VecDeque, f64::consts::PI, ops::RangeInclusive, time::Duration};
+
+use crate::{player::db_to_ratio, NUM_CHANNELS, SAMPLE_RATE};
+
+pub const VALID_NORMALISATION_CEILING_RANGE: RangeInclusive<f64> = -10.0..=0.0;
+
+const CHANNELS: usize = NUM_CHANNELS as usize;
+
+// The true peak is estimated by interpolating three points in between every two samples,
+// i.e. 4x oversampling as per ITU-R BS.1770, with a windowed sinc of this many taps.
+const OVERSAMPLING: usize = 4;
+const TAPS: usize = 24;
+
+// How far the limiter looks ahead, which is also how long it takes to reduce the gain.
+const LOOKAHEAD: Duration = Duration::from_millis(5);
+
+// Look-ahead limiter that keeps the true peak of the output below a ceiling.
+//
+// The gain each frame requires to stay below the ceiling is held for the length of the
+// look-ahead, so that it is already in place by the time the peak comes out of the delay
+// line, and smoothed by averaging over that same length, so that the gain reduction ramps
+// in rather than distorting the waveform. After the peak has passed, the gain is released
+// exponentially.
+//
+// After: Zölzer, U. (2011). DAFX: Digital Audio Effects (2nd ed.), chapter 4.2. Wiley.
+pub struct TruePeakLimiter {
+    ceiling: f64,
+    release_cf: f64,
+    lookahead_frames: usize,
+
+    // interpolation filters of the points in between two samples
+    phases: [[f64; TAPS]; OVERSAMPLING - 1],
+    // the last `TAPS` input frames, oldest first
+    history: VecDeque<[f64; CHANNELS]>,
+
+    // input frames waiting for their gain
+    delay: VecDeque<[f64; CHANNELS]>,
+
+    // running minimum of the required gains within the look-ahead, as (frame, gain)
+    hold: VecDeque<(usize, f64)>,
+    frame: usize,
+    envelope: f64,
+
+    // moving average of the envelope over the look-ahead
+    gains: VecDeque<f64>,
+    gain_sum: f64,
+}
+
+impl TruePeakLimiter {
+    pub fn new(ceiling_db: f64, release_cf: f64) -> Self {
+        let mut phases = [[0.0; TAPS]; OVERSAMPLING - 1];
+
+        for (phase, taps) in phases.iter_mut().enumerate() {
+            let fraction = (phase + 1) as f64 / OVERSAMPLING as f64;
+
+            for (tap, coefficient) in taps.iter_mut().enumerate() {
+                // distance of the interpolated point to the sample under this tap
+                let x = (TAPS / 2 - 1) as f64 - tap as f64 + fraction;
+
+                let sinc = (PI * x).sin() / (PI * x);
+                let blackman = 0.42
+                    + 0.5 * (2.0 * PI * x / TAPS as f64).cos()
+                    + 0.08 * (4.0 * PI * x / TAPS as f64).cos();
+
+                *coefficient = sinc * blackman;
+            }
+        }
+
+        let mut limiter = Self {
+            ceiling: db_to_ratio(ceiling_db),
+            // the release coefficient is per sample, the limiter steps per frame
+            release_cf: release_cf.powi(NUM_CHANNELS as i32),
+            lookahead_frames: (LOOKAHEAD.as_secs_f64() * SAMPLE_RATE as f64) as usize,
+            phases,
+            history: VecDeque::with_capacity(TAPS + 1),
+            delay: VecDeque::new(),
+            hold: VecDeque::new(),
+            frame: 0,
+            envelope: 1.0,
+            gains: VecDeque::new(),
+            gain_sum: 0.0,
+        };
+
+        limiter.reset();
+        limiter
+    }
+
+    // Frames by which the output lags behind the input.
+    fn latency(&self) -> usize {
+        // the detector needs half the filter of future frames, the gain needs the look-ahead
+        (TAPS / 2 - 1) + (self.lookahead_frames - 1)
+    }
+
+    // Drops everything buffered, e.g. on a seek or when another track is loaded.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.history.resize(TAPS, [0.0; CHANNELS]);
+
+        self.delay.clear();
+        self.delay.resize(self.latency(), [0.0; CHANNELS]);
+
+        self.hold.clear();
+        self.frame = 0;
+        self.envelope = 1.0;
+
+        self.gains.clear();
+        self.gains.resize(self.lookahead_frames, 1.0);
+        self.gain_sum = self.lookahead_frames as f64;
+    }
+
+    // The highest true peak in between the previous and the current frame of the
+    // detector, which is in the middle of the history.
+    fn true_peak(&self) -> f64 {
+        let mut peak: f64 = 0.0;
+
+        for channel in 0..CHANNELS {
+            peak = peak.max(self.history[TAPS / 2][channel].abs());
+
+            for taps in &self.phases {
+                let interpolated: f64 = taps
+                    .iter()
+                    .zip(&self.history)
+                    .map(|(coefficient, frame)| coefficient * frame[channel])
+                    .sum();
+
+                peak = peak.max(interpolated.abs());
+            }
+        }
+
+        peak
+    }
+
+    // Limits interleaved samples in place, delayed by a little over the look-ahead.
+    pub fn process(&mut self, samples: &mut [f64]) {
+        for frame in samples.chunks_exact_mut(CHANNELS) {
+            let mut input = [0.0; CHANNELS];
+            input.copy_from_slice(frame);
+
+            self.history.pop_front();
+            self.history.push_back(input);
+            self.delay.push_back(input);
+
+            let peak = self.true_peak();
+            let required_gain = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+
+            // hold the lowest required gain for the length of the look-ahead
+            while matches!(self.hold.back(), Some(&(_, gain)) if gain >= required_gain) {
+                self.hold.pop_back();
+            }
+            self.hold.push_back((self.frame, required_gain));
+            while matches!(self.hold.front(), Some(&(frame, _)) if frame + self.lookahead_frames <= self.frame)
+            {
+                self.hold.pop_front();
+            }
+            self.frame += 1;
+
+            let held_gain = self.hold.front().map_or(1.0, |&(_, gain)| gain);
+
+            // attack instantly, the moving average turns that into a ramp
+            self.envelope = if held_gain < self.envelope {
+                held_gain
+            } else {
+                held_gain + self.release_cf * (self.envelope - held_gain)
+            };
+
+            self.gain_sum += self.envelope - self.gains.pop_front().unwrap_or(1.0);
+            self.gains.push_back(self.envelope);
+            let gain = (self.gain_sum / self.lookahead_frames as f64).min(1.0);
+
+            let output = self.delay.pop_front().unwrap_or_default();
+            for (sample, delayed) in frame.iter_mut().zip(output) {
+                *sample = delayed * gain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::ratio_to_db;
+
+    const CEILING_DB: f64 = -1.0;
+    // the default of `--normalisation-release`
+    const RELEASE_CF: f64 = 0.999_887_63;
+
+    // Interleaved stereo of a sum of sines as (frequency, amplitude, phase), with the
+    // right channel inverted.
+    fn tones(tones: &[(f64, f64, f64)], frames: usize) -> Vec<f64> {
+        (0..frames)
+            .flat_map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let sample: f64 = tones
+                    .iter()
+                    .map(|&(frequency, amplitude, phase)| {
+                        amplitude * (2.0 * PI * frequency * t + phase).sin()
+                    })
+                    .sum();
+                [sample, -sample]
+            })
+            .collect()
+    }
+
+    // The true peak as ITU-R BS.1770 measures it, 4x oversampling, but independent of the
+    // limiter and with a much longer windowed sinc.
+    fn true_peak_db(samples: &[f64]) -> f64 {
+        const REFERENCE_OVERSAMPLING: usize = 4;
+        const REFERENCE_TAPS: isize = 128;
+
+        let mut peak: f64 = 0.0;
+        for channel in 0..CHANNELS {
+            let channel: Vec<f64> = samples
+                .iter()
+                .skip(channel)
+                .step_by(CHANNELS)
+                .copied()
+                .collect();
+
+            for i in 0..channel.len() {
+                for step in 0..REFERENCE_OVERSAMPLING {
+                    let position = i as f64 + step as f64 / REFERENCE_OVERSAMPLING as f64;
+
+                    let mut interpolated = 0.0;
+                    for tap in -REFERENCE_TAPS / 2..REFERENCE_TAPS / 2 {
+                        let index = i as isize + tap;
+                        if index < 0 || index >= channel.len() as isize {
+                            continue;
+                        }
+
+                        let x = position - index as f64;
+                        let sinc = if x == 0.0 {
+                            1.0
+                        } else {
+                            (PI * x).sin() / (PI * x)
+                        };
+                        let phase = PI * x / (REFERENCE_TAPS / 2) as f64;
+                        let window = 0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+                        interpolated += channel[index as usize] * sinc * window;
+                    }
+
+                    peak = peak.max(interpolated.abs());
+                }
+            }
+        }
+
+        ratio_to_db(peak)
+    }
+
+    fn limit(mut samples: Vec<f64>) -> Vec<f64> {
+        let mut limiter = TruePeakLimiter::new(CEILING_DB, RELEASE_CF);
+        // flush the delay line, so that the whole signal comes out
+        samples.extend(vec![0.0; limiter.latency() * CHANNELS]);
+        for packet in samples.chunks_mut(4096) {
+            limiter.process(packet);
+        }
+        samples
+    }
+
+    #[test]
+    fn keeps_inter_sample_peaks_below_the_ceiling() {
+        // A sine at a quarter of the sample rate, sampled 45° off its peaks: the samples
+        // read 3 dB below the true peak. With 6 dB of pregain the true peak is at +6 dBTP.
+        let samples = tones(&[(SAMPLE_RATE as f64 / 4.0, 2.0, PI / 4.0)], 4410);
+        assert!(true_peak_db(&samples) > 5.9);
+        assert!(ratio_to_db(samples.iter().fold(0.0, |peak: f64, s| peak.max(s.abs()))) < 3.1);
+
+        let limited = limit(samples);
+        // up to rounding errors
+        assert!(true_peak_db(&limited) <= CEILING_DB + 1e-9);
+    }
+
+    #[test]
+    fn keeps_full_scale_music_below_the_ceiling() {
+        // Full scale bursts of a low and a high tone with 4 dB of pregain, starting
+        // abruptly after silence, so that the look-ahead has to catch their onsets.
+        let burst = tones(
+            &[(997.0, 0.8, 0.0), (15_000.0, 0.8, 1.0), (62.0, 0.4, 2.0)],
+            SAMPLE_RATE as usize / 10,
+        );
+        let mut samples = Vec::new();
+        for _ in 0..3 {
+            samples.extend(vec![0.0; 441 * CHANNELS]);
+            samples.extend(burst.iter().map(|s| s * db_to_ratio(4.0)));
+        }
+        assert!(true_peak_db(&samples) > 3.0);
+
+        let limited = limit(samples);
+        // up to rounding errors
+        assert!(true_peak_db(&limited) <= CEILING_DB + 1e-9);
+    }
+
+    #[test]
+    fn passes_quiet_signals_unchanged() {
+        let samples = tones(&[(997.0, 0.5, 0.0)], 4410);
+        let limited = limit(samples.clone());
+
+        let latency = TruePeakLimiter::new(CEILING_DB, RELEASE_CF).latency() * CHANNELS;
+        for (limited, sample) in limited[latency..].iter().zip(&samples) {
+            assert!((limited - sample).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Mixer, MixerConfig, VolumeCtrl};
+
+// How often the volume is read with the get command while it isn't changed from here.
+const GET_VOLUME_INTERVAL: Duration = Duration::from_secs(10);
+
+// Controls the volume of external hardware, like an amplifier behind a serial port or an
+// IR blaster, through a program, either by running a command for every volume change, or
+// by talking to a long-running helper over stdin/stdout.
+//
+// The volume is exchanged in % from 0 - 100, without applying `--volume-ctrl`, because the
+// hardware usually has its own volume curve.
+//
+// The volume is read back from the hardware in the background, so that `volume()` never
+// has to wait for it.
+pub struct ExecMixer {
+    // the volume as set, or as last reported by the hardware
+    volume: Arc<AtomicU16>,
+    volume_tx: mpsc::Sender<u16>,
+}
+
+impl Mixer for ExecMixer {
+    fn open(config: MixerConfig) -> Self {
+        let volume = Arc::new(AtomicU16::new(VolumeCtrl::MAX_VOLUME / 2));
+        let (volume_tx, volume_rx) = mpsc::channel();
+
+        let setter = if let Some(helper) = config.exec_helper {
+            info!("Mixing with helper: {}", helper);
+            Setter::Helper(Helper::spawn(&helper, volume.clone()))
+        } else if let Some(set_command) = config.exec_set_command {
+            info!("Mixing with command: {}", set_command);
+            Setter::Command(set_command)
+        } else {
+            panic!("Exec mixer requires a set command or a helper");
+        };
+
+        let getter = config.exec_get_command.map(|get_command| {
+            debug!("Exec mixer reading the volume with: {}", get_command);
+            Getter {
+                get_command,
+                volume: volume.clone(),
+            }
+        });
+
+        let debounce = config.exec_debounce;
+
+        // Slider drags produce lots of volume changes, so only the latest volume within
+        // every `debounce` is passed on. The volume is read back after every change and
+        // every `GET_VOLUME_INTERVAL` in between.
+        thread::spawn(move || {
+            let mut setter = setter;
+
+            if let Some(getter) = &getter {
+                getter.refresh();
+            }
+
+            loop {
+                let received = match &getter {
+                    Some(_) => volume_rx.recv_timeout(GET_VOLUME_INTERVAL),
+                    None => volume_rx.recv().map_err(mpsc::RecvTimeoutError::from),
+                };
+
+                let mut volume = match received {
+                    Ok(volume) => volume,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        if let Some(getter) = &getter {
+                            getter.refresh();
+                        }
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+
+                let deadline = Instant::now() + debounce;
+                while let Ok(next_volume) =
+                    volume_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    volume = next_volume;
+                }
+
+                setter.set_volume(volume);
+
+                if let Some(getter) = &getter {
+                    getter.refresh();
+                }
+            }
+
+            debug!("Exec mixer thread finished.");
+        });
+
+        Self { volume, volume_tx }
+    }
+
+    fn volume(&self) -> u16 {
+        self.volume.load(Ordering::Relaxed)
+    }
+
+    fn set_volume(&self, volume: u16) {
+        self.volume.store(volume, Ordering::Relaxed);
+        if let Err(e) = self.volume_tx.send(volume) {
+            error!("Exec mixer thread is gone: {}", e);
+        }
+    }
+}
+
+impl ExecMixer {
+    pub const NAME: &'static str = "exec";
+}
+
+enum Setter {
+    Command(String),
+    Helper(Helper),
+}
+
+impl Setter {
+    fn set_volume(&mut self, volume: u16) {
+        debug!("Setting exec mixer volume to {}%", to_percent(volume));
+
+        match self {
+            Self::Command(set_command) => {
+                let mut command = command(set_command);
+                command
+                    .env("VOLUME", to_percent(volume).to_string())
+                    .env("VOLUME_RAW", volume.to_string());
+
+                match command.status() {
+                    Err(e) => warn!("Set volume command {} failed to start: {}", set_command, e),
+                    Ok(status) if status.success() => (),
+                    Ok(status) => warn!("Set volume command {} failed: {}", set_command, status),
+                }
+            }
+            Self::Helper(helper) => helper.set_volume(volume),
+        }
+    }
+}
+
+struct Getter {
+    get_command: String,
+    volume: Arc<AtomicU16>,
+}
+
+impl Getter {
+    fn refresh(&self) {
+        let volume = self.volume.load(Ordering::Relaxed);
+
+        match get_volume(&self.get_command) {
+            // a volume that was set in the meantime is newer than what was read
+            Some(reported_volume) => {
+                let _ = self.volume.compare_exchange(
+                    volume,
+                    reported_volume,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+            None => warn!("Could not read the volume with {}", self.get_command),
+        }
+    }
+}
+
+// A long-running program that takes a volume in % per line on stdin, and may report the
+// volume of the hardware in % per line on stdout, e.g. when a knob on the amplifier is turned.
+struct Helper {
+    program: String,
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl Helper {
+    fn spawn(program: &str, volume: Arc<AtomicU16>) -> Self {
+        let mut child = command(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| panic!("Could not start exec mixer helper {program}: {e}"));
+
+        let stdin = child.stdin.take().expect("helper stdin is piped");
+        let stdout = child.stdout.take().expect("helper stdout is piped");
+
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line.as_deref().map(parse_volume) {
+                    Ok(Some(reported_volume)) => {
+                        trace!(
+                            "Exec mixer helper reported {}%",
+                            to_percent(reported_volume)
+                        );
+                        volume.store(reported_volume, Ordering::Relaxed);
+                    }
+                    Ok(None) => warn!("Exec mixer helper reported an invalid volume"),
+                    Err(e) => {
+                        warn!("Could not read from exec mixer helper: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            program: program.to_string(),
+            child,
+            stdin,
+        }
+    }
+
+    fn set_volume(&mut self, volume: u16) {
+        if let Err(e) = writeln!(self.stdin, "{}", to_percent(volume)) {
+            match self.child.try_wait() {
+                Ok(Some(status)) => error!("Exec mixer helper {} exited: {}", self.program, status),
+                _ => error!(
+                    "Could not write to exec mixer helper {}: {}",
+                    self.program, e
+                ),
+            }
+        }
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        if let Err(e) = self.child.kill() {
+            debug!("Could not stop exec mixer helper {}: {}", self.program, e);
+        }
+        let _ = self.child.wait();
+    }
+}
+
+// Commands are run by the shell, so that they can quote their arguments, use pipes or
+// expand $VOLUME themselves.
+fn command(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+fn get_volume(get_command: &str) -> Option<u16> {
+    let output = command(get_command).output();
+
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8(output.stdout)
+            .ok()
+            .and_then(|stdout| stdout.lines().next().and_then(parse_volume)),
+        Ok(output) => {
+            warn!(
+                "Get volume command {} failed: {}",
+                get_command, output.status
+            );
+            None
+        }
+        Err(e) => {
+            warn!("Get volume command {} failed to start: {}", get_command, e);
+            None
+        }
+    }
+}
+
+fn parse_volume(percent: &str) -> Option<u16> {
+    match percent.trim().parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => {
+            Some((percent / 100.0 * VolumeCtrl::MAX_VOLUME as f64).round() as u16)
+        }
+        _ => None,
+    }
+}
+
+fn to_percent(volume: u16) -> u16 {
+    (volume as f64 / VolumeCtrl::MAX_VOLUME as f64 * 100.0).round() as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percent(percent: u16) -> u16 {
+        parse_volume(&percent.to_string()).unwrap()
+    }
+
+    #[test]
+    fn parses_the_volume() {
+        assert_eq!(parse_volume("0"), Some(0));
+        assert_eq!(parse_volume(" 100\n"), Some(VolumeCtrl::MAX_VOLUME));
+        assert_eq!(parse_volume("50.0"), Some(32768));
+        assert_eq!(parse_volume("101"), None);
+        assert_eq!(parse_volume("-1"), None);
+        assert_eq!(parse_volume("loud"), None);
+
+        assert_eq!(to_percent(percent(42)), 42);
+    }
+
+    #[test]
+    fn reads_the_first_line_of_the_get_command() {
+        assert_eq!(get_volume("echo 42"), Some(percent(42)));
+        assert_eq!(get_volume("printf '%s\\n' 42 7"), Some(percent(42)));
+        assert_eq!(get_volume("echo 42 | tr 4 1"), Some(percent(12)));
+
+        assert_eq!(get_volume("echo 42; exit 1"), None);
+        assert_eq!(get_volume("echo loud"), None);
+        assert_eq!(get_volume("true"), None);
+    }
+
+    #[test]
+    fn passes_the_volume_to_the_set_command() {
+        let file = std::env::temp_dir().join(format!("spotipi-exec-mixer-{}", std::process::id()));
+        let mut setter = Setter::Command(format!(
+            "echo \"$VOLUME $VOLUME_RAW\" > '{}'",
+            file.display()
+        ));
+
+        setter.set_volume(percent(30));
+        let written = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(written, format!("30 {}\n", percent(30)));
+    }
+
+    #[test]
+    fn talks_to_the_helper_line_by_line() {
+        let volume = Arc::new(AtomicU16::new(0));
+        // reports back every volume it gets
+        let mut helper = Helper::spawn("cat", volume.clone());
+
+        for expected in [percent(25), percent(80)] {
+            helper.set_volume(expected);
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while volume.load(Ordering::Relaxed) != expected {
+                assert!(
+                    Instant::now() < deadline,
+                    "helper did not report the volume"
+                );
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::config::VolumeCtrl;
+use crate::NUM_CHANNELS;
 
 pub mod mappings;
 use self::mappings::MappedCtrl;
@@ -22,6 +24,59 @@ pub trait Mixer: Send + Sync {
 
 pub trait VolumeGetter {
     fn attenuation_factor(&self) -> f64;
+
+    // Attenuation of the next `frames` frames. Getters whose attenuation can change abruptly
+    // should ramp towards the new factor to avoid zipper noise, by default it is constant.
+    fn attenuation_ramp(&mut self, _frames: usize) -> VolumeRamp {
+        VolumeRamp::constant(self.attenuation_factor())
+    }
+}
+
+// Attenuation factors of the interleaved samples of a packet, interpolated per frame.
+#[derive(Debug, Clone, Copy)]
+pub struct VolumeRamp {
+    factor: f64,
+    step: f64,
+    // frames that are still to be stepped
+    frames: usize,
+    channel: usize,
+}
+
+impl VolumeRamp {
+    pub fn constant(factor: f64) -> Self {
+        Self::new(factor, 0.0, 0)
+    }
+
+    // Starts at `factor` and changes by `step` per frame over the first `frames` frames.
+    pub fn new(factor: f64, step: f64, frames: usize) -> Self {
+        Self {
+            factor,
+            step,
+            frames,
+            channel: 0,
+        }
+    }
+
+    // Whether any of the samples is attenuated at all.
+    pub fn attenuates(&self) -> bool {
+        self.factor < 1.0 || (self.step != 0.0 && self.frames > 0)
+    }
+
+    // The factor of the next sample.
+    pub fn next_factor(&mut self) -> f64 {
+        let factor = self.factor;
+
+        self.channel += 1;
+        if self.channel == NUM_CHANNELS as usize {
+            self.channel = 0;
+            if self.frames > 0 {
+                self.factor += self.step;
+                self.frames -= 1;
+            }
+        }
+
+        factor
+    }
 }
 
 impl VolumeGetter for NoOpVolume {
@@ -38,12 +93,21 @@ pub mod alsamixer;
 #[cfg(feature = "alsa-backend")]
 use self::alsamixer::AlsaMixer;
 
+pub mod execmixer;
+use self::execmixer::ExecMixer;
+
 #[derive(Debug, Clone)]
 pub struct MixerConfig {
     pub device: String,
     pub control: String,
     pub index: u32,
     pub volume_ctrl: VolumeCtrl,
+    // programs run by the exec mixer, either a command for every volume change with an
+    // optional command to read back the volume, or a long-running helper
+    pub exec_set_command: Option<String>,
+    pub exec_get_command: Option<String>,
+    pub exec_helper: Option<String>,
+    pub exec_debounce: Duration,
 }
 
 impl Default for MixerConfig {
@@ -53,6 +117,10 @@ impl Default for MixerConfig {
             control: String::from("PCM"),
             index: 0,
             volume_ctrl: VolumeCtrl::default(),
+            exec_set_command: None,
+            exec_get_command: None,
+            exec_helper: None,
+            exec_debounce: Duration::from_millis(100),
         }
     }
 }
@@ -67,6 +135,7 @@ pub const MIXERS: &[(&str, MixerFn)] = &[
     (SoftMixer::NAME, mk_sink::<SoftMixer>), // default goes first
     #[cfg(feature = "alsa-backend")]
     (AlsaMixer::NAME, mk_sink::<AlsaMixer>),
+    (ExecMixer::NAME, mk_sink::<ExecMixer>),
 ];
 
 pub fn find(name: Option<&str>) -> Option<MixerFn> {
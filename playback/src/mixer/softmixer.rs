@@ -1,10 +1,16 @@
 use portable_atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::VolumeGetter;
 use super::{MappedCtrl, VolumeCtrl};
 use super::{Mixer, MixerConfig};
+use super::{VolumeGetter, VolumeRamp};
+
+use crate::SAMPLE_RATE;
+
+// Volume changes are spread over this long, which is short enough to still feel immediate.
+const VOLUME_RAMP: Duration = Duration::from_millis(20);
 
 #[derive(Clone)]
 pub struct SoftMixer {
@@ -37,7 +43,7 @@ impl Mixer for SoftMixer {
     }
 
     fn get_soft_volume(&self) -> Box<dyn VolumeGetter + Send> {
-        Box::new(SoftVolume(self.volume.clone()))
+        Box::new(SoftVolume::new(self.volume.clone()))
     }
 }
 
@@ -45,10 +51,140 @@ impl SoftMixer {
     pub const NAME: &'static str = "softvol";
 }
 
-struct SoftVolume(Arc<AtomicU64>);
+struct SoftVolume {
+    volume: Arc<AtomicU64>,
+    // where the ramp got to and where it is heading
+    current: f64,
+    target: f64,
+    step: f64,
+    remaining_frames: usize,
+}
+
+impl SoftVolume {
+    fn new(volume: Arc<AtomicU64>) -> Self {
+        let factor = f64::from_bits(volume.load(Ordering::Relaxed));
+
+        Self {
+            volume,
+            current: factor,
+            target: factor,
+            step: 0.0,
+            remaining_frames: 0,
+        }
+    }
+}
 
 impl VolumeGetter for SoftVolume {
     fn attenuation_factor(&self) -> f64 {
-        f64::from_bits(self.0.load(Ordering::Relaxed))
+        f64::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    fn attenuation_ramp(&mut self, frames: usize) -> VolumeRamp {
+        let target = self.attenuation_factor();
+
+        // a new volume restarts the ramp from wherever the previous one got to
+        if target != self.target {
+            let ramp_frames = (VOLUME_RAMP.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+
+            self.target = target;
+            self.step = (target - self.current) / ramp_frames as f64;
+            self.remaining_frames = ramp_frames;
+        }
+
+        if self.remaining_frames == 0 {
+            return VolumeRamp::constant(self.current);
+        }
+
+        let ramp_frames = self.remaining_frames.min(frames);
+        let ramp = VolumeRamp::new(self.current, self.step, ramp_frames);
+
+        self.remaining_frames -= ramp_frames;
+        self.current = if self.remaining_frames == 0 {
+            self.target
+        } else {
+            self.current + self.step * ramp_frames as f64
+        };
+
+        ramp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NUM_CHANNELS;
+
+    const RAMP_FRAMES: usize = (VOLUME_RAMP.as_millis() as usize * SAMPLE_RATE as usize) / 1000;
+
+    fn soft_volume(factor: f64) -> (Arc<AtomicU64>, SoftVolume) {
+        let volume = Arc::new(AtomicU64::new(factor.to_bits()));
+        (volume.clone(), SoftVolume::new(volume))
+    }
+
+    // The factors of a packet of `frames` frames, one per frame.
+    fn factors(soft_volume: &mut SoftVolume, frames: usize) -> Vec<f64> {
+        let mut ramp = soft_volume.attenuation_ramp(frames);
+        (0..frames)
+            .map(|_| {
+                let factors: Vec<f64> = (0..NUM_CHANNELS).map(|_| ramp.next_factor()).collect();
+                assert!(factors.iter().all(|factor| *factor == factors[0]));
+                factors[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn constant_without_a_change() {
+        let (_, mut soft_volume) = soft_volume(0.5);
+        assert!(factors(&mut soft_volume, 1024)
+            .iter()
+            .all(|factor| *factor == 0.5));
+
+        let (_, mut soft_volume) = self::soft_volume(1.0);
+        assert!(!soft_volume.attenuation_ramp(1024).attenuates());
+    }
+
+    #[test]
+    fn ramps_monotonically_over_the_ramp_length() {
+        let (volume, mut soft_volume) = soft_volume(0.25);
+        volume.store(0.75f64.to_bits(), Ordering::Relaxed);
+
+        // spread over several packets
+        let mut factors = Vec::new();
+        for _ in 0..4 {
+            factors.extend(self::factors(&mut soft_volume, 256));
+        }
+
+        assert_eq!(factors[0], 0.25);
+        assert!(factors[..RAMP_FRAMES]
+            .windows(2)
+            .all(|factors| factors[1] > factors[0]));
+        assert!(factors[RAMP_FRAMES - 1] < 0.75);
+        assert!(factors[RAMP_FRAMES..]
+            .iter()
+            .all(|factor| (factor - 0.75).abs() < 1e-9));
+    }
+
+    #[test]
+    fn retargets_from_where_the_ramp_got_to() {
+        let (volume, mut soft_volume) = soft_volume(1.0);
+        volume.store(0.0f64.to_bits(), Ordering::Relaxed);
+
+        let factors = self::factors(&mut soft_volume, RAMP_FRAMES / 2);
+        let halfway = factors[factors.len() - 1];
+        assert!(halfway > 0.45 && halfway < 0.55, "{halfway}");
+
+        volume.store(1.0f64.to_bits(), Ordering::Relaxed);
+        let factors = self::factors(&mut soft_volume, 2 * RAMP_FRAMES);
+
+        // no jump, and up again over a full ramp length
+        assert!((factors[0] - halfway).abs() < 0.01);
+        assert!(factors[..RAMP_FRAMES]
+            .windows(2)
+            .all(|factors| factors[1] > factors[0]));
+        assert!(factors[RAMP_FRAMES - 1] < 1.0);
+        assert!(factors[RAMP_FRAMES..]
+            .iter()
+            .all(|factor| (factor - 1.0).abs() < 1e-9));
     }
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fmt,
     future::Future,
     io::{self, Read, Seek, SeekFrom},
@@ -24,20 +24,25 @@ use symphonia::core::io::MediaSource;
 use tokio::sync::{mpsc, oneshot};
 
 use crate::{
-    audio::{AudioDecrypt, AudioFetchParams, AudioFile, StreamLoaderController},
+    audio::{AudioDecrypt, AudioFile, StreamLoaderController},
     audio_backend::Sink,
-    config::{Bitrate, NormalisationMethod, NormalisationType, PlayerConfig},
+    config::{Bitrate, DithererBuilder, NormalisationMethod, NormalisationType, PlayerConfig},
     convert::Converter,
-    core::{util::SeqGenerator, Error, Session, SpotifyId},
+    core::{util::SeqGenerator, Error, FileId, Session, SpotifyId},
     decoder::{AudioDecoder, AudioPacket, AudioPacketPosition, SymphoniaDecoder},
-    metadata::audio::{AudioFileFormat, AudioFiles, AudioItem},
+    fade::Fader,
+    level_meter::{LevelMeter, LevelMeterReading},
+    limiter::TruePeakLimiter,
+    metadata::audio::{AudioFileFormat, AudioFiles, AudioItem, UniqueFields},
     mixer::VolumeGetter,
+    silence::SilenceTrimmer,
+    time_stretch::{TimeStretcher, VALID_PLAYBACK_SPEED_RANGE},
 };
 
 #[cfg(feature = "passthrough-decoder")]
 use crate::decoder::PassthroughDecoder;
 
-use crate::SAMPLES_PER_SECOND;
+use crate::{NUM_CHANNELS, SAMPLES_PER_SECOND};
 
 const PRELOAD_NEXT_TRACK_BEFORE_END_DURATION_MS: u32 = 30000;
 pub const DB_VOLTAGE_RATIO: f64 = 20.0;
@@ -63,6 +68,26 @@ pub enum SinkStatus {
 
 pub type SinkEventCallback = Box<dyn Fn(SinkStatus) + Send>;
 
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SleepTimer {
+    /// Pause after the given time has passed.
+    Duration(Duration),
+    /// Pause once the current track has finished.
+    EndOfTrack,
+    /// Pause once the last track of the context has finished.
+    EndOfContext,
+}
+
+impl fmt::Display for SleepTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duration(duration) => write!(f, "{}ms", duration.as_millis()),
+            Self::EndOfTrack => f.write_str("end_of_track"),
+            Self::EndOfContext => f.write_str("end_of_context"),
+        }
+    }
+}
+
 struct PlayerInternal {
     session: Session,
     config: PlayerConfig,
@@ -76,19 +101,48 @@ struct PlayerInternal {
     sink_event_callback: Option<SinkEventCallback>,
     volume_getter: Box<dyn VolumeGetter + Send>,
     event_senders: Vec<mpsc::UnboundedSender<PlayerEvent>>,
+    level_meter_senders: Vec<mpsc::UnboundedSender<LevelMeterReading>>,
     converter: Converter,
 
     normalisation_integrator: f64,
     normalisation_peak: f64,
+    limiter: TruePeakLimiter,
 
     auto_normalise_as_album: bool,
 
+    silence_trimmer: SilenceTrimmer,
+
+    // tracks to prefetch once the current track is downloaded, and the task that downloads them
+    prefetch_tracks: Option<Vec<SpotifyId>>,
+    prefetch_tx: Option<mpsc::UnboundedSender<Vec<SpotifyId>>>,
+
+    fader: Fader<FadeOutAction>,
+
+    // also holds the playback speed of the current item
+    time_stretcher: TimeStretcher,
+
+    // only runs while anybody subscribed to the levels or level events are enabled
+    level_meter: LevelMeter,
+
     player_id: usize,
     play_request_id_generator: SeqGenerator<u64>,
 }
 
 static PLAYER_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
+// What to do once a fade-out has completed.
+#[derive(Debug)]
+enum FadeOutAction {
+    Pause,
+    Stop,
+    Load {
+        track_id: SpotifyId,
+        play_request_id: u64,
+        play: bool,
+        position_ms: u32,
+    },
+}
+
 enum PlayerCommand {
     Load {
         track_id: SpotifyId,
@@ -98,15 +152,26 @@ enum PlayerCommand {
     Preload {
         track_id: SpotifyId,
     },
+    Prefetch(Vec<SpotifyId>),
     Play,
     Pause,
+    PauseWithFade(Duration),
     Stop,
     Seek(u32),
+    SetPlaybackSpeed(f64),
     SetSession(Session),
     AddEventSender(mpsc::UnboundedSender<PlayerEvent>),
+    AddLevelMeterSender(mpsc::UnboundedSender<LevelMeterReading>),
     SetSinkEventCallback(Option<SinkEventCallback>),
     EmitVolumeChangedEvent(u16),
     SetAutoNormaliseAsAlbum(bool),
+    SetNormalisation(bool),
+    SetNormalisationType(NormalisationType),
+    SetNormalisationMethod(NormalisationMethod),
+    SetNormalisationPregain(f64),
+    SetGapless(bool),
+    SetDitherer(Option<DithererBuilder>),
+    SetBitrate(Bitrate),
     EmitSessionDisconnectedEvent {
         connection_id: String,
         user_name: String,
@@ -128,6 +193,12 @@ enum PlayerCommand {
         track: bool,
     },
     EmitAutoPlayChangedEvent(bool),
+    EmitSleepTimerChangedEvent(Option<SleepTimer>),
+    EmitActiveDeviceChangedEvent {
+        device_id: Option<String>,
+        device_name: Option<String>,
+    },
+    EmitUserRejectedEvent(String),
 }
 
 #[derive(Debug, Clone)]
@@ -200,6 +271,13 @@ pub enum PlayerEvent {
         track_id: SpotifyId,
         position_ms: u32,
     },
+    // The current item plays at a different speed, positions advance at `speed` times real time.
+    PlaybackSpeedChanged {
+        play_request_id: u64,
+        track_id: SpotifyId,
+        position_ms: u32,
+        speed: f64,
+    },
     TrackChanged {
         audio_item: Box<AudioItem>,
     },
@@ -230,6 +308,25 @@ pub enum PlayerEvent {
     FilterExplicitContentChanged {
         filter: bool,
     },
+    SleepTimerChanged {
+        sleep_timer: Option<SleepTimer>,
+    },
+    // Another device of the connect cluster, this one or none at all is playing now.
+    ActiveDeviceChanged {
+        device_id: Option<String>,
+        device_name: Option<String>,
+    },
+    // A user selected this device through discovery, but was not let in.
+    UserRejected {
+        user_name: String,
+    },
+    // Periodic levels of what is being played, only sent if `PlayerConfig::level_meter_events`
+    // is set. Silence is reported once when playback pauses or stops.
+    Levels {
+        play_request_id: u64,
+        track_id: SpotifyId,
+        levels: LevelMeterReading,
+    },
 }
 
 impl PlayerEvent {
@@ -262,6 +359,12 @@ impl PlayerEvent {
             }
             | Seeked {
                 play_request_id, ..
+            }
+            | PlaybackSpeedChanged {
+                play_request_id, ..
+            }
+            | Levels {
+                play_request_id, ..
             } => Some(*play_request_id),
             _ => None,
         }
@@ -269,6 +372,7 @@ impl PlayerEvent {
 }
 
 pub type PlayerEventChannel = mpsc::UnboundedReceiver<PlayerEvent>;
+pub type LevelMeterChannel = mpsc::UnboundedReceiver<LevelMeterReading>;
 
 pub fn db_to_ratio(db: f64) -> f64 {
     f64::powf(10.0, db / DB_VOLTAGE_RATIO)
@@ -286,6 +390,11 @@ pub fn coefficient_to_duration(coefficient: f64) -> Duration {
     Duration::from_secs_f64(-1.0 / f64::ln(coefficient) / SAMPLES_PER_SECOND as f64)
 }
 
+// The instant playback would have started at to be at `position_ms` by `now`.
+fn nominal_start_time(now: Instant, position_ms: u32, playback_speed: f64) -> Option<Instant> {
+    now.checked_sub(Duration::from_millis(position_ms as u64).div_f64(playback_speed))
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct NormalisationData {
     // Spotify provides these as `f32`, but audio metadata can contain up to `f64`.
@@ -376,24 +485,27 @@ impl NormalisationData {
                 factor
             }
         } else {
-            // For Dynamic Normalisation it's up to the player to decide,
+            // For Dynamic and Limiter Normalisation it's up to the player to decide,
             // factor = ratio of (ReplayGain + PreGain).
-            // We then let the dynamic limiter handle gain reduction.
+            // We then let the dynamic or look-ahead limiter handle gain reduction.
             let factor = db_to_ratio(gain_db + config.normalisation_pregain_db);
-            let threshold_ratio = db_to_ratio(config.normalisation_threshold_dbfs);
+            let threshold_dbfs = if config.normalisation_method == NormalisationMethod::Limiter {
+                config.normalisation_ceiling_dbtp
+            } else {
+                config.normalisation_threshold_dbfs
+            };
+            let threshold_ratio = db_to_ratio(threshold_dbfs);
 
             if factor > PCM_AT_0DBFS {
                 let factor_db = gain_db + config.normalisation_pregain_db;
-                let limiting_db = factor_db + config.normalisation_threshold_dbfs.abs();
+                let limiting_db = factor_db + threshold_dbfs.abs();
 
                 warn!(
                     "This track may exceed dBFS by {:.2} dB and be subject to {:.2} dB of dynamic limiting at its peak.",
                     factor_db, limiting_db
                 );
             } else if factor > threshold_ratio {
-                let limiting_db = gain_db
-                    + config.normalisation_pregain_db
-                    + config.normalisation_threshold_dbfs.abs();
+                let limiting_db = gain_db + config.normalisation_pregain_db + threshold_dbfs.abs();
 
                 info!(
                     "This track may be subject to {:.2} dB of dynamic limiting at its peak.",
@@ -450,38 +562,34 @@ impl Player {
                     coefficient_to_duration(config.normalisation_release_cf).as_secs_f64() * 1000.
                 );
                 debug!("Normalisation Knee: {} dB", config.normalisation_knee_db);
+            } else if config.normalisation_method == NormalisationMethod::Limiter {
+                debug!(
+                    "Normalisation Ceiling: {:.1} dBTP",
+                    config.normalisation_ceiling_dbtp
+                );
+                debug!(
+                    "Normalisation Release: {:.0} ms",
+                    coefficient_to_duration(config.normalisation_release_cf).as_secs_f64() * 1000.
+                );
             }
         }
 
-        let handle = thread::spawn(move || {
-            let player_id = PLAYER_COUNTER.fetch_add(1, Ordering::AcqRel);
-            debug!("new Player [{}]", player_id);
-
-            let converter = Converter::new(config.ditherer);
-
-            let internal = PlayerInternal {
-                session,
-                config,
-                commands: cmd_rx,
-                load_handles: Arc::new(Mutex::new(HashMap::new())),
-
-                state: PlayerState::Stopped,
-                preload: PlayerPreload::None,
-                sink: sink_builder(),
-                sink_status: SinkStatus::Closed,
-                sink_event_callback: None,
-                volume_getter,
-                event_senders: vec![],
-                converter,
-
-                normalisation_peak: 0.0,
-                normalisation_integrator: 0.0,
+        if !config.fade_in.is_zero() || !config.fade_out.is_zero() {
+            debug!("Fade In: {} ms", config.fade_in.as_millis());
+            debug!("Fade Out: {} ms", config.fade_out.as_millis());
+        }
 
-                auto_normalise_as_album: false,
+        if config.playback_speed != 1.0 {
+            debug!("Playback Speed: {}", config.playback_speed);
+            debug!(
+                "Playback Speed Episodes Only: {}",
+                config.playback_speed_episodes_only
+            );
+        }
 
-                player_id,
-                play_request_id_generator: SeqGenerator::new(0),
-            };
+        let handle = thread::spawn(move || {
+            let internal =
+                PlayerInternal::new(session, config, cmd_rx, sink_builder(), volume_getter);
 
             // While PlayerInternal is written as a future, it still contains blocking code.
             // It must be run by using block_on() in a dedicated thread.
@@ -524,6 +632,13 @@ impl Player {
         self.command(PlayerCommand::Preload { track_id });
     }
 
+    // Downloads the first `PlayerConfig::prefetch_tracks` of the upcoming tracks completely
+    // into the cache, once the current track is downloaded. Replaces the tracks of a previous
+    // call that weren't downloaded yet.
+    pub fn prefetch(&self, track_ids: Vec<SpotifyId>) {
+        self.command(PlayerCommand::Prefetch(track_ids));
+    }
+
     pub fn play(&self) {
         self.command(PlayerCommand::Play)
     }
@@ -532,6 +647,11 @@ impl Player {
         self.command(PlayerCommand::Pause)
     }
 
+    // Pauses after fading out over `fade`, regardless of the configured fade-out.
+    pub fn pause_with_fade(&self, fade: Duration) {
+        self.command(PlayerCommand::PauseWithFade(fade))
+    }
+
     pub fn stop(&self) {
         self.command(PlayerCommand::Stop)
     }
@@ -540,6 +660,11 @@ impl Player {
         self.command(PlayerCommand::Seek(position_ms));
     }
 
+    // Sets the speed of pitch preserving playback, clamped to 0.5 - 3.0.
+    pub fn set_playback_speed(&self, speed: f64) {
+        self.command(PlayerCommand::SetPlaybackSpeed(speed));
+    }
+
     pub fn set_session(&self, session: Session) {
         self.command(PlayerCommand::SetSession(session));
     }
@@ -550,6 +675,13 @@ impl Player {
         event_receiver
     }
 
+    // Receives a reading of the levels every `PlayerConfig::level_meter_interval` while playing.
+    pub fn get_level_meter_channel(&self) -> LevelMeterChannel {
+        let (level_meter_sender, level_meter_receiver) = mpsc::unbounded_channel();
+        self.command(PlayerCommand::AddLevelMeterSender(level_meter_sender));
+        level_meter_receiver
+    }
+
     pub async fn await_end_of_track(&self) {
         let mut channel = self.get_player_event_channel();
         while let Some(event) = channel.recv().await {
@@ -574,6 +706,39 @@ impl Player {
         self.command(PlayerCommand::SetAutoNormaliseAsAlbum(setting));
     }
 
+    // The normalisation settings apply to the current track from the next packet on.
+    pub fn set_normalisation(&self, normalisation: bool) {
+        self.command(PlayerCommand::SetNormalisation(normalisation));
+    }
+
+    pub fn set_normalisation_type(&self, normalisation_type: NormalisationType) {
+        self.command(PlayerCommand::SetNormalisationType(normalisation_type));
+    }
+
+    pub fn set_normalisation_method(&self, normalisation_method: NormalisationMethod) {
+        self.command(PlayerCommand::SetNormalisationMethod(normalisation_method));
+    }
+
+    pub fn set_normalisation_pregain(&self, pregain_db: f64) {
+        self.command(PlayerCommand::SetNormalisationPregain(pregain_db));
+    }
+
+    // Applies from the next track on.
+    pub fn set_gapless(&self, gapless: bool) {
+        self.command(PlayerCommand::SetGapless(gapless));
+    }
+
+    // Applies from the next packet on.
+    pub fn set_ditherer(&self, ditherer: Option<DithererBuilder>) {
+        self.command(PlayerCommand::SetDitherer(ditherer));
+    }
+
+    // Applies from the next track that is loaded on, tracks that are already preloaded
+    // keep their bitrate.
+    pub fn set_bitrate(&self, bitrate: Bitrate) {
+        self.command(PlayerCommand::SetBitrate(bitrate));
+    }
+
     pub fn emit_filter_explicit_content_changed_event(&self, filter: bool) {
         self.command(PlayerCommand::EmitFilterExplicitContentChangedEvent(filter));
     }
@@ -618,6 +783,25 @@ impl Player {
     pub fn emit_auto_play_changed_event(&self, auto_play: bool) {
         self.command(PlayerCommand::EmitAutoPlayChangedEvent(auto_play));
     }
+
+    pub fn emit_sleep_timer_changed_event(&self, sleep_timer: Option<SleepTimer>) {
+        self.command(PlayerCommand::EmitSleepTimerChangedEvent(sleep_timer));
+    }
+
+    pub fn emit_active_device_changed_event(
+        &self,
+        device_id: Option<String>,
+        device_name: Option<String>,
+    ) {
+        self.command(PlayerCommand::EmitActiveDeviceChangedEvent {
+            device_id,
+            device_name,
+        });
+    }
+
+    pub fn emit_user_rejected_event(&self, user_name: String) {
+        self.command(PlayerCommand::EmitUserRejectedEvent(user_name));
+    }
 }
 
 impl Drop for Player {
@@ -786,7 +970,7 @@ impl PlayerState {
         }
     }
 
-    fn paused_to_playing(&mut self) {
+    fn paused_to_playing(&mut self, playback_speed: f64) {
         use self::PlayerState::*;
         let new_state = mem::replace(self, Invalid);
         match new_state {
@@ -815,8 +999,11 @@ impl PlayerState {
                     duration_ms,
                     bytes_per_second,
                     stream_position_ms,
-                    reported_nominal_start_time: Instant::now()
-                        .checked_sub(Duration::from_millis(stream_position_ms as u64)),
+                    reported_nominal_start_time: nominal_start_time(
+                        Instant::now(),
+                        stream_position_ms,
+                        playback_speed,
+                    ),
                     suggested_to_preload_next_track,
                     is_explicit,
                 };
@@ -876,6 +1063,47 @@ impl PlayerState {
     }
 }
 
+// The tracks that are yet to be prefetched, until the files prefetched take up half of the
+// cache. Tracks that are received replace those that weren't prefetched yet.
+struct PrefetchQueue {
+    track_ids: VecDeque<SpotifyId>,
+    // the size of the cache, if it is limited
+    cache_size_limit: Option<u64>,
+    prefetched_size: u64,
+}
+
+impl PrefetchQueue {
+    fn new(cache_size_limit: Option<u64>) -> Self {
+        Self {
+            track_ids: VecDeque::new(),
+            cache_size_limit,
+            prefetched_size: 0,
+        }
+    }
+
+    fn replace(&mut self, track_ids: Vec<SpotifyId>) {
+        self.track_ids = track_ids.into();
+        self.prefetched_size = 0;
+    }
+
+    fn next(&mut self) -> Option<SpotifyId> {
+        if self
+            .cache_size_limit
+            .is_some_and(|size_limit| self.prefetched_size >= size_limit / 2)
+            && !self.track_ids.is_empty()
+        {
+            debug!("Not prefetching any more tracks, they would take up too much of the cache");
+            self.track_ids.clear();
+        }
+
+        self.track_ids.pop_front()
+    }
+
+    fn prefetched(&mut self, file_size: u64) {
+        self.prefetched_size += file_size;
+    }
+}
+
 struct PlayerTrackLoader {
     session: Session,
     config: PlayerConfig,
@@ -927,11 +1155,12 @@ impl PlayerTrackLoader {
         Some(data_rate.ceil() as usize)
     }
 
-    async fn load_track(
+    // Finds the audio item to play for a track, or its available alternative, and its
+    // file in the preferred format.
+    async fn find_audio_file(
         &self,
         spotify_id: SpotifyId,
-        position_ms: u32,
-    ) -> Option<PlayerLoadedTrackData> {
+    ) -> Option<(AudioItem, AudioFileFormat, FileId)> {
         let audio_item = match AudioItem::get_file(&self.session, spotify_id).await {
             Ok(audio) => match self.find_available_alternative(audio).await {
                 Some(audio) => audio,
@@ -949,11 +1178,6 @@ impl PlayerTrackLoader {
             }
         };
 
-        info!(
-            "Loading <{}> with Spotify URI <{}>",
-            audio_item.name, audio_item.uri
-        );
-
         // (Most) podcasts seem to support only 96 kbps Ogg Vorbis, so fall back to it
         let formats = match self.config.bitrate {
             Bitrate::Bitrate96 => [
@@ -1002,6 +1226,101 @@ impl PlayerTrackLoader {
                 }
             };
 
+        Some((audio_item, format, file_id))
+    }
+
+    // Downloads a track completely into the cache, unless it is in there already, and
+    // returns the size of its file.
+    async fn prefetch_track(&self, spotify_id: SpotifyId) -> Option<u64> {
+        let (audio_item, format, file_id) = self.find_audio_file(spotify_id).await?;
+        let bytes_per_second = self.stream_data_rate(format)?;
+
+        let encrypted_file = match AudioFile::open(&self.session, file_id, bytes_per_second).await {
+            Ok(encrypted_file) => encrypted_file,
+            Err(e) => {
+                warn!("Unable to prefetch <{}>: {}", audio_item.name, e);
+                return None;
+            }
+        };
+
+        let stream_loader_controller = encrypted_file.get_stream_loader_controller().ok()?;
+        let file_size = stream_loader_controller.len();
+
+        if !encrypted_file.is_cached() {
+            debug!("Prefetching <{}>", audio_item.name);
+
+            // The download is done once the whole file is available, after which it is
+            // moved into the cache.
+            stream_loader_controller.set_background_mode(true);
+            stream_loader_controller.set_stream_mode();
+            let download = tokio::task::spawn_blocking(move || {
+                stream_loader_controller.fetch_next_and_wait(file_size, file_size)
+            });
+
+            match download.await {
+                Ok(Ok(())) => debug!("Prefetched <{}>", audio_item.name),
+                Ok(Err(e)) => {
+                    warn!("Unable to prefetch <{}>: {}", audio_item.name, e);
+                    return None;
+                }
+                Err(e) => {
+                    error!("Prefetch of <{}> panicked: {}", audio_item.name, e);
+                    return None;
+                }
+            }
+        }
+
+        Some(file_size as u64)
+    }
+
+    // Prefetches the tracks received, one at a time, until the player drops the sender,
+    // after which the current download is finished but no other is started.
+    async fn prefetch(self, mut tracks_rx: mpsc::UnboundedReceiver<Vec<SpotifyId>>) {
+        let mut queue = PrefetchQueue::new(
+            self.session
+                .cache()
+                .and_then(|cache| cache.audio_size_limit()),
+        );
+
+        'prefetch: loop {
+            loop {
+                match tracks_rx.try_recv() {
+                    Ok(track_ids) => queue.replace(track_ids),
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    Err(mpsc::error::TryRecvError::Disconnected) => break 'prefetch,
+                }
+            }
+
+            let Some(track_id) = queue.next() else {
+                match tracks_rx.recv().await {
+                    Some(track_ids) => {
+                        queue.replace(track_ids);
+                        continue;
+                    }
+                    None => break,
+                }
+            };
+
+            if let Some(file_size) = self.prefetch_track(track_id).await {
+                queue.prefetched(file_size);
+            }
+        }
+
+        debug!("Prefetch task finished.");
+    }
+
+    async fn load_track(
+        &self,
+        spotify_id: SpotifyId,
+        position_ms: u32,
+    ) -> Option<PlayerLoadedTrackData> {
+        let (audio_item, format, file_id) = self.find_audio_file(spotify_id).await?;
+
+        info!(
+            "Loading <{}> with Spotify URI <{}>",
+            audio_item.name, audio_item.uri
+        );
+
         let bytes_per_second = self.stream_data_rate(format)?;
 
         // This is only a loop to be able to reload the file if an error occurred
@@ -1055,8 +1374,13 @@ impl PlayerTrackLoader {
                 }
             };
 
+            let buffer_len = self
+                .session
+                .config()
+                .audio_fetch_params
+                .minimum_download_size;
             let mut symphonia_decoder = |audio_file, format| {
-                SymphoniaDecoder::new(audio_file, format).map(|mut decoder| {
+                SymphoniaDecoder::new(audio_file, format, buffer_len).map(|mut decoder| {
                     // For formats other that Vorbis, we'll try getting normalisation data from
                     // ReplayGain metadata fields, if present.
                     if normalisation_data.is_none() {
@@ -1164,8 +1488,6 @@ impl Future for PlayerInternal {
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
         // While this is written as a future, it still contains blocking code.
         // It must be run on its own thread.
-        let passthrough = self.config.passthrough;
-
         loop {
             let mut all_futures_completed_or_not_ready = true;
 
@@ -1267,88 +1589,29 @@ impl Future for PlayerInternal {
                     play_request_id,
                     ref mut decoder,
                     normalisation_factor,
-                    ref mut stream_position_ms,
-                    ref mut reported_nominal_start_time,
                     ..
                 } = self.state
                 {
                     match decoder.next_packet() {
-                        Ok(result) => {
-                            if let Some((ref packet_position, ref packet)) = result {
-                                let new_stream_position_ms = packet_position.position_ms;
-                                let expected_position_ms = std::mem::replace(
-                                    &mut *stream_position_ms,
-                                    new_stream_position_ms,
-                                );
-
-                                if !passthrough {
-                                    match packet.samples() {
-                                        Ok(_) => {
-                                            let new_stream_position = Duration::from_millis(
-                                                new_stream_position_ms as u64,
-                                            );
-
-                                            let now = Instant::now();
-
-                                            // Only notify if we're skipped some packets *or* we are behind.
-                                            // If we're ahead it's probably due to a buffer of the backend
-                                            // and we're actually in time.
-                                            let notify_about_position =
-                                                match *reported_nominal_start_time {
-                                                    None => true,
-                                                    Some(reported_nominal_start_time) => {
-                                                        let mut notify = false;
-
-                                                        if packet_position.skipped {
-                                                            if let Some(ahead) = new_stream_position
-                                                                .checked_sub(Duration::from_millis(
-                                                                    expected_position_ms as u64,
-                                                                ))
-                                                            {
-                                                                notify |=
-                                                                    ahead >= Duration::from_secs(1)
-                                                            }
-                                                        }
-
-                                                        if let Some(lag) = now
-                                                            .checked_duration_since(
-                                                                reported_nominal_start_time,
-                                                            )
-                                                        {
-                                                            if let Some(lag) =
-                                                                lag.checked_sub(new_stream_position)
-                                                            {
-                                                                notify |=
-                                                                    lag >= Duration::from_secs(1)
-                                                            }
-                                                        }
-
-                                                        notify
-                                                    }
-                                                };
-
-                                            if notify_about_position {
-                                                *reported_nominal_start_time =
-                                                    now.checked_sub(new_stream_position);
-                                                self.send_event(PlayerEvent::PositionCorrection {
-                                                    play_request_id,
-                                                    track_id,
-                                                    position_ms: new_stream_position_ms,
-                                                });
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!("Skipping to next track, unable to decode samples for track <{:?}>: {:?}", track_id, e);
-                                            self.send_event(PlayerEvent::EndOfTrack {
-                                                track_id,
-                                                play_request_id,
-                                            })
-                                        }
-                                    }
-                                }
+                        Ok(Some(packet)) => {
+                            let packets = if self.config.trim_silence {
+                                self.silence_trimmer.push(packet)
+                            } else {
+                                vec![packet]
+                            };
+
+                            self.handle_packets(packets, normalisation_factor);
+                        }
+                        Ok(None) => {
+                            if self.config.trim_silence {
+                                let trim = self.trim_trailing_silence();
+                                let packets = self.silence_trimmer.end_track(trim);
+                                self.handle_packets(packets, normalisation_factor);
                             }
 
-                            self.handle_packet(result, normalisation_factor);
+                            if self.state.is_playing() {
+                                self.handle_packet(None, normalisation_factor);
+                            }
                         }
                         Err(e) => {
                             error!("Skipping to next track, unable to get next packet for track <{:?}>: {:?}", track_id, e);
@@ -1396,6 +1659,8 @@ impl Future for PlayerInternal {
                 }
             }
 
+            self.prefetch_when_downloaded();
+
             if (!self.state.is_playing()) && all_futures_completed_or_not_ready {
                 return Poll::Pending;
             }
@@ -1404,6 +1669,62 @@ impl Future for PlayerInternal {
 }
 
 impl PlayerInternal {
+    fn new(
+        session: Session,
+        config: PlayerConfig,
+        commands: mpsc::UnboundedReceiver<PlayerCommand>,
+        sink: Box<dyn Sink>,
+        volume_getter: Box<dyn VolumeGetter + Send>,
+    ) -> Self {
+        let player_id = PLAYER_COUNTER.fetch_add(1, Ordering::AcqRel);
+        debug!("new Player [{}]", player_id);
+
+        let converter = Converter::new(config.ditherer);
+        let level_meter = LevelMeter::new(config.level_meter_interval);
+        let silence_trimmer = SilenceTrimmer::new(config.trim_silence_threshold_db);
+        let limiter = TruePeakLimiter::new(
+            config.normalisation_ceiling_dbtp,
+            config.normalisation_release_cf,
+        );
+
+        PlayerInternal {
+            session,
+            config,
+            commands,
+            load_handles: Arc::new(Mutex::new(HashMap::new())),
+
+            state: PlayerState::Stopped,
+            preload: PlayerPreload::None,
+            sink,
+            sink_status: SinkStatus::Closed,
+            sink_event_callback: None,
+            volume_getter,
+            event_senders: vec![],
+            level_meter_senders: vec![],
+            converter,
+
+            normalisation_peak: 0.0,
+            normalisation_integrator: 0.0,
+            limiter,
+
+            auto_normalise_as_album: false,
+
+            silence_trimmer,
+
+            prefetch_tracks: None,
+            prefetch_tx: None,
+
+            fader: Fader::default(),
+
+            time_stretcher: TimeStretcher::default(),
+
+            level_meter,
+
+            player_id,
+            play_request_id_generator: SeqGenerator::new(0),
+        }
+    }
+
     fn ensure_sink_running(&mut self) {
         if self.sink_status != SinkStatus::Running {
             trace!("== Starting sink ==");
@@ -1414,7 +1735,7 @@ impl PlayerInternal {
                 Ok(()) => self.sink_status = SinkStatus::Running,
                 Err(e) => {
                     error!("{}", e);
-                    self.handle_pause();
+                    self.pause_playback();
                 }
             }
         }
@@ -1424,6 +1745,10 @@ impl PlayerInternal {
         match self.sink_status {
             SinkStatus::Running => {
                 trace!("== Stopping sink ==");
+                if self.is_metering() {
+                    self.level_meter.reset();
+                    self.publish_levels(LevelMeterReading::default());
+                }
                 match self.sink.stop() {
                     Ok(()) => {
                         self.sink_status = if temporarily {
@@ -1454,6 +1779,14 @@ impl PlayerInternal {
     }
 
     fn handle_player_stop(&mut self) {
+        if matches!(self.state, PlayerState::Playing { .. })
+            && self.fade_out(FadeOutAction::Stop, self.config.fade_out)
+        {
+            return;
+        }
+
+        self.fader.action = None;
+
         match self.state {
             PlayerState::Playing {
                 track_id,
@@ -1476,6 +1809,7 @@ impl PlayerInternal {
                 ..
             } => {
                 self.ensure_sink_stopped(false);
+                self.limiter.reset();
                 self.send_event(PlayerEvent::Stopped {
                     track_id,
                     play_request_id,
@@ -1498,14 +1832,26 @@ impl PlayerInternal {
                 stream_position_ms,
                 ..
             } => {
-                self.state.paused_to_playing();
+                self.state.paused_to_playing(self.time_stretcher.speed());
                 self.send_event(PlayerEvent::Playing {
                     track_id,
                     play_request_id,
                     position_ms: stream_position_ms,
                 });
+                self.start_fade_in();
                 self.ensure_sink_running();
             }
+            PlayerState::Playing { .. } if self.fader.action.is_some() => {
+                match self.fader.action {
+                    Some(FadeOutAction::Pause) => {
+                        // resume from wherever the fade-out got to
+                        self.fader.action = None;
+                        self.start_fade_in();
+                    }
+                    Some(FadeOutAction::Load { ref mut play, .. }) => *play = true,
+                    _ => warn!("Player::play called while stopping"),
+                }
+            }
             PlayerState::Loading {
                 ref mut start_playback,
                 ..
@@ -1516,7 +1862,24 @@ impl PlayerInternal {
         }
     }
 
-    fn handle_pause(&mut self) {
+    fn handle_pause(&mut self, fade: Duration) {
+        match self.fader.action {
+            Some(FadeOutAction::Load { ref mut play, .. }) => *play = false,
+            Some(FadeOutAction::Stop) => (),
+            // a pause that is already fading out is restarted with the new duration
+            Some(FadeOutAction::Pause) | None
+                if matches!(self.state, PlayerState::Playing { .. }) =>
+            {
+                if !self.fade_out(FadeOutAction::Pause, fade) {
+                    self.fader.action = None;
+                    self.pause_playback();
+                }
+            }
+            _ => self.pause_playback(),
+        }
+    }
+
+    fn pause_playback(&mut self) {
         match self.state {
             PlayerState::Paused { .. } => self.ensure_sink_stopped(false),
             PlayerState::Playing {
@@ -1544,36 +1907,199 @@ impl PlayerInternal {
         }
     }
 
-    fn handle_packet(
+    // Ramps up the gain from wherever it is now, e.g. silence after a pause
+    // or a fade-out that was cancelled halfway.
+    fn start_fade_in(&mut self) {
+        let fade = if self.config.passthrough {
+            Duration::ZERO
+        } else {
+            self.config.fade_in
+        };
+
+        self.fader
+            .fade_in(fade, self.sink_status != SinkStatus::Running);
+    }
+
+    // Starts ramping down the gain and defers `action` until the fade-out completes.
+    // Returns `false` if there is nothing to fade, in which case the caller should
+    // carry out the action immediately.
+    fn fade_out(&mut self, action: FadeOutAction, fade: Duration) -> bool {
+        if self.config.passthrough {
+            return false;
+        }
+
+        trace!("Fading out over {:?} before {:?}", fade, action);
+        self.fader.fade_out(action, fade)
+    }
+
+    fn finish_fade_out(&mut self) {
+        match self.fader.action.take() {
+            Some(FadeOutAction::Pause) => self.pause_playback(),
+            Some(FadeOutAction::Stop) => self.handle_player_stop(),
+            Some(FadeOutAction::Load {
+                track_id,
+                play_request_id,
+                play,
+                position_ms,
+            }) => {
+                // the play request id was already announced when the fade-out began
+                if let Err(e) = self.load_track_now(track_id, play_request_id, play, position_ms) {
+                    error!("Error handling command: {}", e);
+                }
+            }
+            None => (),
+        }
+    }
+
+    // Plays decoded packets, keeping track of their position.
+    fn handle_packets(
         &mut self,
-        packet: Option<(AudioPacketPosition, AudioPacket)>,
+        packets: Vec<(AudioPacketPosition, AudioPacket)>,
         normalisation_factor: f64,
     ) {
-        match packet {
-            Some((_, mut packet)) => {
-                if !packet.is_empty() {
-                    if let AudioPacket::Samples(ref mut data) = packet {
-                        // Get the volume for the packet.
-                        // In the case of hardware volume control this will
-                        // always be 1.0 (no change).
-                        let volume = self.volume_getter.attenuation_factor();
+        for (packet_position, packet) in packets {
+            // a fade out may have completed, or the sink failed
+            if !self.state.is_playing() {
+                break;
+            }
 
-                        // For the basic normalisation method, a normalisation factor of 1.0 indicates that
-                        // there is nothing to normalise (all samples should pass unaltered). For the
-                        // dynamic method, there may still be peaks that we want to shave off.
+            self.handle_stream_position(&packet_position, &packet);
+            self.handle_packet(Some((packet_position, packet)), normalisation_factor);
+        }
+    }
 
-                        // No matter the case we apply volume attenuation last if there is any.
-                        if !self.config.normalisation {
-                            if volume < 1.0 {
-                                for sample in data.iter_mut() {
-                                    *sample *= volume;
+    fn handle_stream_position(
+        &mut self,
+        packet_position: &AudioPacketPosition,
+        packet: &AudioPacket,
+    ) {
+        let playback_speed = self.time_stretcher.speed();
+
+        if let PlayerState::Playing {
+            track_id,
+            play_request_id,
+            ref mut stream_position_ms,
+            ref mut reported_nominal_start_time,
+            ..
+        } = self.state
+        {
+            let new_stream_position_ms = packet_position.position_ms;
+            let expected_position_ms =
+                std::mem::replace(&mut *stream_position_ms, new_stream_position_ms);
+
+            if !self.config.passthrough {
+                match packet.samples() {
+                    Ok(_) => {
+                        let new_stream_position =
+                            Duration::from_millis(new_stream_position_ms as u64);
+
+                        let now = Instant::now();
+
+                        // Only notify if we're skipped some packets *or* we are behind.
+                        // If we're ahead it's probably due to a buffer of the backend
+                        // and we're actually in time.
+                        let notify_about_position = match *reported_nominal_start_time {
+                            None => true,
+                            Some(reported_nominal_start_time) => {
+                                let mut notify = false;
+
+                                if packet_position.skipped {
+                                    if let Some(ahead) = new_stream_position.checked_sub(
+                                        Duration::from_millis(expected_position_ms as u64),
+                                    ) {
+                                        notify |= ahead >= Duration::from_secs(1)
+                                    }
+                                }
+
+                                if let Some(lag) = now
+                                    .checked_duration_since(reported_nominal_start_time)
+                                    .map(|elapsed| elapsed.mul_f64(playback_speed))
+                                {
+                                    if let Some(lag) = lag.checked_sub(new_stream_position) {
+                                        notify |= lag >= Duration::from_secs(1)
+                                    }
                                 }
+
+                                notify
                             }
-                        } else if self.config.normalisation_method == NormalisationMethod::Basic
-                            && (normalisation_factor < 1.0 || volume < 1.0)
+                        };
+
+                        if notify_about_position {
+                            *reported_nominal_start_time =
+                                nominal_start_time(now, new_stream_position_ms, playback_speed);
+                            self.send_event(PlayerEvent::PositionCorrection {
+                                play_request_id,
+                                track_id,
+                                position_ms: new_stream_position_ms,
+                            });
+                        }
+                    }
+                    Err(e) => {
+                        error!("Skipping to next track, unable to decode samples for track <{:?}>: {:?}", track_id, e);
+                        self.send_event(PlayerEvent::EndOfTrack {
+                            track_id,
+                            play_request_id,
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    // Trailing silence is kept when the next track continues the album, or when it is
+    // still loading and it can't be told yet.
+    fn trim_trailing_silence(&self) -> bool {
+        match self.preload {
+            PlayerPreload::None => true,
+            PlayerPreload::Loading { .. } => false,
+            PlayerPreload::Ready {
+                ref loaded_track, ..
+            } => !self
+                .silence_trimmer
+                .continues_into(&loaded_track.audio_item),
+        }
+    }
+
+    fn handle_packet(
+        &mut self,
+        packet: Option<(AudioPacketPosition, AudioPacket)>,
+        normalisation_factor: f64,
+    ) {
+        match packet {
+            Some((_, mut packet)) => {
+                if let AudioPacket::Samples(ref mut data) = packet {
+                    if !self.time_stretcher.is_bypassed() {
+                        *data = self.time_stretcher.process(data);
+                    }
+                }
+
+                if !packet.is_empty() {
+                    let mut fade_out_completed = false;
+
+                    if let AudioPacket::Samples(ref mut data) = packet {
+                        // Get the volume for the packet, which may ramp from the previous
+                        // volume to a new one. In the case of hardware volume control this
+                        // will always be 1.0 (no change).
+                        let mut volume = self
+                            .volume_getter
+                            .attenuation_ramp(data.len() / NUM_CHANNELS as usize);
+
+                        // For the basic normalisation method, a normalisation factor of 1.0 indicates that
+                        // there is nothing to normalise (all samples should pass unaltered). For the
+                        // dynamic method, there may still be peaks that we want to shave off.
+
+                        // No matter the case we apply volume attenuation last if there is any.
+                        if !self.config.normalisation {
+                            if volume.attenuates() {
+                                for sample in data.iter_mut() {
+                                    *sample *= volume.next_factor();
+                                }
+                            }
+                        } else if self.config.normalisation_method == NormalisationMethod::Basic
+                            && (normalisation_factor < 1.0 || volume.attenuates())
                         {
                             for sample in data.iter_mut() {
-                                *sample *= normalisation_factor * volume;
+                                *sample *= normalisation_factor * volume.next_factor();
                             }
                         } else if self.config.normalisation_method == NormalisationMethod::Dynamic {
                             // zero-cost shorthands
@@ -1658,14 +2184,41 @@ impl PlayerInternal {
                                     *sample *= db_to_ratio(-self.normalisation_peak);
                                 }
 
-                                *sample *= volume;
+                                *sample *= volume.next_factor();
+                            }
+                        } else if self.config.normalisation_method == NormalisationMethod::Limiter {
+                            // The limiter delays the samples by its look-ahead, the volume
+                            // applies to them as they come out.
+                            for sample in data.iter_mut() {
+                                *sample *= normalisation_factor;
+                            }
+
+                            self.limiter.process(data);
+
+                            if volume.attenuates() {
+                                for sample in data.iter_mut() {
+                                    *sample *= volume.next_factor();
+                                }
+                            }
+                        }
+
+                        if self.fader.is_fading() {
+                            fade_out_completed = self.fader.apply(data);
+                        }
+
+                        if self.is_metering() {
+                            if let Some(levels) = self.level_meter.process(data) {
+                                self.publish_levels(levels);
                             }
                         }
                     }
 
                     if let Err(e) = self.sink.write(packet, &mut self.converter) {
                         error!("{}", e);
-                        self.handle_pause();
+                        self.fader.action = None;
+                        self.pause_playback();
+                    } else if fade_out_completed {
+                        self.finish_fade_out();
                     }
                 }
             }
@@ -1678,10 +2231,20 @@ impl PlayerInternal {
                     ..
                 } = self.state
                 {
-                    self.send_event(PlayerEvent::EndOfTrack {
-                        track_id,
-                        play_request_id,
-                    })
+                    // A pending stop or load supersedes the end of track, a pending
+                    // pause is moot now that there's nothing left to play.
+                    match self.fader.action.take() {
+                        Some(FadeOutAction::Pause) | None => {
+                            self.send_event(PlayerEvent::EndOfTrack {
+                                track_id,
+                                play_request_id,
+                            })
+                        }
+                        Some(action) => {
+                            self.fader.action = Some(action);
+                            self.finish_fade_out();
+                        }
+                    }
                 } else {
                     error!("PlayerInternal handle_packet: Invalid PlayerState");
                     exit(1);
@@ -1703,18 +2266,25 @@ impl PlayerInternal {
 
         let position_ms = loaded_track.stream_position_ms;
 
-        let mut config = self.config.clone();
-        if config.normalisation_type == NormalisationType::Auto {
-            if self.auto_normalise_as_album {
-                config.normalisation_type = NormalisationType::Album;
-            } else {
-                config.normalisation_type = NormalisationType::Track;
-            }
-        };
-        let normalisation_factor =
-            NormalisationData::get_factor(&config, loaded_track.normalisation_data);
+        let normalisation_factor = self.normalisation_factor(loaded_track.normalisation_data);
+
+        self.silence_trimmer
+            .start_track(&loaded_track.audio_item, position_ms);
+
+        self.time_stretcher.reset();
+        let playback_speed = self.item_playback_speed(&loaded_track.audio_item);
+        if playback_speed != self.time_stretcher.speed() || playback_speed != 1.0 {
+            self.time_stretcher.set_speed(playback_speed);
+            self.send_event(PlayerEvent::PlaybackSpeedChanged {
+                play_request_id,
+                track_id,
+                position_ms,
+                speed: playback_speed,
+            });
+        }
 
         if start_playback {
+            self.start_fade_in();
             self.ensure_sink_running();
             self.send_event(PlayerEvent::Playing {
                 track_id,
@@ -1733,8 +2303,11 @@ impl PlayerInternal {
                 duration_ms: loaded_track.duration_ms,
                 bytes_per_second: loaded_track.bytes_per_second,
                 stream_position_ms: loaded_track.stream_position_ms,
-                reported_nominal_start_time: Instant::now()
-                    .checked_sub(Duration::from_millis(position_ms as u64)),
+                reported_nominal_start_time: nominal_start_time(
+                    Instant::now(),
+                    position_ms,
+                    playback_speed,
+                ),
                 suggested_to_preload_next_track: false,
                 is_explicit: loaded_track.is_explicit,
             };
@@ -1776,8 +2349,39 @@ impl PlayerInternal {
 
         self.send_event(PlayerEvent::PlayRequestIdChanged { play_request_id });
 
+        // Let the current track fade out first. The new play request id is already
+        // announced so that events of the outgoing track are not mistaken for it.
+        if matches!(self.state, PlayerState::Playing { .. })
+            && self.fade_out(
+                FadeOutAction::Load {
+                    track_id,
+                    play_request_id,
+                    play,
+                    position_ms,
+                },
+                self.config.fade_out,
+            )
+        {
+            return Ok(());
+        }
+
+        self.load_track_now(track_id, play_request_id, play, position_ms)
+    }
+
+    fn load_track_now(
+        &mut self,
+        track_id: SpotifyId,
+        play_request_id: u64,
+        play: bool,
+        position_ms: u32,
+    ) -> PlayerResult {
+        self.fader.action = None;
+
+        // The limiter keeps its look-ahead across tracks, so that gapless playback neither
+        // drops the end of one track nor delays the next with silence.
         if !self.config.gapless {
             self.ensure_sink_stopped(play);
+            self.limiter.reset();
         }
 
         if matches!(self.state, PlayerState::Invalid { .. }) {
@@ -2033,6 +2637,10 @@ impl PlayerInternal {
         if let Some(decoder) = self.state.decoder() {
             match decoder.seek(position_ms) {
                 Ok(new_position_ms) => {
+                    self.time_stretcher.reset();
+                    self.limiter.reset();
+                    self.silence_trimmer.seek();
+
                     if let PlayerState::Playing {
                         ref mut stream_position_ms,
                         track_id,
@@ -2070,12 +2678,115 @@ impl PlayerInternal {
         } = self.state
         {
             *reported_nominal_start_time =
-                Instant::now().checked_sub(Duration::from_millis(position_ms as u64));
+                nominal_start_time(Instant::now(), position_ms, self.time_stretcher.speed());
         }
 
         Ok(())
     }
 
+    fn item_playback_speed(&self, audio_item: &AudioItem) -> f64 {
+        let is_episode = matches!(audio_item.unique_fields, UniqueFields::Episode { .. });
+        if self.config.passthrough || (self.config.playback_speed_episodes_only && !is_episode) {
+            1.0
+        } else {
+            self.config.playback_speed
+        }
+    }
+
+    fn normalisation_factor(&self, normalisation_data: NormalisationData) -> f64 {
+        let mut config = self.config.clone();
+        if config.normalisation_type == NormalisationType::Auto {
+            if self.auto_normalise_as_album {
+                config.normalisation_type = NormalisationType::Album;
+            } else {
+                config.normalisation_type = NormalisationType::Track;
+            }
+        };
+        NormalisationData::get_factor(&config, normalisation_data)
+    }
+
+    // Recomputes the normalisation factor of the current track after a settings change.
+    fn update_normalisation_factor(&mut self) {
+        if let PlayerState::Playing {
+            normalisation_data, ..
+        }
+        | PlayerState::Paused {
+            normalisation_data, ..
+        } = self.state
+        {
+            let factor = self.normalisation_factor(normalisation_data);
+
+            if let PlayerState::Playing {
+                ref mut normalisation_factor,
+                ..
+            }
+            | PlayerState::Paused {
+                ref mut normalisation_factor,
+                ..
+            } = self.state
+            {
+                *normalisation_factor = factor;
+            }
+        }
+    }
+
+    // Starts the gain reduction of the dynamic and look-ahead limiters afresh, as what they
+    // held was meant for the previous method.
+    fn reset_normalisation(&mut self) {
+        self.normalisation_integrator = 0.0;
+        self.normalisation_peak = 0.0;
+        self.limiter.reset();
+        self.update_normalisation_factor();
+    }
+
+    fn handle_command_set_playback_speed(&mut self, speed: f64) {
+        self.config.playback_speed = speed.clamp(
+            *VALID_PLAYBACK_SPEED_RANGE.start(),
+            *VALID_PLAYBACK_SPEED_RANGE.end(),
+        );
+
+        if let PlayerState::Playing {
+            track_id,
+            play_request_id,
+            ref audio_item,
+            stream_position_ms,
+            ..
+        }
+        | PlayerState::Paused {
+            track_id,
+            play_request_id,
+            ref audio_item,
+            stream_position_ms,
+            ..
+        } = self.state
+        {
+            let playback_speed = self.item_playback_speed(audio_item);
+            if playback_speed == self.time_stretcher.speed() {
+                return;
+            }
+
+            // the buffered input was meant for the old speed, resume from where we are
+            self.time_stretcher.reset();
+            self.time_stretcher.set_speed(playback_speed);
+
+            if let PlayerState::Playing {
+                ref mut reported_nominal_start_time,
+                ..
+            } = self.state
+            {
+                *reported_nominal_start_time =
+                    nominal_start_time(Instant::now(), stream_position_ms, playback_speed);
+            }
+
+            self.send_event(PlayerEvent::PlaybackSpeedChanged {
+                play_request_id,
+                track_id,
+                position_ms: stream_position_ms,
+                speed: playback_speed,
+            });
+        }
+    }
+
     fn handle_command(&mut self, cmd: PlayerCommand) -> PlayerResult {
         debug!("command={:?}", cmd);
         match cmd {
@@ -2087,18 +2798,30 @@ impl PlayerInternal {
 
             PlayerCommand::Preload { track_id } => self.handle_command_preload(track_id),
 
+            PlayerCommand::Prefetch(track_ids) => self.handle_command_prefetch(track_ids),
+
             PlayerCommand::Seek(position_ms) => self.handle_command_seek(position_ms)?,
 
+            PlayerCommand::SetPlaybackSpeed(speed) => self.handle_command_set_playback_speed(speed),
+
             PlayerCommand::Play => self.handle_play(),
 
-            PlayerCommand::Pause => self.handle_pause(),
+            PlayerCommand::Pause => self.handle_pause(self.config.fade_out),
+
+            PlayerCommand::PauseWithFade(fade) => self.handle_pause(fade),
 
             PlayerCommand::Stop => self.handle_player_stop(),
 
-            PlayerCommand::SetSession(session) => self.session = session,
+            PlayerCommand::SetSession(session) => {
+                self.session = session;
+                // the prefetch task finishes its download for the previous session and stops
+                self.prefetch_tx = None;
+            }
 
             PlayerCommand::AddEventSender(sender) => self.event_senders.push(sender),
 
+            PlayerCommand::AddLevelMeterSender(sender) => self.level_meter_senders.push(sender),
+
             PlayerCommand::SetSinkEventCallback(callback) => self.sink_event_callback = callback,
 
             PlayerCommand::EmitVolumeChangedEvent(volume) => {
@@ -2117,6 +2840,22 @@ impl PlayerInternal {
                 self.send_event(PlayerEvent::AutoPlayChanged { auto_play })
             }
 
+            PlayerCommand::EmitSleepTimerChangedEvent(sleep_timer) => {
+                self.send_event(PlayerEvent::SleepTimerChanged { sleep_timer })
+            }
+
+            PlayerCommand::EmitActiveDeviceChangedEvent {
+                device_id,
+                device_name,
+            } => self.send_event(PlayerEvent::ActiveDeviceChanged {
+                device_id,
+                device_name,
+            }),
+
+            PlayerCommand::EmitUserRejectedEvent(user_name) => {
+                self.send_event(PlayerEvent::UserRejected { user_name })
+            }
+
             PlayerCommand::EmitSessionClientChangedEvent {
                 client_id,
                 client_name,
@@ -2146,7 +2885,44 @@ impl PlayerInternal {
             }),
 
             PlayerCommand::SetAutoNormaliseAsAlbum(setting) => {
-                self.auto_normalise_as_album = setting
+                self.auto_normalise_as_album = setting;
+                self.update_normalisation_factor();
+            }
+
+            PlayerCommand::SetNormalisation(normalisation) => {
+                self.config.normalisation = normalisation;
+                self.reset_normalisation();
+            }
+
+            PlayerCommand::SetNormalisationType(normalisation_type) => {
+                self.config.normalisation_type = normalisation_type;
+                self.update_normalisation_factor();
+            }
+
+            PlayerCommand::SetNormalisationMethod(normalisation_method) => {
+                self.config.normalisation_method = normalisation_method;
+                self.reset_normalisation();
+            }
+
+            PlayerCommand::SetNormalisationPregain(pregain_db) => {
+                self.config.normalisation_pregain_db = pregain_db;
+                self.update_normalisation_factor();
+            }
+
+            PlayerCommand::SetGapless(gapless) => self.config.gapless = gapless,
+
+            PlayerCommand::SetDitherer(ditherer) => {
+                self.config.ditherer = ditherer;
+                self.converter = Converter::new(ditherer);
+            }
+
+            PlayerCommand::SetBitrate(bitrate) => {
+                if self.config.bitrate != bitrate {
+                    self.config.bitrate = bitrate;
+                    // the prefetch task has a copy of the config, start one that prefetches
+                    // at the new bitrate
+                    self.prefetch_tx = None;
+                }
             }
 
             PlayerCommand::EmitFilterExplicitContentChangedEvent(filter) => {
@@ -2186,6 +2962,40 @@ impl PlayerInternal {
             .retain(|sender| sender.send(event.clone()).is_ok());
     }
 
+    fn is_metering(&self) -> bool {
+        !self.level_meter_senders.is_empty() || self.config.level_meter_events
+    }
+
+    fn publish_levels(&mut self, levels: LevelMeterReading) {
+        self.level_meter_senders
+            .retain(|sender| sender.send(levels).is_ok());
+
+        if self.config.level_meter_events {
+            match self.state {
+                PlayerState::Playing {
+                    track_id,
+                    play_request_id,
+                    ..
+                }
+                | PlayerState::Paused {
+                    track_id,
+                    play_request_id,
+                    ..
+                }
+                | PlayerState::EndOfTrack {
+                    track_id,
+                    play_request_id,
+                    ..
+                } => self.send_event(PlayerEvent::Levels {
+                    play_request_id,
+                    track_id,
+                    levels,
+                }),
+                _ => (),
+            }
+        }
+    }
+
     fn load_track(
         &mut self,
         spotify_id: SpotifyId,
@@ -2222,6 +3032,61 @@ impl PlayerInternal {
         result_rx.map_err(|_| ())
     }
 
+    fn handle_command_prefetch(&mut self, mut track_ids: Vec<SpotifyId>) {
+        if self.config.prefetch_tracks == 0 {
+            return;
+        }
+
+        if self.session.cache().is_none() {
+            return;
+        }
+
+        track_ids.truncate(self.config.prefetch_tracks);
+        self.prefetch_tracks = Some(track_ids);
+    }
+
+    // Hands the tracks to prefetch to the prefetch task once the current track is downloaded
+    // completely, so that prefetching doesn't take bandwidth from what is playing.
+    fn prefetch_when_downloaded(&mut self) {
+        if self.prefetch_tracks.is_none() {
+            return;
+        }
+
+        let downloaded = match self.state {
+            PlayerState::Playing {
+                ref stream_loader_controller,
+                ..
+            }
+            | PlayerState::Paused {
+                ref stream_loader_controller,
+                ..
+            } => stream_loader_controller.range_to_end_available(),
+            PlayerState::Loading { .. } => false,
+            _ => true,
+        };
+
+        if !downloaded {
+            return;
+        }
+
+        let track_ids = self.prefetch_tracks.take().unwrap_or_default();
+
+        let prefetch_tx = self.prefetch_tx.get_or_insert_with(|| {
+            let (prefetch_tx, prefetch_rx) = mpsc::unbounded_channel();
+            let loader = PlayerTrackLoader {
+                session: self.session.clone(),
+                config: self.config.clone(),
+            };
+            self.session.spawn(loader.prefetch(prefetch_rx));
+            prefetch_tx
+        });
+
+        if prefetch_tx.send(track_ids).is_err() {
+            error!("Prefetch task is gone");
+            self.prefetch_tx = None;
+        }
+    }
+
     fn preload_data_before_playback(&mut self) -> PlayerResult {
         if let PlayerState::Playing {
             bytes_per_second,
@@ -2229,14 +3094,14 @@ impl PlayerInternal {
             ..
         } = self.state
         {
-            let read_ahead_during_playback = AudioFetchParams::get().read_ahead_during_playback;
+            let params = &self.session.config().audio_fetch_params;
             // Request our read ahead range
-            let request_data_length =
-                (read_ahead_during_playback.as_secs_f32() * bytes_per_second as f32) as usize;
+            let request_data_length = (params.read_ahead_during_playback.as_secs_f32()
+                * bytes_per_second as f32) as usize;
 
             // Request the part we want to wait for blocking. This effectively means we wait for the previous request to partially complete.
-            let wait_for_data_length =
-                (read_ahead_during_playback.as_secs_f32() * bytes_per_second as f32) as usize;
+            let wait_for_data_length = (params.read_ahead_before_playback.as_secs_f32()
+                * bytes_per_second as f32) as usize;
 
             stream_loader_controller.fetch_next_and_wait(request_data_length, wait_for_data_length)
         } else {
@@ -2282,12 +3147,22 @@ impl fmt::Debug for PlayerCommand {
             PlayerCommand::Preload { track_id } => {
                 f.debug_tuple("Preload").field(&track_id).finish()
             }
+            PlayerCommand::Prefetch(track_ids) => {
+                f.debug_tuple("Prefetch").field(&track_ids).finish()
+            }
             PlayerCommand::Play => f.debug_tuple("Play").finish(),
             PlayerCommand::Pause => f.debug_tuple("Pause").finish(),
+            PlayerCommand::PauseWithFade(fade) => {
+                f.debug_tuple("PauseWithFade").field(&fade).finish()
+            }
             PlayerCommand::Stop => f.debug_tuple("Stop").finish(),
             PlayerCommand::Seek(position) => f.debug_tuple("Seek").field(&position).finish(),
+            PlayerCommand::SetPlaybackSpeed(speed) => {
+                f.debug_tuple("SetPlaybackSpeed").field(&speed).finish()
+            }
             PlayerCommand::SetSession(_) => f.debug_tuple("SetSession").finish(),
             PlayerCommand::AddEventSender(_) => f.debug_tuple("AddEventSender").finish(),
+            PlayerCommand::AddLevelMeterSender(_) => f.debug_tuple("AddLevelMeterSender").finish(),
             PlayerCommand::SetSinkEventCallback(_) => {
                 f.debug_tuple("SetSinkEventCallback").finish()
             }
@@ -2299,6 +3174,32 @@ impl fmt::Debug for PlayerCommand {
                 .debug_tuple("SetAutoNormaliseAsAlbum")
                 .field(&setting)
                 .finish(),
+            PlayerCommand::SetNormalisation(normalisation) => f
+                .debug_tuple("SetNormalisation")
+                .field(&normalisation)
+                .finish(),
+            PlayerCommand::SetNormalisationType(normalisation_type) => f
+                .debug_tuple("SetNormalisationType")
+                .field(&normalisation_type)
+                .finish(),
+            PlayerCommand::SetNormalisationMethod(normalisation_method) => f
+                .debug_tuple("SetNormalisationMethod")
+                .field(&normalisation_method)
+                .finish(),
+            PlayerCommand::SetNormalisationPregain(pregain_db) => f
+                .debug_tuple("SetNormalisationPregain")
+                .field(&pregain_db)
+                .finish(),
+            PlayerCommand::SetGapless(gapless) => {
+                f.debug_tuple("SetGapless").field(&gapless).finish()
+            }
+            PlayerCommand::SetDitherer(ditherer) => f
+                .debug_tuple("SetDitherer")
+                .field(&ditherer.is_some())
+                .finish(),
+            PlayerCommand::SetBitrate(bitrate) => {
+                f.debug_tuple("SetBitrate").field(&bitrate).finish()
+            }
             PlayerCommand::EmitFilterExplicitContentChangedEvent(filter) => f
                 .debug_tuple("EmitFilterExplicitContentChangedEvent")
                 .field(&filter)
@@ -2344,6 +3245,22 @@ impl fmt::Debug for PlayerCommand {
                 .debug_tuple("EmitAutoPlayChangedEvent")
                 .field(&auto_play)
                 .finish(),
+            PlayerCommand::EmitSleepTimerChangedEvent(sleep_timer) => f
+                .debug_tuple("EmitSleepTimerChangedEvent")
+                .field(&sleep_timer)
+                .finish(),
+            PlayerCommand::EmitActiveDeviceChangedEvent {
+                device_id,
+                device_name,
+            } => f
+                .debug_tuple("EmitActiveDeviceChangedEvent")
+                .field(&device_id)
+                .field(&device_name)
+                .finish(),
+            PlayerCommand::EmitUserRejectedEvent(user_name) => f
+                .debug_tuple("EmitUserRejectedEvent")
+                .field(&user_name)
+                .finish(),
         }
     }
 }
@@ -2452,3 +3369,214 @@ where
         Some(self.length)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        audio_backend::SinkResult,
+        config::PlayerConfig,
+        core::{spotify_id::SpotifyItemType, SessionConfig},
+        dither::{mk_ditherer, TriangularDitherer},
+        mixer::NoOpVolume,
+    };
+
+    struct NullSink;
+
+    impl Sink for NullSink {
+        fn write(&mut self, _: AudioPacket, _: &mut Converter) -> SinkResult<()> {
+            Ok(())
+        }
+    }
+
+    fn player(config: PlayerConfig) -> PlayerInternal {
+        let (_, commands) = mpsc::unbounded_channel();
+        PlayerInternal::new(
+            Session::new(SessionConfig::default(), None),
+            config,
+            commands,
+            Box::new(NullSink),
+            Box::new(NoOpVolume),
+        )
+    }
+
+    const NORMALISATION_DATA: NormalisationData = NormalisationData {
+        track_gain_db: -6.0,
+        track_peak: 0.25,
+        album_gain_db: -3.0,
+        album_peak: 0.25,
+    };
+
+    fn normalisation_db(player: &PlayerInternal) -> f64 {
+        ratio_to_db(player.normalisation_factor(NORMALISATION_DATA))
+    }
+
+    #[tokio::test]
+    async fn applies_the_normalisation_settings() {
+        let mut player = player(PlayerConfig {
+            normalisation: true,
+            normalisation_method: NormalisationMethod::Dynamic,
+            normalisation_type: NormalisationType::Track,
+            normalisation_pregain_db: 0.0,
+            ..Default::default()
+        });
+        assert!((normalisation_db(&player) + 6.0).abs() < 1e-9);
+
+        player
+            .handle_command(PlayerCommand::SetNormalisationPregain(2.0))
+            .unwrap();
+        assert!((normalisation_db(&player) + 4.0).abs() < 1e-9);
+
+        player
+            .handle_command(PlayerCommand::SetNormalisationType(
+                NormalisationType::Album,
+            ))
+            .unwrap();
+        assert!((normalisation_db(&player) + 1.0).abs() < 1e-9);
+
+        player
+            .handle_command(PlayerCommand::SetNormalisationType(NormalisationType::Auto))
+            .unwrap();
+        assert!((normalisation_db(&player) + 4.0).abs() < 1e-9);
+        player
+            .handle_command(PlayerCommand::SetAutoNormaliseAsAlbum(true))
+            .unwrap();
+        assert!((normalisation_db(&player) + 1.0).abs() < 1e-9);
+
+        player
+            .handle_command(PlayerCommand::SetNormalisation(false))
+            .unwrap();
+        assert_eq!(player.normalisation_factor(NORMALISATION_DATA), 1.0);
+    }
+
+    #[tokio::test]
+    async fn starts_the_gain_reduction_afresh_for_another_method() {
+        let mut player = player(PlayerConfig {
+            normalisation: true,
+            normalisation_method: NormalisationMethod::Dynamic,
+            ..Default::default()
+        });
+
+        player.normalisation_integrator = 0.5;
+        player.normalisation_peak = 0.5;
+        player
+            .handle_command(PlayerCommand::SetNormalisationPregain(1.0))
+            .unwrap();
+        assert_eq!(player.normalisation_integrator, 0.5);
+
+        player
+            .handle_command(PlayerCommand::SetNormalisationMethod(
+                NormalisationMethod::Limiter,
+            ))
+            .unwrap();
+        assert_eq!(
+            player.config.normalisation_method,
+            NormalisationMethod::Limiter
+        );
+        assert_eq!(player.normalisation_integrator, 0.0);
+        assert_eq!(player.normalisation_peak, 0.0);
+
+        player.normalisation_integrator = 0.5;
+        player
+            .handle_command(PlayerCommand::SetNormalisation(false))
+            .unwrap();
+        assert_eq!(player.normalisation_integrator, 0.0);
+    }
+
+    #[tokio::test]
+    async fn applies_gapless_and_dither() {
+        let mut player = player(PlayerConfig {
+            gapless: true,
+            ditherer: None,
+            ..Default::default()
+        });
+
+        player
+            .handle_command(PlayerCommand::SetGapless(false))
+            .unwrap();
+        assert!(!player.config.gapless);
+
+        // without dither every sample of the same value converts the same
+        let samples = vec![0.3; 256];
+        let converted = player.converter.f64_to_s16(&samples);
+        assert!(converted.iter().all(|sample| *sample == converted[0]));
+
+        player
+            .handle_command(PlayerCommand::SetDitherer(Some(
+                mk_ditherer::<TriangularDitherer>,
+            )))
+            .unwrap();
+        let converted = player.converter.f64_to_s16(&samples);
+        assert!(converted.iter().any(|sample| *sample != converted[0]));
+    }
+
+    #[tokio::test]
+    async fn restarts_prefetching_at_another_bitrate() {
+        let mut player = player(PlayerConfig {
+            bitrate: Bitrate::Bitrate160,
+            ..Default::default()
+        });
+        let (prefetch_tx, _prefetch_rx) = mpsc::unbounded_channel();
+        player.prefetch_tx = Some(prefetch_tx);
+
+        player
+            .handle_command(PlayerCommand::SetBitrate(Bitrate::Bitrate160))
+            .unwrap();
+        assert!(player.prefetch_tx.is_some());
+
+        player
+            .handle_command(PlayerCommand::SetBitrate(Bitrate::Bitrate320))
+            .unwrap();
+        assert_eq!(player.config.bitrate, Bitrate::Bitrate320);
+        assert!(player.prefetch_tx.is_none());
+    }
+
+    fn track(id: u128) -> SpotifyId {
+        SpotifyId {
+            id,
+            item_type: SpotifyItemType::Track,
+        }
+    }
+
+    fn prefetch_all(queue: &mut PrefetchQueue, file_size: u64) -> Vec<u128> {
+        let mut prefetched = Vec::new();
+        while let Some(track_id) = queue.next() {
+            prefetched.push(track_id.id);
+            queue.prefetched(file_size);
+        }
+        prefetched
+    }
+
+    #[test]
+    fn prefetches_in_order() {
+        let mut queue = PrefetchQueue::new(None);
+        assert_eq!(queue.next(), None);
+
+        queue.replace(vec![track(1), track(2), track(3)]);
+        assert_eq!(prefetch_all(&mut queue, 1000), [1, 2, 3]);
+    }
+
+    #[test]
+    fn replaces_the_tracks_not_prefetched_yet() {
+        let mut queue = PrefetchQueue::new(None);
+        queue.replace(vec![track(1), track(2), track(3)]);
+        assert_eq!(queue.next(), Some(track(1)));
+
+        queue.replace(vec![track(4), track(5)]);
+        assert_eq!(prefetch_all(&mut queue, 1000), [4, 5]);
+    }
+
+    #[test]
+    fn takes_up_at_most_half_of_the_cache() {
+        let mut queue = PrefetchQueue::new(Some(10_000));
+
+        // the track that crosses the limit is still prefetched, none after it
+        queue.replace((1..=10).map(track).collect());
+        assert_eq!(prefetch_all(&mut queue, 2000), [1, 2, 3]);
+
+        // the next tracks may take up the cache afresh
+        queue.replace((11..=20).map(track).collect());
+        assert_eq!(prefetch_all(&mut queue, 2500), [11, 12]);
+    }
+}
@@ -0,0 +1,348 @@
+use std::{collections::VecDeque, ops::RangeInclusive};
+
+use crate::{
+    decoder::{AudioPacket, AudioPacketPosition},
+    metadata::audio::{AudioItem, UniqueFields},
+    player::db_to_ratio,
+    SAMPLES_PER_SECOND,
+};
+
+pub const VALID_TRIM_SILENCE_THRESHOLD_RANGE: RangeInclusive<f64> = -90.0..=-20.0;
+
+// Silence that may turn out to be trailing is held back up to this long. Any longer and
+// the oldest of it is played after all, so that only the last few seconds of long trailing
+// silence are trimmed. Holding back more would leave the sink without anything to play
+// while the decoder races ahead of the download.
+const MAX_HELD_SAMPLES: usize = 3 * SAMPLES_PER_SECOND as usize;
+
+// Where a track sits on its album, to tell whether two tracks follow each other.
+#[derive(Debug, Clone, PartialEq)]
+struct AlbumPosition {
+    album: String,
+    album_artists: Vec<String>,
+    disc_number: u32,
+    number: u32,
+}
+
+impl AlbumPosition {
+    fn of(audio_item: &AudioItem) -> Option<Self> {
+        match &audio_item.unique_fields {
+            UniqueFields::Track {
+                album,
+                album_artists,
+                disc_number,
+                number,
+                ..
+            } => Some(Self {
+                album: album.clone(),
+                album_artists: album_artists.clone(),
+                disc_number: *disc_number,
+                number: *number,
+            }),
+            UniqueFields::Episode { .. } => None,
+        }
+    }
+
+    fn is_followed_by(&self, next: &Self) -> bool {
+        self.album == next.album
+            && self.album_artists == next.album_artists
+            && ((next.disc_number == self.disc_number && next.number == self.number + 1)
+                || (next.disc_number == self.disc_number + 1 && next.number == 1))
+    }
+}
+
+// Skips the silence at the start and end of tracks, except where one track of an album
+// flows into the next, as silence there is part of the music.
+//
+// Leading silence is dropped as it is decoded. Silence later on is held back until either
+// sound follows, and it is played after all, or the track ends, and it is dropped.
+pub struct SilenceTrimmer {
+    threshold: f64,
+
+    current_track: Option<AlbumPosition>,
+    // the track that last played to its end, cleared when the next one starts
+    ended_track: Option<AlbumPosition>,
+
+    leading: bool,
+    leading_trimmed: bool,
+
+    held: VecDeque<(AudioPacketPosition, AudioPacket)>,
+    held_samples: usize,
+}
+
+impl SilenceTrimmer {
+    pub fn new(threshold_db: f64) -> Self {
+        Self {
+            threshold: db_to_ratio(threshold_db),
+            current_track: None,
+            ended_track: None,
+            leading: false,
+            leading_trimmed: false,
+            held: VecDeque::new(),
+            held_samples: 0,
+        }
+    }
+
+    // Prepares for a track that starts playing at `position_ms`. Leading silence is only
+    // trimmed when it starts from the beginning, and doesn't continue the album of the
+    // track that just ended.
+    pub fn start_track(&mut self, audio_item: &AudioItem, position_ms: u32) {
+        let current_track = AlbumPosition::of(audio_item);
+        let continues_album = match (&self.ended_track, &current_track) {
+            (Some(ended_track), Some(current_track)) => ended_track.is_followed_by(current_track),
+            _ => false,
+        };
+
+        if continues_album {
+            debug!("Not trimming leading silence within an album sequence");
+        }
+
+        self.leading = position_ms == 0 && !continues_album;
+        self.leading_trimmed = false;
+        self.current_track = current_track;
+        self.ended_track = None;
+        self.clear();
+    }
+
+    // Whether `next` is the next track on the album of the current track.
+    pub fn continues_into(&self, next: &AudioItem) -> bool {
+        match (&self.current_track, AlbumPosition::of(next)) {
+            (Some(current_track), Some(next)) => current_track.is_followed_by(&next),
+            _ => false,
+        }
+    }
+
+    // Forgets the silence held back, e.g. on a seek, from where nothing is leading.
+    pub fn seek(&mut self) {
+        self.leading = false;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.held.clear();
+        self.held_samples = 0;
+    }
+
+    fn is_silent(&self, packet: &AudioPacket) -> bool {
+        // Encoded packets of the passthrough decoder can't be inspected.
+        packet
+            .samples()
+            .is_ok_and(|samples| samples.iter().all(|sample| sample.abs() <= self.threshold))
+    }
+
+    // Takes a decoded packet and returns the packets to play now, in order.
+    pub fn push(
+        &mut self,
+        mut packet: (AudioPacketPosition, AudioPacket),
+    ) -> Vec<(AudioPacketPosition, AudioPacket)> {
+        if !self.is_silent(&packet.1) {
+            if self.leading && self.leading_trimmed {
+                debug!("Trimmed {} ms of leading silence", packet.0.position_ms);
+                // so that the position is corrected for the packets that were dropped
+                packet.0.skipped = true;
+            }
+            self.leading = false;
+
+            let mut packets: Vec<_> = self.held.drain(..).collect();
+            self.held_samples = 0;
+            packets.push(packet);
+            return packets;
+        }
+
+        if self.leading {
+            self.leading_trimmed = true;
+            return Vec::new();
+        }
+
+        self.held_samples += packet.1.samples().map_or(0, |samples| samples.len());
+        self.held.push_back(packet);
+
+        let mut packets = Vec::new();
+        while self.held_samples > MAX_HELD_SAMPLES {
+            match self.held.pop_front() {
+                Some(packet) => {
+                    self.held_samples -= packet.1.samples().map_or(0, |samples| samples.len());
+                    packets.push(packet);
+                }
+                None => break,
+            }
+        }
+        packets
+    }
+
+    // Called when the decoder has reached the end of the track, returns the packets still
+    // held back that are to be played, which are none if trailing silence is trimmed.
+    pub fn end_track(&mut self, trim: bool) -> Vec<(AudioPacketPosition, AudioPacket)> {
+        self.ended_track = self.current_track.take();
+        self.leading = false;
+
+        if trim {
+            if self.held_samples > 0 {
+                debug!(
+                    "Trimmed {} ms of trailing silence",
+                    self.held_samples as u64 * 1000 / SAMPLES_PER_SECOND as u64
+                );
+            }
+            self.clear();
+            Vec::new()
+        } else {
+            self.held_samples = 0;
+            self.held.drain(..).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::SpotifyId,
+        metadata::{artist::ArtistsWithRole, audio::AudioFiles},
+    };
+
+    const THRESHOLD_DB: f64 = -60.0;
+
+    fn track(album: &str, disc_number: u32, number: u32) -> AudioItem {
+        AudioItem {
+            track_id: SpotifyId::from_base62("4uLU6hMCjMI75M1A2tKUQC").unwrap(),
+            uri: String::new(),
+            files: AudioFiles::default(),
+            name: format!("{album} {disc_number}-{number}"),
+            covers: Vec::new(),
+            language: Vec::new(),
+            duration_ms: 180_000,
+            is_explicit: false,
+            availability: Ok(()),
+            alternatives: None,
+            unique_fields: UniqueFields::Track {
+                artists: ArtistsWithRole(Vec::new()),
+                album: album.to_string(),
+                album_artists: vec!["Artist".to_string()],
+                popularity: 0,
+                number,
+                disc_number,
+            },
+        }
+    }
+
+    // 10 ms of a constant level
+    fn packet(position_ms: u32, level: f64) -> (AudioPacketPosition, AudioPacket) {
+        (
+            AudioPacketPosition {
+                position_ms,
+                skipped: false,
+            },
+            AudioPacket::Samples(vec![level; SAMPLES_PER_SECOND as usize / 100]),
+        )
+    }
+
+    fn positions(packets: &[(AudioPacketPosition, AudioPacket)]) -> Vec<u32> {
+        packets
+            .iter()
+            .map(|(position, _)| position.position_ms)
+            .collect()
+    }
+
+    #[test]
+    fn trims_leading_silence_below_the_threshold() {
+        let mut trimmer = SilenceTrimmer::new(THRESHOLD_DB);
+        trimmer.start_track(&track("Album", 1, 1), 0);
+
+        // -66 dB and just below -60 dB
+        assert!(trimmer.push(packet(0, 0.0005)).is_empty());
+        assert!(trimmer.push(packet(10, 0.000_999)).is_empty());
+
+        // -54 dB is sound, and the position skips the dropped packets
+        let packets = trimmer.push(packet(20, 0.002));
+        assert_eq!(positions(&packets), [20]);
+        assert!(packets[0].0.skipped);
+
+        // silence after the sound has started is held back, not dropped
+        assert!(trimmer.push(packet(30, 0.0)).is_empty());
+        let packets = trimmer.push(packet(40, 0.5));
+        assert_eq!(positions(&packets), [30, 40]);
+        assert!(packets.iter().all(|(position, _)| !position.skipped));
+    }
+
+    #[test]
+    fn keeps_leading_silence_when_not_starting_from_the_beginning() {
+        let mut trimmer = SilenceTrimmer::new(THRESHOLD_DB);
+        trimmer.start_track(&track("Album", 1, 1), 60_000);
+
+        assert!(trimmer.push(packet(60_000, 0.0)).is_empty());
+        let packets = trimmer.push(packet(60_010, 0.5));
+        assert_eq!(positions(&packets), [60_000, 60_010]);
+        assert!(!packets[1].0.skipped);
+    }
+
+    #[test]
+    fn trims_trailing_silence_below_the_threshold() {
+        let mut trimmer = SilenceTrimmer::new(THRESHOLD_DB);
+        trimmer.start_track(&track("Album", 1, 1), 0);
+
+        assert_eq!(positions(&trimmer.push(packet(0, 0.5))), [0]);
+        assert!(trimmer.push(packet(10, 0.0005)).is_empty());
+        assert!(trimmer.push(packet(20, 0.0)).is_empty());
+
+        assert!(trimmer.end_track(true).is_empty());
+    }
+
+    #[test]
+    fn plays_held_silence_after_all_after_very_long_silence() {
+        let mut trimmer = SilenceTrimmer::new(THRESHOLD_DB);
+        trimmer.start_track(&track("Album", 1, 1), 0);
+        trimmer.push(packet(0, 0.5));
+
+        // 3.5 s of silence, the first half second of which can't be held back any longer
+        let mut played = Vec::new();
+        for i in 0..350 {
+            played.extend(positions(&trimmer.push(packet(10 + i * 10, 0.0))));
+        }
+        assert_eq!(played, (0..50).map(|i| 10 + i * 10).collect::<Vec<_>>());
+
+        assert!(trimmer.end_track(true).is_empty());
+    }
+
+    #[test]
+    fn trims_nothing_in_between_tracks_of_an_album_sequence() {
+        let mut trimmer = SilenceTrimmer::new(THRESHOLD_DB);
+        let first = track("Album", 1, 1);
+        let second = track("Album", 1, 2);
+
+        trimmer.start_track(&first, 0);
+        trimmer.push(packet(0, 0.5));
+        assert!(trimmer.push(packet(10, 0.0)).is_empty());
+        assert!(trimmer.push(packet(20, 0.0)).is_empty());
+
+        // the player keeps the trailing silence, as the next track continues the album
+        assert!(trimmer.continues_into(&second));
+        assert_eq!(positions(&trimmer.end_track(false)), [10, 20]);
+
+        // and the next track keeps its leading silence
+        trimmer.start_track(&second, 0);
+        assert!(trimmer.push(packet(0, 0.0)).is_empty());
+        let packets = trimmer.push(packet(10, 0.5));
+        assert_eq!(positions(&packets), [0, 10]);
+        assert!(!packets[1].0.skipped);
+    }
+
+    #[test]
+    fn tells_album_sequences_apart() {
+        let mut trimmer = SilenceTrimmer::new(THRESHOLD_DB);
+        trimmer.start_track(&track("Album", 1, 9), 0);
+
+        assert!(trimmer.continues_into(&track("Album", 1, 10)));
+        assert!(trimmer.continues_into(&track("Album", 2, 1)));
+        assert!(!trimmer.continues_into(&track("Album", 1, 9)));
+        assert!(!trimmer.continues_into(&track("Album", 1, 11)));
+        assert!(!trimmer.continues_into(&track("Album", 2, 2)));
+        assert!(!trimmer.continues_into(&track("Other album", 1, 10)));
+
+        // a track that doesn't continue the album after the last one ended has its
+        // leading silence trimmed
+        trimmer.end_track(true);
+        trimmer.start_track(&track("Other album", 1, 1), 0);
+        assert!(trimmer.push(packet(0, 0.0)).is_empty());
+        assert_eq!(positions(&trimmer.push(packet(10, 0.5))), [10]);
+    }
+}
@@ -0,0 +1,278 @@
+use std::{f64::consts::PI, ops::RangeInclusive};
+
+use crate::{NUM_CHANNELS, SAMPLE_RATE};
+
+pub const VALID_PLAYBACK_SPEED_RANGE: RangeInclusive<f64> = 0.5..=3.0;
+
+// Frame length of ~23 ms at 44.1 kHz, overlapping by half. Short enough not to smear
+// transients of speech, long enough to hold a couple of periods of a low voice.
+const FRAME_LEN: usize = 1024;
+const SYNTHESIS_HOP: usize = FRAME_LEN / 2;
+// How far (in frames) the analysis frame may be moved to find the best match, ~6 ms.
+const SEEK_TOLERANCE: usize = SAMPLE_RATE as usize / 170;
+// The similarity search is done on every other frame to spare the CPU.
+const SEEK_STEP: usize = 2;
+
+const CHANNELS: usize = NUM_CHANNELS as usize;
+
+// Pitch preserving time stretching by waveform similarity overlap-add (WSOLA).
+//
+// Frames are read from the input at `speed` times the rate at which they are written
+// to the output, each shifted within a small tolerance to where it is most similar
+// to the natural continuation of the previous frame, so that the overlap-add
+// doesn't cause phase cancellation.
+//
+// After: Verhelst, W., & Roelands, M. (1993). An overlap-add technique based on
+// waveform similarity (WSOLA) for high quality time-scale modification of speech.
+// IEEE International Conference on Acoustics, Speech, and Signal Processing, 2, 554-557.
+pub struct TimeStretcher {
+    speed: f64,
+    window: Vec<f64>,
+    // interleaved input that has not been consumed yet
+    input: Vec<f64>,
+    // nominal position of the next analysis frame, in frames into `input`
+    analysis_position: f64,
+    // position right after the first hop of the previous analysis frame, in frames
+    // into `input`, where the previous frame would naturally continue
+    continuation: Option<usize>,
+    // interleaved overlap-add accumulator of `FRAME_LEN` frames
+    output: Vec<f64>,
+}
+
+impl Default for TimeStretcher {
+    fn default() -> Self {
+        // periodic Hann window, sums to unity at an overlap of one half
+        let window = (0..FRAME_LEN)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / FRAME_LEN as f64).cos())
+            .collect();
+
+        Self {
+            speed: 1.0,
+            window,
+            input: Vec::new(),
+            analysis_position: 0.0,
+            continuation: None,
+            output: vec![0.0; FRAME_LEN * CHANNELS],
+        }
+    }
+}
+
+impl TimeStretcher {
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(
+            *VALID_PLAYBACK_SPEED_RANGE.start(),
+            *VALID_PLAYBACK_SPEED_RANGE.end(),
+        );
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.speed == 1.0
+    }
+
+    // Drops everything buffered, e.g. on a seek or when another track starts.
+    pub fn reset(&mut self) {
+        self.input.clear();
+        self.analysis_position = 0.0;
+        self.continuation = None;
+        self.output.fill(0.0);
+    }
+
+    fn frames(&self) -> usize {
+        self.input.len() / CHANNELS
+    }
+
+    // Sum of the channels of the input frame at `position`.
+    fn mono(&self, position: usize) -> f64 {
+        self.input[position * CHANNELS..(position + 1) * CHANNELS]
+            .iter()
+            .sum()
+    }
+
+    // Finds the frame position around `nominal` that is most similar to the
+    // natural continuation of the previous frame, by normalised cross-correlation
+    // over the part that is going to overlap.
+    fn best_position(&self, nominal: usize, continuation: usize) -> usize {
+        let start = nominal.saturating_sub(SEEK_TOLERANCE);
+        let end = nominal + SEEK_TOLERANCE;
+
+        let mut best_position = nominal;
+        let mut best_similarity = f64::MIN;
+
+        for position in (start..=end).step_by(SEEK_STEP) {
+            let mut correlation = 0.0;
+            let mut energy = 0.0;
+
+            for i in (0..SYNTHESIS_HOP).step_by(SEEK_STEP) {
+                let candidate = self.mono(position + i);
+                correlation += candidate * self.mono(continuation + i);
+                energy += candidate * candidate;
+            }
+
+            let similarity = if energy > 0.0 {
+                correlation / energy.sqrt()
+            } else {
+                0.0
+            };
+
+            if similarity > best_similarity {
+                best_similarity = similarity;
+                best_position = position;
+            }
+        }
+
+        best_position
+    }
+
+    // Feeds interleaved samples and returns the stretched samples that are ready,
+    // which are roughly `1 / speed` times as many, delayed by up to a frame. At
+    // normal speed they are passed through as they are.
+    pub fn process(&mut self, samples: &[f64]) -> Vec<f64> {
+        if self.is_bypassed() {
+            return samples.to_vec();
+        }
+
+        self.input.extend_from_slice(samples);
+
+        let mut stretched = Vec::with_capacity(
+            (samples.len() as f64 / self.speed) as usize + SYNTHESIS_HOP * CHANNELS,
+        );
+
+        loop {
+            let nominal = self.analysis_position.round() as usize;
+            let continuation = self.continuation;
+
+            // both the search area and the continuation must be fully buffered
+            let required = match continuation {
+                Some(continuation) => (nominal + SEEK_TOLERANCE).max(continuation) + FRAME_LEN,
+                None => nominal + FRAME_LEN,
+            };
+            if required > self.frames() {
+                break;
+            }
+
+            let position = match continuation {
+                Some(continuation) => self.best_position(nominal, continuation),
+                None => nominal,
+            };
+
+            for (i, weight) in self.window.iter().enumerate() {
+                for channel in 0..CHANNELS {
+                    self.output[i * CHANNELS + channel] +=
+                        self.input[(position + i) * CHANNELS + channel] * weight;
+                }
+            }
+
+            // the first hop has received all its overlapping frames
+            stretched.extend(self.output.drain(..SYNTHESIS_HOP * CHANNELS));
+            self.output.resize(FRAME_LEN * CHANNELS, 0.0);
+
+            let continuation = position + SYNTHESIS_HOP;
+            self.analysis_position += SYNTHESIS_HOP as f64 * self.speed;
+
+            // forget input that neither the next search nor the next continuation needs
+            let consumed = (self.analysis_position.floor() as usize)
+                .saturating_sub(SEEK_TOLERANCE)
+                .min(continuation);
+            self.input.drain(..consumed * CHANNELS);
+            self.analysis_position -= consumed as f64;
+            self.continuation = Some(continuation - consumed);
+        }
+
+        stretched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TONE_HZ: f64 = 441.0;
+
+    // Interleaved stereo of a sine, with a little less on the right channel.
+    fn tone(frames: usize) -> Vec<f64> {
+        (0..frames)
+            .flat_map(|i| {
+                let sample = (2.0 * PI * TONE_HZ * i as f64 / SAMPLE_RATE as f64).sin() * 0.5;
+                [sample, sample * 0.8]
+            })
+            .collect()
+    }
+
+    // Feeds the samples in packets of the size the decoder produces.
+    fn stretch(speed: f64, samples: &[f64]) -> Vec<f64> {
+        let mut stretcher = TimeStretcher::default();
+        stretcher.set_speed(speed);
+
+        samples
+            .chunks(4096)
+            .flat_map(|packet| stretcher.process(packet))
+            .collect()
+    }
+
+    fn zero_crossings(samples: &[f64]) -> usize {
+        samples
+            .iter()
+            .step_by(CHANNELS)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .filter(|pair| (*pair[0] < 0.0) != (*pair[1] < 0.0))
+            .count()
+    }
+
+    #[test]
+    fn passes_through_at_normal_speed() {
+        let samples = tone(SAMPLE_RATE as usize);
+        assert_eq!(stretch(1.0, &samples), samples);
+    }
+
+    #[test]
+    fn output_length_scales_with_speed() {
+        let frames = 2 * SAMPLE_RATE as usize;
+        let samples = tone(frames);
+
+        for speed in [0.5, 0.75, 1.5, 2.0, 3.0] {
+            let stretched_frames = stretch(speed, &samples).len() / CHANNELS;
+            let expected_frames = frames as f64 / speed;
+
+            // the input of the last frame and its search area is held back for more input
+            let tolerance = (2 * FRAME_LEN + SEEK_TOLERANCE) as f64 / speed;
+            assert!(
+                (stretched_frames as f64 - expected_frames).abs() <= tolerance,
+                "{stretched_frames} frames at {speed}x, expected {expected_frames}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_pitch() {
+        let samples = tone(2 * SAMPLE_RATE as usize);
+        let crossings_per_frame =
+            zero_crossings(&samples) as f64 / (samples.len() / CHANNELS) as f64;
+
+        for speed in [0.5, 2.0] {
+            let stretched = stretch(speed, &samples);
+            // skip the fade in of the first frame
+            let stretched = &stretched[FRAME_LEN * CHANNELS..];
+            let stretched_crossings_per_frame =
+                zero_crossings(stretched) as f64 / (stretched.len() / CHANNELS) as f64;
+
+            assert!(
+                (stretched_crossings_per_frame / crossings_per_frame - 1.0).abs() < 0.05,
+                "pitch changed at {speed}x"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_channels_apart() {
+        let stretched = stretch(1.5, &tone(SAMPLE_RATE as usize));
+
+        for frame in stretched.chunks(CHANNELS) {
+            assert!((frame[1] - frame[0] * 0.8).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use log::debug;
+
+use tokio::sync::{mpsc, oneshot};
+
+use spotipi::{
+    connect::{LoadRequest, Spirc},
+    core::Error,
+    playback::player::SleepTimer,
+};
+
+// The commands that control surfaces send to `Spirc`, which is replaced whenever the
+// daemon reconnects, and therefore only known to the main loop.
+#[derive(Debug)]
+pub enum ControlCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Prev,
+    VolumeUp,
+    VolumeDown,
+    SetVolume(u16),
+    SetPosition(u32),
+    Shuffle(bool),
+    Repeat(bool),
+    RepeatTrack(bool),
+    Load(LoadRequest),
+    Activate,
+    Disconnect { pause: bool },
+    SetSleepTimer(SleepTimer),
+    CancelSleepTimer,
+}
+
+impl ControlCommand {
+    fn run(self, spirc: &Spirc) -> Result<(), Error> {
+        match self {
+            Self::Play => spirc.play(),
+            Self::Pause => spirc.pause(),
+            Self::PlayPause => spirc.play_pause(),
+            Self::Next => spirc.next(),
+            Self::Prev => spirc.prev(),
+            Self::VolumeUp => spirc.volume_up(),
+            Self::VolumeDown => spirc.volume_down(),
+            Self::SetVolume(volume) => spirc.set_volume(volume),
+            Self::SetPosition(position_ms) => spirc.set_position_ms(position_ms),
+            Self::Shuffle(shuffle) => spirc.shuffle(shuffle),
+            Self::Repeat(repeat) => spirc.repeat(repeat),
+            Self::RepeatTrack(repeat) => spirc.repeat_track(repeat),
+            Self::Load(request) => spirc.load(request),
+            Self::Activate => spirc.activate(),
+            Self::Disconnect { pause } => spirc.disconnect(pause),
+            Self::SetSleepTimer(sleep_timer) => spirc.set_sleep_timer(sleep_timer),
+            Self::CancelSleepTimer => spirc.cancel_sleep_timer(),
+        }
+    }
+}
+
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    response_tx: oneshot::Sender<Result<(), Error>>,
+}
+
+impl ControlRequest {
+    pub fn handle(self, spirc: Option<&Spirc>) {
+        let result = match spirc {
+            Some(spirc) => self.command.run(spirc),
+            None => Err(Error::unavailable("not connected")),
+        };
+
+        // the control surface may have given up in the meantime
+        let _ = self.response_tx.send(result);
+    }
+
+    #[cfg(all(test, any(feature = "mpris", feature = "mqtt")))]
+    pub fn respond(self, result: Result<(), Error>) {
+        let _ = self.response_tx.send(result);
+    }
+}
+
+#[derive(Clone)]
+pub struct ControlSender {
+    request_tx: mpsc::UnboundedSender<ControlRequest>,
+}
+
+impl ControlSender {
+    // Sends a command to the main loop and waits until `Spirc` took it.
+    pub async fn send(&self, command: ControlCommand) -> Result<(), Error> {
+        debug!("Control command: {:?}", command);
+
+        let (response_tx, response_rx) = oneshot::channel();
+        self.request_tx
+            .send(ControlRequest {
+                command,
+                response_tx,
+            })
+            .map_err(|_| Error::unavailable("shutting down"))?;
+
+        response_rx
+            .await
+            .unwrap_or_else(|_| Err(Error::unavailable("shutting down")))
+    }
+}
+
+pub fn channel() -> (ControlSender, mpsc::UnboundedReceiver<ControlRequest>) {
+    let (request_tx, request_rx) = mpsc::unbounded_channel();
+    (ControlSender { request_tx }, request_rx)
+}
@@ -0,0 +1,739 @@
+use log::{debug, error, info, warn};
+
+use std::{
+    convert::Infallible,
+    net::{SocketAddr, TcpListener},
+    ops::RangeInclusive,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{body::Incoming, header, HeaderMap, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinSet,
+};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+
+use spotipi::{
+    connect::{LoadContextOptions, LoadRequest, LoadRequestOptions, Options, PlayingTrack},
+    core::{error::ErrorKind, Error},
+    playback::{
+        config::{AudioFormat, Bitrate, NormalisationMethod, NormalisationType},
+        dither,
+        player::{Player, SleepTimer},
+    },
+};
+
+use crate::{
+    control::{ControlCommand, ControlSender},
+    event_stream,
+    status::{from_percent, StatusTracker},
+};
+
+// the same as for `--normalisation-pregain`
+const VALID_NORMALISATION_PREGAIN_RANGE: RangeInclusive<f64> = -10.0..=10.0;
+const VALID_VOLUME_RANGE: RangeInclusive<u16> = 0..=100;
+
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ControlApiConfig {
+    pub address: SocketAddr,
+    // the origins of web pages that may use the API, e.g. "http://localhost:8080",
+    // requests from any other page are refused
+    pub allowed_origins: Vec<String>,
+    // which ditherers can be selected depends on the output format
+    pub format: AudioFormat,
+}
+
+#[derive(Deserialize)]
+struct VolumeBody {
+    // in % from 0 - 100
+    volume: u16,
+}
+
+#[derive(Deserialize)]
+struct PositionBody {
+    position_ms: u32,
+}
+
+#[derive(Deserialize)]
+struct ShuffleBody {
+    shuffle: bool,
+}
+
+#[derive(Deserialize)]
+struct RepeatBody {
+    context: Option<bool>,
+    track: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct LoadBody {
+    context_uri: String,
+    #[serde(default = "default_start_playing")]
+    start_playing: bool,
+    #[serde(default)]
+    position_ms: u32,
+    track_uri: Option<String>,
+    track_index: Option<u32>,
+    #[serde(default)]
+    shuffle: bool,
+    #[serde(default)]
+    repeat: bool,
+    #[serde(default)]
+    repeat_track: bool,
+}
+
+fn default_start_playing() -> bool {
+    true
+}
+
+// Either a duration or what to wait for the end of, "track" or "context".
+#[derive(Deserialize)]
+struct SleepTimerBody {
+    duration_ms: Option<u64>,
+    end_of: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct DisconnectBody {
+    #[serde(default)]
+    pause: bool,
+}
+
+// Settings of the player, all optional so that only what is given changes.
+#[derive(Deserialize)]
+struct SettingsBody {
+    normalisation: Option<bool>,
+    normalisation_type: Option<String>,
+    normalisation_method: Option<String>,
+    normalisation_pregain: Option<f64>,
+    gapless: Option<bool>,
+    ditherer: Option<String>,
+    bitrate: Option<String>,
+}
+
+enum ApiError {
+    BadRequest(String),
+    Forbidden(String),
+    NotFound,
+    MethodNotAllowed,
+    UnsupportedMediaType,
+    Command(Error),
+}
+
+impl ApiError {
+    fn into_response(self) -> Response<Full<Bytes>> {
+        let (status, message) = match self {
+            Self::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Self::Forbidden(message) => (StatusCode::FORBIDDEN, message),
+            Self::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            Self::MethodNotAllowed => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                "method not allowed".to_string(),
+            ),
+            Self::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "expected a body of type application/json".to_string(),
+            ),
+            Self::Command(e) => {
+                let status = match e.kind {
+                    ErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                (status, e.to_string())
+            }
+        };
+
+        json_response(status, json!({ "error": message }).to_string())
+    }
+}
+
+fn json_response(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn no_content() -> Response<Full<Bytes>> {
+    let mut response = Response::default();
+    *response.status_mut() = StatusCode::NO_CONTENT;
+    response
+}
+
+fn invalid<T>(field: &str, value: impl std::fmt::Display) -> Result<T, ApiError> {
+    Err(ApiError::BadRequest(format!(
+        "invalid {field}: \"{value}\""
+    )))
+}
+
+struct RequestHandler {
+    config: ControlApiConfig,
+    player: Arc<Player>,
+    status: StatusTracker,
+    control: ControlSender,
+    closing_rx: watch::Receiver<bool>,
+}
+
+impl RequestHandler {
+    async fn send(&self, command: ControlCommand) -> Result<Response<Full<Bytes>>, ApiError> {
+        self.control
+            .send(command)
+            .await
+            .map(|()| no_content())
+            .map_err(ApiError::Command)
+    }
+
+    fn handle_settings(&self, settings: SettingsBody) -> Result<Response<Full<Bytes>>, ApiError> {
+        // Everything is validated before anything changes.
+        let normalisation_type = match settings.normalisation_type {
+            Some(value) => match NormalisationType::from_str(&value) {
+                Ok(normalisation_type) => Some(normalisation_type),
+                Err(_) => return invalid("normalisation_type", value),
+            },
+            None => None,
+        };
+
+        let normalisation_method = match settings.normalisation_method {
+            Some(value) => match NormalisationMethod::from_str(&value) {
+                Ok(normalisation_method) => Some(normalisation_method),
+                Err(_) => return invalid("normalisation_method", value),
+            },
+            None => None,
+        };
+
+        if let Some(pregain) = settings.normalisation_pregain {
+            if !VALID_NORMALISATION_PREGAIN_RANGE.contains(&pregain) {
+                return invalid("normalisation_pregain", pregain);
+            }
+        }
+
+        let ditherer = match settings.ditherer {
+            Some(value) => match (value.as_str(), self.config.format) {
+                ("none", _) => Some(None),
+                (_, AudioFormat::F64 | AudioFormat::F32) => return invalid("ditherer", value),
+                _ => match dither::find_ditherer(Some(value.clone())) {
+                    Some(ditherer) => Some(Some(ditherer)),
+                    None => return invalid("ditherer", value),
+                },
+            },
+            None => None,
+        };
+
+        let bitrate = match settings.bitrate {
+            Some(value) => match Bitrate::from_str(&value) {
+                Ok(bitrate) => Some(bitrate),
+                Err(_) => return invalid("bitrate", value),
+            },
+            None => None,
+        };
+
+        if let Some(normalisation) = settings.normalisation {
+            self.player.set_normalisation(normalisation);
+        }
+        if let Some(normalisation_type) = normalisation_type {
+            self.player.set_normalisation_type(normalisation_type);
+        }
+        if let Some(normalisation_method) = normalisation_method {
+            self.player.set_normalisation_method(normalisation_method);
+        }
+        if let Some(pregain) = settings.normalisation_pregain {
+            self.player.set_normalisation_pregain(pregain);
+        }
+        if let Some(gapless) = settings.gapless {
+            self.player.set_gapless(gapless);
+        }
+        if let Some(ditherer) = ditherer {
+            self.player.set_ditherer(ditherer);
+        }
+        if let Some(bitrate) = bitrate {
+            self.player.set_bitrate(bitrate);
+        }
+
+        Ok(no_content())
+    }
+
+    // Upgrades to a WebSocket that pushes the status and the player events, see
+    // `event_stream`.
+    fn upgrade_events(
+        &self,
+        mut request: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, ApiError> {
+        if request.method() != Method::GET {
+            return Err(ApiError::MethodNotAllowed);
+        }
+
+        // Browsers don't restrict WebSockets to the origin of the page, so any page could
+        // follow the playback otherwise.
+        let headers = request.headers();
+        check_origin(&self.config.allowed_origins, headers)?;
+
+        let is_websocket = headers
+            .get(header::UPGRADE)
+            .and_then(|upgrade| upgrade.to_str().ok())
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+        let key = match headers.get(header::SEC_WEBSOCKET_KEY) {
+            Some(key) if is_websocket => key,
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "expected a WebSocket upgrade".to_string(),
+                ))
+            }
+        };
+        let accept = derive_accept_key(key.as_bytes());
+
+        let on_upgrade = hyper::upgrade::on(&mut request);
+        let status = self.status.clone();
+        let closing_rx = self.closing_rx.clone();
+        tokio::spawn(async move {
+            match on_upgrade.await {
+                Ok(upgraded) => event_stream::run(upgraded, status, closing_rx).await,
+                Err(e) => debug!("Event stream upgrade failed: {e}"),
+            }
+        });
+
+        let mut response = Response::default();
+        *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        let headers = response.headers_mut();
+        headers.insert(
+            header::UPGRADE,
+            header::HeaderValue::from_static("websocket"),
+        );
+        headers.insert(
+            header::CONNECTION,
+            header::HeaderValue::from_static("Upgrade"),
+        );
+        if let Ok(accept) = header::HeaderValue::from_str(&accept) {
+            headers.insert(header::SEC_WEBSOCKET_ACCEPT, accept);
+        }
+        Ok(response)
+    }
+
+    async fn route(
+        &self,
+        method: Method,
+        path: &str,
+        body: Bytes,
+    ) -> Result<Response<Full<Bytes>>, ApiError> {
+        let command = match path {
+            "/status" => {
+                if method != Method::GET {
+                    return Err(ApiError::MethodNotAllowed);
+                }
+
+                let status = serde_json::to_string(&self.status.snapshot())
+                    .map_err(|e| ApiError::Command(Error::internal(e)))?;
+                return Ok(json_response(StatusCode::OK, status));
+            }
+            "/settings" => {
+                if method != Method::PUT {
+                    return Err(ApiError::MethodNotAllowed);
+                }
+
+                return self.handle_settings(parse_body(&body)?);
+            }
+            "/sleep-timer" if method == Method::GET => {
+                let sleep_timer = serde_json::to_string(&self.status.snapshot().sleep_timer)
+                    .map_err(|e| ApiError::Command(Error::internal(e)))?;
+                return Ok(json_response(StatusCode::OK, sleep_timer));
+            }
+            "/sleep-timer" if method == Method::DELETE => ControlCommand::CancelSleepTimer,
+            _ if method != Method::POST && method != Method::PUT => {
+                return Err(ApiError::MethodNotAllowed);
+            }
+            "/play" => ControlCommand::Play,
+            "/pause" => ControlCommand::Pause,
+            "/play-pause" => ControlCommand::PlayPause,
+            "/next" => ControlCommand::Next,
+            "/prev" => ControlCommand::Prev,
+            "/volume/up" => ControlCommand::VolumeUp,
+            "/volume/down" => ControlCommand::VolumeDown,
+            "/activate" => ControlCommand::Activate,
+            "/volume" => {
+                let VolumeBody { volume } = parse_body(&body)?;
+                if !VALID_VOLUME_RANGE.contains(&volume) {
+                    return invalid("volume", volume);
+                }
+
+                ControlCommand::SetVolume(from_percent(volume))
+            }
+            "/position" => {
+                let PositionBody { position_ms } = parse_body(&body)?;
+                ControlCommand::SetPosition(position_ms)
+            }
+            "/shuffle" => {
+                let ShuffleBody { shuffle } = parse_body(&body)?;
+                ControlCommand::Shuffle(shuffle)
+            }
+            "/repeat" => {
+                let RepeatBody { context, track } = parse_body(&body)?;
+
+                if let Some(context) = context {
+                    self.send(ControlCommand::Repeat(context)).await?;
+                }
+                match track {
+                    Some(track) => ControlCommand::RepeatTrack(track),
+                    None => return Ok(no_content()),
+                }
+            }
+            "/load" => {
+                let load: LoadBody = parse_body(&body)?;
+
+                let playing_track = match (load.track_uri, load.track_index) {
+                    (Some(uri), _) => Some(PlayingTrack::Uri(uri)),
+                    (None, Some(index)) => Some(PlayingTrack::Index(index)),
+                    (None, None) => None,
+                };
+
+                let options = LoadRequestOptions {
+                    start_playing: load.start_playing,
+                    seek_to: load.position_ms,
+                    context_options: Some(LoadContextOptions::Options(Options {
+                        shuffle: load.shuffle,
+                        repeat: load.repeat,
+                        repeat_track: load.repeat_track,
+                    })),
+                    playing_track,
+                };
+
+                ControlCommand::Load(LoadRequest::from_context_uri(load.context_uri, options))
+            }
+            "/sleep-timer" => {
+                let sleep_timer = match parse_body(&body)? {
+                    SleepTimerBody {
+                        duration_ms: Some(duration_ms),
+                        end_of: None,
+                    } if duration_ms > 0 => {
+                        SleepTimer::Duration(Duration::from_millis(duration_ms))
+                    }
+                    SleepTimerBody {
+                        duration_ms: None,
+                        end_of: Some(end_of),
+                    } => match end_of.as_str() {
+                        "track" => SleepTimer::EndOfTrack,
+                        "context" => SleepTimer::EndOfContext,
+                        _ => return invalid("end_of", end_of),
+                    },
+                    _ => {
+                        return Err(ApiError::BadRequest(
+                            "expected either duration_ms larger than 0 or end_of".to_string(),
+                        ))
+                    }
+                };
+
+                ControlCommand::SetSleepTimer(sleep_timer)
+            }
+            "/disconnect" => {
+                let DisconnectBody { pause } = if body.is_empty() {
+                    DisconnectBody::default()
+                } else {
+                    parse_body(&body)?
+                };
+                ControlCommand::Disconnect { pause }
+            }
+            _ => return Err(ApiError::NotFound),
+        };
+
+        self.send(command).await
+    }
+
+    async fn handle(self: Arc<Self>, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        if request.uri().path() == "/events" {
+            return self
+                .upgrade_events(request)
+                .unwrap_or_else(ApiError::into_response);
+        }
+
+        let (parts, body) = request.into_parts();
+
+        let origin = match check_origin(&self.config.allowed_origins, &parts.headers) {
+            Ok(origin) => origin,
+            Err(e) => return e.into_response(),
+        };
+
+        let mut response = if parts.method == Method::OPTIONS && origin.is_some() {
+            preflight()
+        } else {
+            match Limited::new(body, MAX_BODY_SIZE).collect().await {
+                Ok(body) => {
+                    let body = body.to_bytes();
+                    match check_content_type(&parts.headers, &body) {
+                        Ok(()) => self.route(parts.method, parts.uri.path(), body).await,
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(e) => Err(ApiError::BadRequest(format!("could not read body: {e}"))),
+            }
+            .unwrap_or_else(ApiError::into_response)
+        };
+
+        if let Some(origin) = origin {
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.insert(header::VARY, header::HeaderValue::from_static("Origin"));
+        }
+        response
+    }
+}
+
+// Browsers let any web page send simple requests to the API, so those of pages that are not
+// allowed are refused. Other clients don't send an origin. Returns the origin that is allowed.
+fn check_origin(
+    allowed_origins: &[String],
+    headers: &HeaderMap,
+) -> Result<Option<header::HeaderValue>, ApiError> {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return Ok(None);
+    };
+
+    let is_allowed = allowed_origins.iter().any(|allowed_origin| {
+        allowed_origin
+            .trim_end_matches('/')
+            .as_bytes()
+            .eq_ignore_ascii_case(origin.as_bytes())
+    });
+
+    if is_allowed {
+        Ok(Some(origin.clone()))
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "origin {} is not allowed",
+            String::from_utf8_lossy(origin.as_bytes())
+        )))
+    }
+}
+
+// Only JSON bodies are accepted, which browsers don't send to other origins without asking
+// first.
+fn check_content_type(headers: &HeaderMap, body: &[u8]) -> Result<(), ApiError> {
+    if body.is_empty() {
+        return Ok(());
+    }
+
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|mime_type| mime_type.trim().eq_ignore_ascii_case("application/json"));
+
+    if is_json {
+        Ok(())
+    } else {
+        Err(ApiError::UnsupportedMediaType)
+    }
+}
+
+// Answers browsers that ask whether an allowed page may send a request.
+fn preflight() -> Response<Full<Bytes>> {
+    let mut response = no_content();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        header::HeaderValue::from_static("GET, POST, PUT, DELETE"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_HEADERS,
+        header::HeaderValue::from_static("Content-Type"),
+    );
+    response
+}
+
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| ApiError::BadRequest(format!("invalid body: {e}")))
+}
+
+// A small HTTP server that takes commands as JSON and reports the status, so that the
+// daemon can be controlled by more than the Spotify apps.
+//
+// GET  /status                                  the current `Status`
+// GET  /events                                  a WebSocket, see `event_stream`
+// POST /play, /pause, /play-pause, /next, /prev, /volume/up, /volume/down, /activate
+// PUT  /volume {"volume"}                       in % from 0 - 100
+// PUT  /position {"position_ms"}
+// PUT  /shuffle {"shuffle"}
+// PUT  /repeat {"context", "track"}             either is optional
+// POST /load {"context_uri", ...}               see `LoadBody`
+// POST /disconnect {"pause"}                    the body is optional
+// PUT  /settings {"normalisation", ...}         see `SettingsBody`, all are optional
+// GET  /sleep-timer                             the `sleep_timer` of the status
+// PUT  /sleep-timer {"duration_ms"}             or {"end_of"}, "track" or "context"
+// DELETE /sleep-timer
+//
+// Commands answer 204 when done, errors are reported as {"error"}, with 503 while the
+// daemon is not connected.
+//
+// There is no authentication, anyone who can reach the address can control the playback.
+// Bodies must be JSON, and web pages may only use the API if their origin is allowed.
+pub struct ControlApi {
+    close_tx: oneshot::Sender<()>,
+    closing_tx: watch::Sender<bool>,
+    task_handle: tokio::task::JoinHandle<()>,
+}
+
+impl ControlApi {
+    pub fn new(
+        config: ControlApiConfig,
+        player: Arc<Player>,
+        status: StatusTracker,
+        control: ControlSender,
+    ) -> Result<Self, Error> {
+        let listener = TcpListener::bind(config.address)?;
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+
+        info!("Control API listening on {}", listener.local_addr()?);
+
+        let (close_tx, close_rx) = oneshot::channel();
+        // Connections can be upgraded to event streams, which hyper does not shut down
+        // gracefully, so every connection is told about it instead.
+        let (closing_tx, closing_rx) = watch::channel(false);
+
+        let handler = Arc::new(RequestHandler {
+            config,
+            player,
+            status,
+            control,
+            closing_rx: closing_rx.clone(),
+        });
+
+        let task_handle = tokio::spawn(async move {
+            let server = hyper::server::conn::http1::Builder::new();
+            let mut connections = JoinSet::new();
+            let mut close_rx = std::pin::pin!(close_rx);
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => {
+                        let stream = match accepted {
+                            Ok((stream, _)) => stream,
+                            Err(e) => {
+                                warn!("Control API could not accept a connection: {e}");
+                                continue;
+                            }
+                        };
+
+                        let io = TokioIo::new(stream);
+                        let handler = handler.clone();
+
+                        let svc = hyper::service::service_fn(move |request| {
+                            let handler = handler.clone();
+                            async move { Ok::<_, Infallible>(handler.handle(request).await) }
+                        });
+
+                        let conn = server.serve_connection(io, svc).with_upgrades();
+                        let mut closing_rx = closing_rx.clone();
+                        connections.spawn(async move {
+                            let mut conn = std::pin::pin!(conn);
+                            let result = tokio::select! {
+                                result = conn.as_mut() => result,
+                                _ = async { closing_rx.wait_for(|closing| *closing).await.is_ok() } => {
+                                    conn.as_mut().graceful_shutdown();
+                                    conn.await
+                                }
+                            };
+
+                            if let Err(e) = result {
+                                debug!("Control API connection failed: {e}");
+                            }
+                        });
+                    }
+                    Some(_) = connections.join_next(), if !connections.is_empty() => (),
+                    _ = &mut close_rx => {
+                        break;
+                    }
+                }
+            }
+
+            while connections.join_next().await.is_some() {}
+        });
+
+        Ok(Self {
+            close_tx,
+            closing_tx,
+            task_handle,
+        })
+    }
+
+    pub async fn shutdown(self) {
+        debug!("Shutting down control API");
+        let _ = self.closing_tx.send(true);
+        if self.close_tx.send(()).is_err() {
+            error!("Control API unexpectedly disappeared");
+        } else {
+            let _ = self.task_handle.await;
+            debug!("Control API stopped");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(headers: &[(header::HeaderName, &'static str)]) -> HeaderMap {
+        headers
+            .iter()
+            .map(|(name, value)| (name.clone(), header::HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn refuses_pages_of_other_origins() {
+        let allowed_origins = vec!["http://localhost:8080/".to_string()];
+
+        assert!(matches!(
+            check_origin(&allowed_origins, &headers(&[])),
+            Ok(None)
+        ));
+        assert!(matches!(
+            check_origin(
+                &allowed_origins,
+                &headers(&[(header::ORIGIN, "http://LOCALHOST:8080")])
+            ),
+            Ok(Some(origin)) if origin == "http://LOCALHOST:8080"
+        ));
+
+        for origin in ["http://evil.example", "http://localhost:8081", "null"] {
+            assert!(matches!(
+                check_origin(&allowed_origins, &headers(&[(header::ORIGIN, origin)])),
+                Err(ApiError::Forbidden(_))
+            ));
+            assert!(matches!(
+                check_origin(&[], &headers(&[(header::ORIGIN, origin)])),
+                Err(ApiError::Forbidden(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn accepts_only_json_bodies() {
+        let json = headers(&[(header::CONTENT_TYPE, "application/json")]);
+        let json_utf8 = headers(&[(header::CONTENT_TYPE, "Application/JSON; charset=utf-8")]);
+        let text = headers(&[(header::CONTENT_TYPE, "text/plain")]);
+        let form = headers(&[(header::CONTENT_TYPE, "application/x-www-form-urlencoded")]);
+        let body = br#"{"pause": true}"#;
+
+        assert!(check_content_type(&json, body).is_ok());
+        assert!(check_content_type(&json_utf8, body).is_ok());
+
+        for headers in [text, form, HeaderMap::new()] {
+            assert!(matches!(
+                check_content_type(&headers, body),
+                Err(ApiError::UnsupportedMediaType)
+            ));
+            // commands without a body
+            assert!(check_content_type(&headers, b"").is_ok());
+        }
+    }
+}
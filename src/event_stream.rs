@@ -0,0 +1,469 @@
+use log::debug;
+
+use futures_util::{SinkExt, StreamExt};
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tokio::sync::{broadcast::error::RecvError, watch};
+use tokio_tungstenite::{
+    tungstenite::{protocol::Role, Message},
+    WebSocketStream,
+};
+
+use spotipi::{
+    core::SpotifyId,
+    metadata::audio::{AudioItem, UniqueFields},
+    playback::player::PlayerEvent,
+};
+
+use crate::status::{to_percent, Status, StatusTracker};
+
+// Raised whenever a message changes in a way that clients have to know about. Fields may
+// be added without raising it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct CoverMessage {
+    pub url: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistMessage {
+    pub uri: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "item_type", rename_all = "lowercase")]
+pub enum ItemFieldsMessage {
+    Track {
+        artists: Vec<ArtistMessage>,
+        album: String,
+        album_artists: Vec<String>,
+        popularity: u8,
+        number: u32,
+        disc_number: u32,
+    },
+    Episode {
+        description: String,
+        // in seconds since the Unix epoch
+        publish_time: i64,
+        show_name: String,
+    },
+}
+
+// Everything about the item that is playing, the same as for `--onevent`.
+#[derive(Debug, Serialize)]
+pub struct AudioItemMessage {
+    pub uri: String,
+    pub name: String,
+    pub covers: Vec<CoverMessage>,
+    pub language: Vec<String>,
+    pub duration_ms: u32,
+    pub is_explicit: bool,
+    #[serde(flatten)]
+    pub fields: ItemFieldsMessage,
+}
+
+impl From<&AudioItem> for AudioItemMessage {
+    fn from(audio_item: &AudioItem) -> Self {
+        let fields = match &audio_item.unique_fields {
+            UniqueFields::Track {
+                artists,
+                album,
+                album_artists,
+                popularity,
+                number,
+                disc_number,
+            } => ItemFieldsMessage::Track {
+                artists: artists
+                    .0
+                    .iter()
+                    .map(|artist| ArtistMessage {
+                        uri: artist.id.to_uri().ok(),
+                        name: artist.name.clone(),
+                    })
+                    .collect(),
+                album: album.clone(),
+                album_artists: album_artists.clone(),
+                popularity: *popularity,
+                number: *number,
+                disc_number: *disc_number,
+            },
+            UniqueFields::Episode {
+                description,
+                publish_time,
+                show_name,
+            } => ItemFieldsMessage::Episode {
+                description: description.clone(),
+                publish_time: publish_time.unix_timestamp(),
+                show_name: show_name.clone(),
+            },
+        };
+
+        Self {
+            uri: audio_item.uri.clone(),
+            name: audio_item.name.clone(),
+            covers: audio_item
+                .covers
+                .iter()
+                .map(|cover| CoverMessage {
+                    url: cover.url.clone(),
+                    width: cover.width,
+                    height: cover.height,
+                })
+                .collect(),
+            language: audio_item.language.clone(),
+            duration_ms: audio_item.duration_ms,
+            is_explicit: audio_item.is_explicit,
+            fields,
+        }
+    }
+}
+
+// One message per player event, named like the events of `--onevent`. Track ids are
+// given as uris, and are null when they cannot be represented as such.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventMessage {
+    // sent first on every connection, and again whenever the client fell behind
+    Snapshot {
+        status: Status,
+    },
+    PlayRequestIdChanged {
+        play_request_id: u64,
+    },
+    Stopped {
+        track_uri: Option<String>,
+    },
+    Loading {
+        track_uri: Option<String>,
+        position_ms: u32,
+    },
+    Preloading {
+        track_uri: Option<String>,
+    },
+    Playing {
+        track_uri: Option<String>,
+        position_ms: u32,
+    },
+    Paused {
+        track_uri: Option<String>,
+        position_ms: u32,
+    },
+    PreloadNext {
+        track_uri: Option<String>,
+    },
+    EndOfTrack {
+        track_uri: Option<String>,
+    },
+    Unavailable {
+        track_uri: Option<String>,
+    },
+    VolumeChanged {
+        // in % from 0 - 100
+        volume: u16,
+    },
+    PositionCorrection {
+        track_uri: Option<String>,
+        position_ms: u32,
+    },
+    Seeked {
+        track_uri: Option<String>,
+        position_ms: u32,
+    },
+    PlaybackSpeedChanged {
+        track_uri: Option<String>,
+        position_ms: u32,
+        speed: f64,
+    },
+    TrackChanged {
+        track: AudioItemMessage,
+    },
+    SessionConnected {
+        connection_id: String,
+        user_name: String,
+    },
+    SessionDisconnected {
+        connection_id: String,
+        user_name: String,
+    },
+    SessionClientChanged {
+        client_id: String,
+        client_name: String,
+        client_brand_name: String,
+        client_model_name: String,
+    },
+    ShuffleChanged {
+        shuffle: bool,
+    },
+    RepeatChanged {
+        context: bool,
+        track: bool,
+    },
+    AutoPlayChanged {
+        auto_play: bool,
+    },
+    FilterExplicitContentChanged {
+        filter: bool,
+    },
+    SleepTimerChanged {
+        sleep_timer: Option<String>,
+    },
+    ActiveDeviceChanged {
+        device_id: Option<String>,
+        device_name: Option<String>,
+    },
+    UserRejected {
+        user_name: String,
+    },
+    Levels {
+        track_uri: Option<String>,
+        // one per channel, in dBFS
+        rms_db: Vec<f64>,
+        peak_db: Vec<f64>,
+        momentary_lufs: f64,
+        short_term_lufs: f64,
+    },
+}
+
+fn uri(track_id: &SpotifyId) -> Option<String> {
+    track_id.to_uri().ok()
+}
+
+impl From<&PlayerEvent> for EventMessage {
+    fn from(event: &PlayerEvent) -> Self {
+        match event {
+            PlayerEvent::PlayRequestIdChanged { play_request_id } => Self::PlayRequestIdChanged {
+                play_request_id: *play_request_id,
+            },
+            PlayerEvent::Stopped { track_id, .. } => Self::Stopped {
+                track_uri: uri(track_id),
+            },
+            PlayerEvent::Loading {
+                track_id,
+                position_ms,
+                ..
+            } => Self::Loading {
+                track_uri: uri(track_id),
+                position_ms: *position_ms,
+            },
+            PlayerEvent::Preloading { track_id } => Self::Preloading {
+                track_uri: uri(track_id),
+            },
+            PlayerEvent::Playing {
+                track_id,
+                position_ms,
+                ..
+            } => Self::Playing {
+                track_uri: uri(track_id),
+                position_ms: *position_ms,
+            },
+            PlayerEvent::Paused {
+                track_id,
+                position_ms,
+                ..
+            } => Self::Paused {
+                track_uri: uri(track_id),
+                position_ms: *position_ms,
+            },
+            PlayerEvent::TimeToPreloadNextTrack { track_id, .. } => Self::PreloadNext {
+                track_uri: uri(track_id),
+            },
+            PlayerEvent::EndOfTrack { track_id, .. } => Self::EndOfTrack {
+                track_uri: uri(track_id),
+            },
+            PlayerEvent::Unavailable { track_id, .. } => Self::Unavailable {
+                track_uri: uri(track_id),
+            },
+            PlayerEvent::VolumeChanged { volume } => Self::VolumeChanged {
+                volume: to_percent(*volume),
+            },
+            PlayerEvent::PositionCorrection {
+                track_id,
+                position_ms,
+                ..
+            } => Self::PositionCorrection {
+                track_uri: uri(track_id),
+                position_ms: *position_ms,
+            },
+            PlayerEvent::Seeked {
+                track_id,
+                position_ms,
+                ..
+            } => Self::Seeked {
+                track_uri: uri(track_id),
+                position_ms: *position_ms,
+            },
+            PlayerEvent::PlaybackSpeedChanged {
+                track_id,
+                position_ms,
+                speed,
+                ..
+            } => Self::PlaybackSpeedChanged {
+                track_uri: uri(track_id),
+                position_ms: *position_ms,
+                speed: *speed,
+            },
+            PlayerEvent::TrackChanged { audio_item } => Self::TrackChanged {
+                track: AudioItemMessage::from(audio_item.as_ref()),
+            },
+            PlayerEvent::SessionConnected {
+                connection_id,
+                user_name,
+            } => Self::SessionConnected {
+                connection_id: connection_id.clone(),
+                user_name: user_name.clone(),
+            },
+            PlayerEvent::SessionDisconnected {
+                connection_id,
+                user_name,
+            } => Self::SessionDisconnected {
+                connection_id: connection_id.clone(),
+                user_name: user_name.clone(),
+            },
+            PlayerEvent::SessionClientChanged {
+                client_id,
+                client_name,
+                client_brand_name,
+                client_model_name,
+            } => Self::SessionClientChanged {
+                client_id: client_id.clone(),
+                client_name: client_name.clone(),
+                client_brand_name: client_brand_name.clone(),
+                client_model_name: client_model_name.clone(),
+            },
+            PlayerEvent::ShuffleChanged { shuffle } => Self::ShuffleChanged { shuffle: *shuffle },
+            PlayerEvent::RepeatChanged { context, track } => Self::RepeatChanged {
+                context: *context,
+                track: *track,
+            },
+            PlayerEvent::AutoPlayChanged { auto_play } => Self::AutoPlayChanged {
+                auto_play: *auto_play,
+            },
+            PlayerEvent::FilterExplicitContentChanged { filter } => {
+                Self::FilterExplicitContentChanged { filter: *filter }
+            }
+            PlayerEvent::SleepTimerChanged { sleep_timer } => Self::SleepTimerChanged {
+                sleep_timer: sleep_timer.as_ref().map(ToString::to_string),
+            },
+            PlayerEvent::ActiveDeviceChanged {
+                device_id,
+                device_name,
+            } => Self::ActiveDeviceChanged {
+                device_id: device_id.clone(),
+                device_name: device_name.clone(),
+            },
+            PlayerEvent::UserRejected { user_name } => Self::UserRejected {
+                user_name: user_name.clone(),
+            },
+            PlayerEvent::Levels {
+                track_id, levels, ..
+            } => Self::Levels {
+                track_uri: uri(track_id),
+                rms_db: levels.channels.iter().map(|c| c.rms_db).collect(),
+                peak_db: levels.channels.iter().map(|c| c.peak_db).collect(),
+                momentary_lufs: levels.momentary_lufs,
+                short_term_lufs: levels.short_term_lufs,
+            },
+        }
+    }
+}
+
+// What is sent over the WebSocket, as text, see docs/event-stream.md:
+// {"version": 1, "event": "<name>", ...the fields of the event}
+#[derive(Debug, Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    #[serde(flatten)]
+    message: &'a EventMessage,
+}
+
+pub fn to_json(message: &EventMessage) -> String {
+    serde_json::to_string(&Envelope {
+        version: SCHEMA_VERSION,
+        message,
+    })
+    .unwrap_or_default()
+}
+
+// Pushes the status and then every player event to a WebSocket client, until the client
+// goes away or `close_rx` tells that the daemon is shutting down.
+pub async fn run(upgraded: Upgraded, status: StatusTracker, mut close_rx: watch::Receiver<bool>) {
+    let mut ws = WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+
+    // subscribe first, so that nothing falls between the snapshot and the updates
+    let mut updates = status.subscribe();
+    let mut next = Some(EventMessage::Snapshot {
+        status: status.snapshot(),
+    });
+
+    loop {
+        if let Some(message) = next.take() {
+            if let Err(e) = ws.send(Message::text(to_json(&message))).await {
+                debug!("Event stream client went away: {e}");
+                return;
+            }
+        }
+
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(update) => next = Some(EventMessage::from(&update.event)),
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("Event stream client fell behind by {skipped} events");
+                    next = Some(EventMessage::Snapshot {
+                        status: status.snapshot(),
+                    });
+                }
+                Err(RecvError::Closed) => break,
+            },
+            // pings are answered while reading, anything else from the client is ignored
+            received = ws.next() => match received {
+                Some(Ok(Message::Close(_))) | None => return,
+                Some(Ok(_)) => (),
+                Some(Err(e)) => {
+                    debug!("Event stream client failed: {e}");
+                    return;
+                }
+            },
+            _ = async { close_rx.wait_for(|closed| *closed).await.is_ok() } => break,
+        }
+    }
+
+    let _ = ws.close(None).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn messages_carry_version_and_event() {
+        let message = EventMessage::from(&PlayerEvent::VolumeChanged { volume: u16::MAX });
+        let message: Value = serde_json::from_str(&to_json(&message)).unwrap();
+
+        assert_eq!(
+            message,
+            json!({ "version": SCHEMA_VERSION, "event": "volume_changed", "volume": 100 })
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_has_the_status() {
+        let status = StatusTracker::from_events(tokio::sync::mpsc::unbounded_channel().1, 0);
+        let message = EventMessage::Snapshot {
+            status: Status {
+                active_user: Some("user".to_string()),
+                ..status.snapshot()
+            },
+        };
+        let message: Value = serde_json::from_str(&to_json(&message)).unwrap();
+
+        assert_eq!(message["event"], "snapshot");
+        assert_eq!(message["status"]["state"], "stopped");
+        assert_eq!(message["status"]["active_user"], "user");
+    }
+}
@@ -0,0 +1,634 @@
+use log::{debug, error, info, warn};
+
+use std::{collections::HashMap, str::FromStr};
+
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use zbus::{
+    connection, fdo, interface,
+    object_server::{InterfaceRef, SignalEmitter},
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    Connection,
+};
+
+use spotipi::{
+    connect::{LoadRequest, LoadRequestOptions},
+    core::Error,
+    playback::{config::VolumeCtrl, player::PlayerEvent, time_stretch::VALID_PLAYBACK_SPEED_RANGE},
+};
+
+use crate::{
+    control::{ControlCommand, ControlSender},
+    status::{PlaybackState, RepeatStatus, Status, StatusTracker, StatusUpdate},
+};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.spotipi";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MprisBus {
+    Session,
+    System,
+}
+
+impl FromStr for MprisBus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "session" => Ok(Self::Session),
+            "system" => Ok(Self::System),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MprisConfig {
+    pub bus: MprisBus,
+    pub identity: String,
+}
+
+// The MPRIS track id of an item, derived from its uri, e.g. /org/spotipi/track/<id> for
+// spotify:track:<id>. Object paths only allow ASCII letters, digits and underscores.
+fn track_path(uri: &str) -> OwnedObjectPath {
+    let mut path = String::from("/org/spotipi");
+    for segment in uri.split(':').skip(1).filter(|segment| !segment.is_empty()) {
+        path.push('/');
+        path.extend(segment.chars().map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        }));
+    }
+
+    ObjectPath::try_from(path)
+        .map(OwnedObjectPath::from)
+        .unwrap_or_else(|_| no_track())
+}
+
+fn no_track() -> OwnedObjectPath {
+    OwnedObjectPath::from(ObjectPath::from_static_str_unchecked(NO_TRACK))
+}
+
+fn metadata(status: &Status) -> HashMap<String, OwnedValue> {
+    let mut metadata = HashMap::new();
+    let mut insert = |key: &str, value: Value<'_>| {
+        if let Ok(value) = value.try_to_owned() {
+            metadata.insert(key.to_string(), value);
+        }
+    };
+
+    match &status.track {
+        Some(track) => {
+            insert("mpris:trackid", track_path(&track.uri).into());
+            insert("mpris:length", (track.duration_ms as i64 * 1000).into());
+            if let Some(cover) = track.covers.first() {
+                insert("mpris:artUrl", cover.as_str().into());
+            }
+            insert("xesam:title", track.name.as_str().into());
+            insert("xesam:album", track.album.as_str().into());
+            insert("xesam:artist", track.artists.clone().into());
+            insert("xesam:url", track.uri.as_str().into());
+        }
+        None => insert("mpris:trackid", no_track().into()),
+    }
+
+    metadata
+}
+
+fn playback_status(state: PlaybackState) -> &'static str {
+    match state {
+        // Loading is almost always followed by playing, e.g. from one track to the next.
+        PlaybackState::Playing | PlaybackState::Loading => "Playing",
+        PlaybackState::Paused => "Paused",
+        PlaybackState::Stopped => "Stopped",
+    }
+}
+
+fn loop_status(repeat: RepeatStatus) -> &'static str {
+    if repeat.track {
+        "Track"
+    } else if repeat.context {
+        "Playlist"
+    } else {
+        "None"
+    }
+}
+
+fn to_fdo_error(e: Error) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+struct MediaPlayer {
+    identity: String,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer {
+    // There is no window to raise, and the daemon is not quit by its clients.
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn identity(&self) -> String {
+        self.identity.clone()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["spotify".to_string()]
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct MediaPlayerPlayer {
+    status: StatusTracker,
+    control: ControlSender,
+}
+
+impl MediaPlayerPlayer {
+    async fn send(&self, command: ControlCommand) -> fdo::Result<()> {
+        self.control.send(command).await.map_err(to_fdo_error)
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl MediaPlayerPlayer {
+    async fn next(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Next).await
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Prev).await
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Pause).await
+    }
+
+    async fn play_pause(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::PlayPause).await
+    }
+
+    // Spotify Connect has no stop, pausing is as close as it gets.
+    async fn stop(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Pause).await
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        self.send(ControlCommand::Play).await
+    }
+
+    // The offset is in µs, seeking past the end skips to the next track.
+    async fn seek(&self, offset: i64) -> fdo::Result<()> {
+        let status = self.status.snapshot();
+        let Some(track) = status.track else {
+            return Ok(());
+        };
+
+        let position_ms = (status.position_ms as i64 + offset / 1000).max(0);
+        if position_ms > track.duration_ms as i64 {
+            self.send(ControlCommand::Next).await
+        } else {
+            self.send(ControlCommand::SetPosition(position_ms as u32))
+                .await
+        }
+    }
+
+    // Only applies to the current track, as the request may be outdated otherwise.
+    async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+        let status = self.status.snapshot();
+        let Some(track) = status.track else {
+            return Ok(());
+        };
+
+        let position_ms = position / 1000;
+        if track_path(&track.uri).as_ref() != track_id
+            || !(0..=track.duration_ms as i64).contains(&position_ms)
+        {
+            debug!("Ignoring MPRIS SetPosition {position} for {track_id}");
+            return Ok(());
+        }
+
+        self.send(ControlCommand::SetPosition(position_ms as u32))
+            .await
+    }
+
+    async fn open_uri(&self, uri: &str) -> fdo::Result<()> {
+        let options = LoadRequestOptions {
+            start_playing: true,
+            ..Default::default()
+        };
+
+        self.send(ControlCommand::Activate).await?;
+        self.send(ControlCommand::Load(LoadRequest::from_context_uri(
+            uri.to_string(),
+            options,
+        )))
+        .await
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        playback_status(self.status.snapshot().state).to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        loop_status(self.status.snapshot().repeat).to_string()
+    }
+
+    #[zbus(property)]
+    async fn set_loop_status(&self, loop_status: &str) -> zbus::Result<()> {
+        // Repeating the track leaves repeating the context as it is.
+        let (context, track) = match loop_status {
+            "None" => (Some(false), false),
+            "Track" => (None, true),
+            "Playlist" => (Some(true), false),
+            _ => {
+                return Err(
+                    fdo::Error::InvalidArgs(format!("invalid loop status: {loop_status}")).into(),
+                )
+            }
+        };
+
+        if let Some(context) = context {
+            self.send(ControlCommand::Repeat(context)).await?;
+        }
+        Ok(self.send(ControlCommand::RepeatTrack(track)).await?)
+    }
+
+    // The playback speed can only be set on the command line.
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        self.status.snapshot().playback_speed
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn minimum_rate(&self) -> f64 {
+        *VALID_PLAYBACK_SPEED_RANGE.start()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn maximum_rate(&self) -> f64 {
+        *VALID_PLAYBACK_SPEED_RANGE.end()
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.status.snapshot().shuffle
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()> {
+        Ok(self.send(ControlCommand::Shuffle(shuffle)).await?)
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        metadata(&self.status.snapshot())
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.status.snapshot().volume as f64 / 100.0
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&self, volume: f64) -> zbus::Result<()> {
+        let volume = (volume.clamp(0.0, 1.0) * VolumeCtrl::MAX_VOLUME as f64) as u16;
+        Ok(self.send(ControlCommand::SetVolume(volume)).await?)
+    }
+
+    // in µs, clients are to follow it with the playback status and rate, and `Seeked`
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.status.snapshot().position_ms as i64 * 1000
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+// Signals what changed between two statuses, or everything without a previous status.
+async fn signal_changes(
+    player: &InterfaceRef<MediaPlayerPlayer>,
+    previous: Option<&Status>,
+    update: &StatusUpdate,
+) -> zbus::Result<()> {
+    let emitter = player.signal_emitter();
+    let iface = player.get().await;
+    let status = &update.status;
+    let changed = |differs: &dyn Fn(&Status) -> bool| previous.is_none_or(differs);
+
+    if changed(&|previous| previous.state != status.state) {
+        iface.playback_status_changed(emitter).await?;
+    }
+    if changed(&|previous| previous.track != status.track) {
+        iface.metadata_changed(emitter).await?;
+    }
+    if changed(&|previous| previous.volume != status.volume) {
+        iface.volume_changed(emitter).await?;
+    }
+    if changed(&|previous| previous.shuffle != status.shuffle) {
+        iface.shuffle_changed(emitter).await?;
+    }
+    if changed(&|previous| previous.repeat != status.repeat) {
+        iface.loop_status_changed(emitter).await?;
+    }
+    if changed(&|previous| previous.playback_speed != status.playback_speed) {
+        iface.rate_changed(emitter).await?;
+    }
+
+    if let PlayerEvent::Seeked { position_ms, .. }
+    | PlayerEvent::PositionCorrection { position_ms, .. } = update.event
+    {
+        MediaPlayerPlayer::seeked(emitter, position_ms as i64 * 1000).await?;
+    }
+
+    Ok(())
+}
+
+// Registers the MPRIS interfaces on D-Bus, so that desktops and home automation can
+// control the daemon like any other media player.
+pub struct Mpris {
+    connection: Connection,
+    task_handle: JoinHandle<()>,
+}
+
+impl Mpris {
+    pub async fn new(
+        config: MprisConfig,
+        status: StatusTracker,
+        control: ControlSender,
+    ) -> zbus::Result<Self> {
+        let builder = match config.bus {
+            MprisBus::Session => connection::Builder::session()?,
+            MprisBus::System => connection::Builder::system()?,
+        };
+
+        let mpris = Self::serve(builder, config.identity, status, control).await?;
+        info!(
+            "MPRIS registered as {} on the {:?} bus",
+            BUS_NAME, config.bus
+        );
+
+        Ok(mpris)
+    }
+
+    async fn serve(
+        builder: connection::Builder<'_>,
+        identity: String,
+        status: StatusTracker,
+        control: ControlSender,
+    ) -> zbus::Result<Self> {
+        let mut updates = status.subscribe();
+
+        let connection = builder
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, MediaPlayer { identity })?
+            .serve_at(OBJECT_PATH, MediaPlayerPlayer { status, control })?
+            .build()
+            .await?;
+
+        let player = connection
+            .object_server()
+            .interface::<_, MediaPlayerPlayer>(OBJECT_PATH)
+            .await?;
+
+        let task_handle = tokio::spawn(async move {
+            let mut previous: Option<Status> = None;
+
+            loop {
+                let update = match updates.recv().await {
+                    Ok(update) => update,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("MPRIS missed {skipped} updates, signalling everything");
+                        previous = None;
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if let Err(e) = signal_changes(&player, previous.as_ref(), &update).await {
+                    error!("Could not signal MPRIS changes: {e}");
+                }
+                previous = Some(update.status);
+            }
+
+            debug!("MPRIS stopped, the player is gone");
+        });
+
+        Ok(Self {
+            connection,
+            task_handle,
+        })
+    }
+
+    pub async fn shutdown(self) {
+        debug!("Shutting down MPRIS");
+        self.task_handle.abort();
+        if let Err(e) = self.connection.release_name(BUS_NAME).await {
+            debug!("Could not release the MPRIS bus name: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        time::Duration,
+    };
+
+    use tokio::sync::mpsc;
+
+    use spotipi::core::{spotify_id::SpotifyItemType, SpotifyId};
+
+    use crate::control;
+
+    // A dbus-daemon of our own, so that the tests don't depend on a session bus.
+    struct DbusDaemon {
+        child: Child,
+        address: String,
+    }
+
+    impl DbusDaemon {
+        fn start() -> Self {
+            let mut child = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon should start");
+
+            let mut address = String::new();
+            BufReader::new(child.stdout.take().expect("stdout is piped"))
+                .read_line(&mut address)
+                .expect("dbus-daemon should print its address");
+
+            Self {
+                child,
+                address: address.trim().to_string(),
+            }
+        }
+    }
+
+    impl Drop for DbusDaemon {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    #[test]
+    fn track_paths_are_valid_object_paths() {
+        assert_eq!(
+            track_path("spotify:track:4uLU6hMCjMI75M1A2tKUQC").as_str(),
+            "/org/spotipi/track/4uLU6hMCjMI75M1A2tKUQC"
+        );
+        assert_eq!(
+            track_path("spotify:local:Artist:Album:A+Title:180").as_str(),
+            "/org/spotipi/local/Artist/Album/A_Title/180"
+        );
+        assert_eq!(track_path("").as_str(), "/org/spotipi");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs dbus-daemon, run with --ignored"]
+    async fn controls_and_reports_over_dbus() {
+        let daemon = DbusDaemon::start();
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let status = StatusTracker::from_events(event_rx, VolumeCtrl::MAX_VOLUME / 2);
+        let (control, mut control_rx) = control::channel();
+
+        let mpris = Mpris::serve(
+            connection::Builder::address(daemon.address.as_str()).unwrap(),
+            "spotipi test".to_string(),
+            status,
+            control,
+        )
+        .await
+        .unwrap();
+
+        let client = connection::Builder::address(daemon.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let proxy: zbus::Proxy = zbus::proxy::Builder::new(&client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface("org.mpris.MediaPlayer2.Player")
+            .unwrap()
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await
+            .unwrap();
+
+        let playback_status: String = proxy.get_property("PlaybackStatus").await.unwrap();
+        assert_eq!(playback_status, "Stopped");
+        let volume: f64 = proxy.get_property("Volume").await.unwrap();
+        assert_eq!(volume, 0.5);
+        let metadata: HashMap<String, OwnedValue> = proxy.get_property("Metadata").await.unwrap();
+        assert_eq!(
+            metadata["mpris:trackid"],
+            Value::from(no_track()).try_to_owned().unwrap()
+        );
+
+        event_tx
+            .send(PlayerEvent::Playing {
+                play_request_id: 0,
+                track_id: SpotifyId {
+                    id: 1,
+                    item_type: SpotifyItemType::Track,
+                },
+                position_ms: 0,
+            })
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let playback_status: String = proxy.get_property("PlaybackStatus").await.unwrap();
+        assert_eq!(playback_status, "Playing");
+
+        // Commands are answered with what `Spirc` made of them.
+        let (result, ()) = tokio::join!(proxy.call_method("PlayPause", &()), async {
+            let request = control_rx.recv().await.unwrap();
+            assert!(matches!(request.command, ControlCommand::PlayPause));
+            request.respond(Ok(()));
+        });
+        assert!(result.is_ok());
+
+        let (result, ()) = tokio::join!(proxy.set_property("Volume", 0.25), async {
+            let request = control_rx.recv().await.unwrap();
+            assert!(matches!(
+                request.command,
+                ControlCommand::SetVolume(volume) if volume == VolumeCtrl::MAX_VOLUME / 4
+            ));
+            request.respond(Ok(()));
+        });
+        assert!(result.is_ok());
+
+        let (result, ()) = tokio::join!(proxy.call_method("Next", &()), async {
+            let request = control_rx.recv().await.unwrap();
+            request.handle(None);
+        });
+        assert!(result.is_err());
+
+        mpris.shutdown().await;
+    }
+}
@@ -0,0 +1,656 @@
+use log::{debug, error, info, warn};
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use rumqttc::{
+    AsyncClient, ConnectionError, Event, EventLoop, Incoming, LastWill, MqttOptions, Outgoing, QoS,
+};
+use serde_json::{json, Value};
+use tokio::{
+    sync::{broadcast::error::RecvError, Notify},
+    task::JoinHandle,
+    time,
+};
+use url::Url;
+
+use spotipi::{
+    connect::{LoadRequest, LoadRequestOptions},
+    core::version::SEMVER,
+    playback::player::PlayerEvent,
+};
+
+use crate::{
+    control::{ControlCommand, ControlSender},
+    status::{from_percent, Status, StatusTracker, StatusUpdate},
+};
+
+pub const DEFAULT_DISCOVERY_PREFIX: &str = "homeassistant";
+
+const DEFAULT_PORT: u16 = 1883;
+const MAX_PACKET_SIZE: usize = 64 * 1024;
+const REQUEST_CAPACITY: usize = 64;
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+// Where the broker is, from mqtt://[USER[:PASSWORD]@]HOST[:PORT].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttBroker {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+}
+
+impl FromStr for MqttBroker {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(s).map_err(|_| ())?;
+        if !matches!(url.scheme(), "mqtt" | "tcp") {
+            return Err(());
+        }
+
+        let host = match url.host_str() {
+            Some(host) if !host.is_empty() => host.trim_matches(['[', ']']).to_string(),
+            _ => return Err(()),
+        };
+
+        let credentials = match url.username() {
+            "" => None,
+            username => Some((
+                username.to_string(),
+                url.password().unwrap_or_default().to_string(),
+            )),
+        };
+
+        Ok(Self {
+            host,
+            port: url.port().unwrap_or(DEFAULT_PORT),
+            credentials,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub broker: MqttBroker,
+    // what all topics of this device start with
+    pub topic: String,
+    // where Home Assistant looks for discovery configs, None to not publish them
+    pub discovery_prefix: Option<String>,
+    pub device_id: String,
+    pub name: String,
+}
+
+// <topic>/state         the `Status` as JSON
+// <topic>/volume        in % from 0 - 100
+// <topic>/availability  "online" or "offline"
+// <topic>/command/...   see `command`
+#[derive(Debug, Clone)]
+struct Topics {
+    state: String,
+    volume: String,
+    availability: String,
+    command: String,
+}
+
+impl Topics {
+    fn new(topic: &str) -> Self {
+        let topic = topic.trim_end_matches('/');
+        Self {
+            state: format!("{topic}/state"),
+            volume: format!("{topic}/volume"),
+            availability: format!("{topic}/availability"),
+            command: format!("{topic}/command/"),
+        }
+    }
+}
+
+// The commands for <topic>/command/<name>, with the payload where one is needed.
+fn command(name: &str, payload: &str) -> Result<Vec<ControlCommand>, String> {
+    let payload = payload.trim();
+    let command = match name {
+        "play" => ControlCommand::Play,
+        "pause" => ControlCommand::Pause,
+        "play_pause" => ControlCommand::PlayPause,
+        "next" => ControlCommand::Next,
+        "prev" => ControlCommand::Prev,
+        "volume" => match payload.parse::<f64>() {
+            Ok(volume) if (0.0..=100.0).contains(&volume) => {
+                ControlCommand::SetVolume(from_percent(volume.round() as u16))
+            }
+            _ => return Err(format!("invalid volume: \"{payload}\"")),
+        },
+        "load" if payload.is_empty() => return Err("missing uri to load".to_string()),
+        "load" => {
+            let options = LoadRequestOptions {
+                start_playing: true,
+                ..Default::default()
+            };
+
+            return Ok(vec![
+                ControlCommand::Activate,
+                ControlCommand::Load(LoadRequest::from_context_uri(payload.to_string(), options)),
+            ]);
+        }
+        _ => return Err(format!("unknown command: \"{name}\"")),
+    };
+
+    Ok(vec![command])
+}
+
+// The Home Assistant discovery configs as (topic, config). Home Assistant has no MQTT
+// media player, so the device shows up with sensors for what it plays and with buttons,
+// a volume and a text to control it.
+fn discovery_configs(
+    config: &MqttConfig,
+    topics: &Topics,
+    discovery_prefix: &str,
+) -> Vec<(String, Value)> {
+    let device = json!({
+        "identifiers": [config.device_id],
+        "name": config.name,
+        "manufacturer": "SpotiPi",
+        "model": "SpotiPi",
+        "sw_version": SEMVER,
+    });
+
+    let command = |name: &str| format!("{}{name}", topics.command);
+
+    let entities = [
+        (
+            "sensor",
+            "state",
+            json!({
+                "name": "Playback",
+                "icon": "mdi:speaker",
+                "state_topic": topics.state,
+                "value_template": "{{ value_json.state }}",
+            }),
+        ),
+        (
+            "sensor",
+            "track",
+            json!({
+                "name": "Now playing",
+                "icon": "mdi:music",
+                "state_topic": topics.state,
+                "value_template": "{{ value_json.track.name if value_json.track else '' }}",
+                "json_attributes_topic": topics.state,
+                "json_attributes_template": "{{ (value_json.track or {}) | tojson }}",
+            }),
+        ),
+        (
+            "number",
+            "volume",
+            json!({
+                "name": "Volume",
+                "icon": "mdi:volume-high",
+                "state_topic": topics.volume,
+                "command_topic": command("volume"),
+                "min": 0,
+                "max": 100,
+                "step": 1,
+                "unit_of_measurement": "%",
+            }),
+        ),
+        (
+            "button",
+            "play",
+            json!({ "name": "Play", "icon": "mdi:play", "command_topic": command("play") }),
+        ),
+        (
+            "button",
+            "pause",
+            json!({ "name": "Pause", "icon": "mdi:pause", "command_topic": command("pause") }),
+        ),
+        (
+            "button",
+            "next",
+            json!({ "name": "Next", "icon": "mdi:skip-next", "command_topic": command("next") }),
+        ),
+        (
+            "button",
+            "prev",
+            json!({
+                "name": "Previous",
+                "icon": "mdi:skip-previous",
+                "command_topic": command("prev"),
+            }),
+        ),
+        (
+            "text",
+            "load",
+            json!({
+                "name": "Play URI",
+                "icon": "mdi:playlist-play",
+                "command_topic": command("load"),
+            }),
+        ),
+    ];
+
+    entities
+        .into_iter()
+        .map(|(component, object_id, mut entity)| {
+            entity["unique_id"] = json!(format!("spotipi_{}_{object_id}", config.device_id));
+            entity["availability_topic"] = json!(topics.availability);
+            entity["device"] = device.clone();
+
+            let topic = format!(
+                "{discovery_prefix}/{component}/{}/{object_id}/config",
+                config.device_id
+            );
+            (topic, entity)
+        })
+        .collect()
+}
+
+struct Publisher {
+    client: AsyncClient,
+    config: MqttConfig,
+    topics: Topics,
+    volume: Option<u16>,
+}
+
+impl Publisher {
+    async fn publish(&self, topic: &str, payload: impl Into<Vec<u8>>) -> Result<(), String> {
+        self.client
+            .publish(topic, QoS::AtLeastOnce, true, payload)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    // Everything there is to tell after (re)connecting, as the broker may have lost it.
+    async fn announce(&mut self, status: &Status) -> Result<(), String> {
+        self.client
+            .subscribe(format!("{}#", self.topics.command), QoS::AtLeastOnce)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(discovery_prefix) = &self.config.discovery_prefix {
+            for (topic, config) in discovery_configs(&self.config, &self.topics, discovery_prefix) {
+                self.publish(&topic, config.to_string()).await?;
+            }
+        }
+
+        self.publish(&self.topics.availability, ONLINE).await?;
+        self.volume = None;
+        self.publish_status(status).await
+    }
+
+    async fn publish_status(&mut self, status: &Status) -> Result<(), String> {
+        let state = serde_json::to_string(status).map_err(|e| e.to_string())?;
+        self.publish(&self.topics.state, state).await?;
+
+        if self.volume != Some(status.volume) {
+            self.publish(&self.topics.volume, status.volume.to_string())
+                .await?;
+            self.volume = Some(status.volume);
+        }
+
+        Ok(())
+    }
+}
+
+async fn run_connection(
+    mut eventloop: EventLoop,
+    connected: Arc<Notify>,
+    control: ControlSender,
+    command_topic: String,
+) {
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                info!("MQTT connected");
+                connected.notify_one();
+            }
+            Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                let Some(name) = publish.topic.strip_prefix(&command_topic) else {
+                    continue;
+                };
+
+                let commands = match command(name, &String::from_utf8_lossy(&publish.payload)) {
+                    Ok(commands) => commands,
+                    Err(e) => {
+                        warn!("Ignoring MQTT command on {}: {e}", publish.topic);
+                        continue;
+                    }
+                };
+
+                // the event loop must keep running while `Spirc` takes the commands
+                let control = control.clone();
+                tokio::spawn(async move {
+                    for command in commands {
+                        if let Err(e) = control.send(command).await {
+                            warn!("MQTT command failed: {e}");
+                            break;
+                        }
+                    }
+                });
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(ConnectionError::RequestsDone) => break,
+            Ok(_) => (),
+            Err(e) => {
+                warn!(
+                    "MQTT connection failed: {e}, reconnecting in {}s",
+                    RECONNECT_DELAY.as_secs()
+                );
+                time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+
+    debug!("MQTT disconnected");
+}
+
+// Publishes the status to an MQTT broker and takes commands from it, along with the
+// Home Assistant discovery configs.
+pub struct Mqtt {
+    client: AsyncClient,
+    availability_topic: String,
+    task_handle: JoinHandle<()>,
+    connection_handle: JoinHandle<()>,
+}
+
+impl Mqtt {
+    pub fn new(config: MqttConfig, status: StatusTracker, control: ControlSender) -> Self {
+        let topics = Topics::new(&config.topic);
+
+        let mut options = MqttOptions::new(
+            format!("spotipi-{}", config.device_id),
+            config.broker.host.clone(),
+            config.broker.port,
+        );
+        options
+            .set_keep_alive(KEEP_ALIVE)
+            .set_max_packet_size(MAX_PACKET_SIZE, MAX_PACKET_SIZE)
+            .set_last_will(LastWill::new(
+                topics.availability.clone(),
+                OFFLINE,
+                QoS::AtLeastOnce,
+                true,
+            ));
+        if let Some((username, password)) = &config.broker.credentials {
+            options.set_credentials(username, password);
+        }
+
+        info!(
+            "MQTT connecting to {}:{} as {}",
+            config.broker.host, config.broker.port, config.topic
+        );
+
+        let (client, eventloop) = AsyncClient::new(options, REQUEST_CAPACITY);
+        let connected = Arc::new(Notify::new());
+
+        let connection_handle = tokio::spawn(run_connection(
+            eventloop,
+            connected.clone(),
+            control,
+            topics.command.clone(),
+        ));
+
+        let availability_topic = topics.availability.clone();
+        let mut publisher = Publisher {
+            client: client.clone(),
+            config,
+            topics,
+            volume: None,
+        };
+
+        let task_handle = tokio::spawn(async move {
+            let mut updates = status.subscribe();
+
+            loop {
+                let result = tokio::select! {
+                    _ = connected.notified() => publisher.announce(&status.snapshot()).await,
+                    update = updates.recv() => match update {
+                        // far too many to publish them all
+                        Ok(StatusUpdate {
+                            event: PlayerEvent::Levels { .. },
+                            ..
+                        }) => continue,
+                        Ok(update) => publisher.publish_status(&update.status).await,
+                        Err(RecvError::Lagged(_)) => {
+                            publisher.publish_status(&status.snapshot()).await
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+
+                if let Err(e) = result {
+                    error!("Could not publish to MQTT: {e}");
+                }
+            }
+
+            debug!("MQTT stopped, the player is gone");
+        });
+
+        Self {
+            client,
+            availability_topic,
+            task_handle,
+            connection_handle,
+        }
+    }
+
+    pub async fn shutdown(mut self) {
+        debug!("Shutting down MQTT");
+        self.task_handle.abort();
+
+        // without waiting, in case the broker is gone
+        let _ = self
+            .client
+            .try_publish(&self.availability_topic, QoS::AtLeastOnce, true, OFFLINE);
+        let _ = self.client.try_disconnect();
+
+        if time::timeout(DISCONNECT_TIMEOUT, &mut self.connection_handle)
+            .await
+            .is_err()
+        {
+            self.connection_handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        collections::HashMap,
+        net::{TcpListener, TcpStream},
+        process::{Child, Command, Stdio},
+    };
+
+    use tokio::sync::mpsc;
+
+    use spotipi::playback::config::VolumeCtrl;
+
+    use crate::control;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // A mosquitto of our own on a free port, so that the tests don't depend on a broker.
+    struct Mosquitto {
+        child: Child,
+        port: u16,
+    }
+
+    impl Mosquitto {
+        fn start() -> Self {
+            let port = TcpListener::bind("127.0.0.1:0")
+                .and_then(|listener| listener.local_addr())
+                .expect("a free port")
+                .port();
+            let child = Command::new("mosquitto")
+                .args(["-p", &port.to_string()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("mosquitto should start");
+            let mosquitto = Self { child, port };
+
+            for _ in 0..50 {
+                if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    return mosquitto;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            panic!("mosquitto does not accept connections");
+        }
+    }
+
+    impl Drop for Mosquitto {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    #[test]
+    fn parses_broker_urls() {
+        assert_eq!(
+            MqttBroker::from_str("mqtt://localhost"),
+            Ok(MqttBroker {
+                host: "localhost".to_string(),
+                port: DEFAULT_PORT,
+                credentials: None,
+            })
+        );
+        assert_eq!(
+            MqttBroker::from_str("mqtt://user:secret@[::1]:1884"),
+            Ok(MqttBroker {
+                host: "::1".to_string(),
+                port: 1884,
+                credentials: Some(("user".to_string(), "secret".to_string())),
+            })
+        );
+        assert!(MqttBroker::from_str("http://localhost").is_err());
+        assert!(MqttBroker::from_str("localhost:1883").is_err());
+    }
+
+    #[test]
+    fn maps_commands() {
+        assert!(matches!(
+            command("play_pause", "").as_deref(),
+            Ok([ControlCommand::PlayPause])
+        ));
+        assert!(matches!(
+            command("volume", "100").as_deref(),
+            Ok([ControlCommand::SetVolume(u16::MAX)])
+        ));
+        assert!(matches!(
+            command("load", "spotify:album:1").as_deref(),
+            Ok([ControlCommand::Activate, ControlCommand::Load(_)])
+        ));
+
+        assert!(command("volume", "101").is_err());
+        assert!(command("load", " ").is_err());
+        assert!(command("stop", "").is_err());
+    }
+
+    #[test]
+    fn discovery_uses_the_topics() {
+        let config = MqttConfig {
+            broker: MqttBroker::from_str("mqtt://localhost").unwrap(),
+            topic: "spotipi/kitchen/".to_string(),
+            discovery_prefix: Some(DEFAULT_DISCOVERY_PREFIX.to_string()),
+            device_id: "abc".to_string(),
+            name: "Kitchen".to_string(),
+        };
+        let topics = Topics::new(&config.topic);
+
+        let configs = discovery_configs(&config, &topics, DEFAULT_DISCOVERY_PREFIX);
+        let (topic, volume) = configs
+            .iter()
+            .find(|(topic, _)| topic.contains("/number/"))
+            .unwrap();
+
+        assert_eq!(topic, "homeassistant/number/abc/volume/config");
+        assert_eq!(volume["command_topic"], "spotipi/kitchen/command/volume");
+        assert_eq!(volume["state_topic"], "spotipi/kitchen/volume");
+        assert_eq!(volume["availability_topic"], "spotipi/kitchen/availability");
+        assert_eq!(volume["unique_id"], "spotipi_abc_volume");
+        assert_eq!(volume["device"]["name"], "Kitchen");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs mosquitto, run with --ignored"]
+    async fn announces_and_takes_commands() {
+        let mosquitto = Mosquitto::start();
+        // a client to watch what is published and to send commands
+        let mut options = MqttOptions::new("spotipi-test", "127.0.0.1", mosquitto.port);
+        options.set_max_packet_size(MAX_PACKET_SIZE, MAX_PACKET_SIZE);
+        let (client, mut eventloop) = AsyncClient::new(options, REQUEST_CAPACITY);
+        client.subscribe("#", QoS::AtLeastOnce).await.unwrap();
+        let (publish_tx, mut publish_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok(event) = eventloop.poll().await {
+                if let Event::Incoming(Incoming::Publish(publish)) = event {
+                    let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                    let _ = publish_tx.send((publish.topic, payload));
+                }
+            }
+        });
+
+        let config = MqttConfig {
+            broker: MqttBroker::from_str(&format!("mqtt://127.0.0.1:{}", mosquitto.port)).unwrap(),
+            topic: "spotipi/kitchen".to_string(),
+            discovery_prefix: Some(DEFAULT_DISCOVERY_PREFIX.to_string()),
+            device_id: "abc".to_string(),
+            name: "Kitchen".to_string(),
+        };
+        let expected = discovery_configs(
+            &config,
+            &Topics::new(&config.topic),
+            DEFAULT_DISCOVERY_PREFIX,
+        );
+
+        let (_event_tx, event_rx) = mpsc::unbounded_channel();
+        let status = StatusTracker::from_events(event_rx, VolumeCtrl::MAX_VOLUME / 2);
+        let (control, mut control_rx) = control::channel();
+        let mqtt = Mqtt::new(config, status, control);
+
+        // the discovery configs, the availability, the state and the volume, as the last
+        // one to be announced
+        let mut published = HashMap::new();
+        while !published.contains_key("spotipi/kitchen/volume") {
+            let (topic, payload) = time::timeout(TIMEOUT, publish_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            published.insert(topic, payload);
+        }
+
+        for (topic, config) in expected {
+            let published: Value = serde_json::from_str(&published[&topic]).unwrap();
+            assert_eq!(published, config);
+        }
+        assert_eq!(published["spotipi/kitchen/availability"], ONLINE);
+        assert_eq!(published["spotipi/kitchen/volume"], "50");
+        let state: Value = serde_json::from_str(&published["spotipi/kitchen/state"]).unwrap();
+        assert_eq!(state["state"], "stopped");
+
+        client
+            .publish(
+                "spotipi/kitchen/command/play_pause",
+                QoS::AtLeastOnce,
+                false,
+                "",
+            )
+            .await
+            .unwrap();
+        let request = time::timeout(TIMEOUT, control_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(request.command, ControlCommand::PlayPause));
+        request.respond(Ok(()));
+
+        mqtt.shutdown().await;
+        let _ = client.disconnect().await;
+    }
+}
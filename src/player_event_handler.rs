@@ -194,6 +194,23 @@ impl EventHandler {
                                 env_vars.insert("POSITION_MS", position_ms.to_string());
                             }
                         },
+                        PlayerEvent::PlaybackSpeedChanged {
+                            track_id,
+                            position_ms,
+                            speed,
+                            ..
+                        } => match track_id.to_base62() {
+                            Err(e) => {
+                                warn!("PlayerEvent::PlaybackSpeedChanged: Invalid track id: {}", e)
+                            }
+                            Ok(id) => {
+                                env_vars
+                                    .insert("PLAYER_EVENT", "playback_speed_changed".to_string());
+                                env_vars.insert("TRACK_ID", id);
+                                env_vars.insert("POSITION_MS", position_ms.to_string());
+                                env_vars.insert("PLAYBACK_SPEED", speed.to_string());
+                            }
+                        },
                         PlayerEvent::SessionConnected {
                             connection_id,
                             user_name,
@@ -243,6 +260,28 @@ impl EventHandler {
                             );
                             env_vars.insert("FILTER", filter.to_string());
                         }
+                        PlayerEvent::SleepTimerChanged { sleep_timer } => {
+                            env_vars.insert("PLAYER_EVENT", "sleep_timer_changed".to_string());
+                            env_vars.insert(
+                                "SLEEP_TIMER",
+                                sleep_timer.map(|t| t.to_string()).unwrap_or_default(),
+                            );
+                        }
+                        PlayerEvent::ActiveDeviceChanged {
+                            device_id,
+                            device_name,
+                        } => {
+                            env_vars.insert("PLAYER_EVENT", "active_device_changed".to_string());
+                            env_vars.insert("DEVICE_ID", device_id.unwrap_or_default());
+                            env_vars.insert("DEVICE_NAME", device_name.unwrap_or_default());
+                        }
+                        PlayerEvent::UserRejected { user_name } => {
+                            env_vars.insert("PLAYER_EVENT", "user_rejected".to_string());
+                            env_vars.insert("USER_NAME", user_name);
+                        }
+                        // A process per reading would be too much, the levels are
+                        // only sent to the event stream of the control API.
+                        PlayerEvent::Levels { .. } => (),
                     }
 
                     if !env_vars.is_empty() {
@@ -0,0 +1,575 @@
+use log::{debug, info};
+
+use std::{str::FromStr, time::Duration};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeDelta};
+use thiserror::Error;
+use tokio::time::{sleep, Instant};
+
+use spotipi::playback::player::{Player, PlayerEvent, PlayerEventChannel};
+
+// limits how long we sleep at once, so that changes of the system clock
+// (e.g. an NTP sync after boot) are picked up in time
+const MAX_ALARM_WAIT: Duration = Duration::from_secs(60);
+// alarms that were due longer ago than this are considered missed and skipped
+const MISSED_ALARM_GRACE: TimeDelta = TimeDelta::minutes(1);
+// enough to find the next match of a schedule like "0 0 29 2 *"
+const MAX_SEARCH_DAYS: usize = 366 * 8;
+
+const RAMP_INTERVAL: Duration = Duration::from_millis(500);
+const RAMP_START_VOLUME: u16 = 0;
+// how far a reported volume may be off the one we requested and still be ours,
+// e.g. after being rounded by the mixer or the CEC volume scale
+const RAMP_VOLUME_TOLERANCE: u16 = u16::MAX / 100;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("expected {expected} fields, got {actual}")]
+    FieldCount { expected: usize, actual: usize },
+    #[error("invalid {field} field: \"{value}\"")]
+    InvalidField { field: &'static str, value: String },
+    #[error("invalid context uri: \"{0}\"")]
+    InvalidUri(String),
+}
+
+// A cron-like schedule of the form "MINUTE HOUR DAY-OF-MONTH MONTH WEEKDAY".
+//
+// Every field accepts `*`, single values, ranges (`1-5`), lists (`1,3,5`)
+// and steps (`*/15`, `0-30/10`). Months and weekdays also accept their
+// three letter names (`jan`, `mon-fri`), Sunday is either 0 or 7.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    weekdays: u64,
+    // like cron, a day matches either restricted day field if both are restricted
+    restricted_days_of_month: bool,
+    restricted_weekdays: bool,
+}
+
+fn parse_field(
+    field: &'static str,
+    value: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+) -> Result<u64, ScheduleError> {
+    let invalid = || ScheduleError::InvalidField {
+        field,
+        value: value.to_string(),
+    };
+
+    let parse_value = |s: &str| -> Result<u32, ScheduleError> {
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(s))
+            .map(|index| index as u32 + min)
+            .or_else(|| s.parse().ok())
+            .filter(|v| (min..=max).contains(v))
+            .ok_or_else(invalid)
+    };
+
+    let mut bits = 0;
+    for item in value.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<usize>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(invalid)?,
+            ),
+            None => (item, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            let start = parse_value(range)?;
+            // "5/15" is short for "5-max/15"
+            (start, if step > 1 { max } else { start })
+        };
+
+        if start > end {
+            return Err(invalid());
+        }
+
+        for v in (start..=end).step_by(step) {
+            bits |= 1 << v;
+        }
+    }
+
+    Ok(bits)
+}
+
+impl Schedule {
+    fn from_fields(fields: &[&str]) -> Result<Self, ScheduleError> {
+        let [minutes, hours, days_of_month, months, weekdays] = fields else {
+            return Err(ScheduleError::FieldCount {
+                expected: 5,
+                actual: fields.len(),
+            });
+        };
+
+        let mut weekday_bits = parse_field("weekday", weekdays, 0, 7, &WEEKDAY_NAMES)?;
+        if weekday_bits & 1 << 7 != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field("minute", minutes, 0, 59, &[])?,
+            hours: parse_field("hour", hours, 0, 23, &[])?,
+            days_of_month: parse_field("day of month", days_of_month, 1, 31, &[])?,
+            months: parse_field("month", months, 1, 12, &MONTH_NAMES)?,
+            weekdays: weekday_bits,
+            restricted_days_of_month: *days_of_month != "*",
+            restricted_weekdays: *weekdays != "*",
+        })
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & 1 << date.month() == 0 {
+            return false;
+        }
+
+        let day_of_month = self.days_of_month & 1 << date.day() != 0;
+        let weekday = self.weekdays & 1 << date.weekday().num_days_from_sunday() != 0;
+
+        if self.restricted_days_of_month && self.restricted_weekdays {
+            day_of_month || weekday
+        } else {
+            day_of_month && weekday
+        }
+    }
+
+    // Returns the first time matching the schedule strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let after = after.naive_local();
+        let mut date = after.date();
+
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                for hour in (0..24).filter(|hour| self.hours & 1 << hour != 0) {
+                    for minute in (0..60).filter(|minute| self.minutes & 1 << minute != 0) {
+                        let time = date.and_hms_opt(hour, minute, 0)?;
+                        if time <= after {
+                            continue;
+                        }
+
+                        // times skipped by a daylight saving transition don't exist locally
+                        if let Some(time) = time.and_local_timezone(Local).earliest() {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+
+            date = date.succ_opt()?;
+        }
+
+        None
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_fields(&s.split_whitespace().collect::<Vec<_>>())
+    }
+}
+
+// A schedule followed by the context to play, e.g. "30 7 * * mon-fri spotify:playlist:...".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alarm {
+    pub schedule: Schedule,
+    pub context_uri: String,
+}
+
+impl FromStr for Alarm {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let Some((context_uri, schedule)) = fields.split_last() else {
+            return Err(ScheduleError::FieldCount {
+                expected: 6,
+                actual: 0,
+            });
+        };
+
+        if schedule.len() != 5 {
+            return Err(ScheduleError::FieldCount {
+                expected: 6,
+                actual: fields.len(),
+            });
+        }
+
+        if !context_uri.starts_with("spotify:") {
+            return Err(ScheduleError::InvalidUri(context_uri.to_string()));
+        }
+
+        Ok(Self {
+            schedule: Schedule::from_fields(schedule)?,
+            context_uri: context_uri.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub alarms: Vec<Alarm>,
+    // the volume the playback is ramped up to
+    pub volume: u16,
+    // the time over which the volume is ramped up, zero starts at the target volume
+    pub ramp: Duration,
+    // the limits the device keeps the volume within
+    pub min_volume: u16,
+    pub max_volume: u16,
+    // skips alarms while something is already playing
+    pub only_if_idle: bool,
+}
+
+pub enum SchedulerAction {
+    // activate the device and start playing the context at the given volume
+    Start { context_uri: String, volume: u16 },
+    SetVolume(u16),
+}
+
+struct VolumeRamp {
+    started: Instant,
+    // every volume we requested, within the limits, to tell our own volume changes
+    // apart from the user's
+    sent: Vec<u16>,
+}
+
+impl VolumeRamp {
+    fn sent_volume(&self, volume: u16) -> bool {
+        self.sent
+            .iter()
+            .any(|sent| sent.abs_diff(volume) <= RAMP_VOLUME_TOLERANCE)
+    }
+}
+
+pub struct Scheduler {
+    config: SchedulerConfig,
+    player_events: PlayerEventChannel,
+    is_playing: bool,
+    next_alarm: Option<(DateTime<Local>, String)>,
+    ramp: Option<VolumeRamp>,
+}
+
+impl Scheduler {
+    pub fn new(config: SchedulerConfig, player: &Player) -> Self {
+        Self {
+            config,
+            player_events: player.get_player_event_channel(),
+            is_playing: false,
+            next_alarm: None,
+            ramp: None,
+        }
+    }
+
+    pub fn stop_ramp(&mut self) {
+        self.ramp = None;
+    }
+
+    // Waits for the next thing to do, returns `None` once the player is gone.
+    pub async fn next_action(&mut self) -> Option<SchedulerAction> {
+        loop {
+            if self.next_alarm.is_none() {
+                let now = Local::now();
+                self.next_alarm = self
+                    .config
+                    .alarms
+                    .iter()
+                    .filter_map(|alarm| {
+                        let time = alarm.schedule.next_after(now)?;
+                        Some((time, alarm.context_uri.clone()))
+                    })
+                    .min_by_key(|(time, _)| *time);
+
+                if let Some((time, context_uri)) = &self.next_alarm {
+                    debug!("next alarm at {time} for {context_uri}");
+                }
+            }
+
+            let alarm_wait = self.next_alarm.as_ref().map(|(time, _)| {
+                (*time - Local::now())
+                    .to_std()
+                    .unwrap_or_default()
+                    .min(MAX_ALARM_WAIT)
+            });
+
+            tokio::select! {
+                event = self.player_events.recv() => self.handle_player_event(event?),
+                _ = sleep(alarm_wait.unwrap_or_default()), if alarm_wait.is_some() => {
+                    if let Some(action) = self.handle_alarm() {
+                        return Some(action);
+                    }
+                },
+                _ = sleep(RAMP_INTERVAL), if self.ramp.is_some() => {
+                    if let Some(volume) = self.next_ramp_volume() {
+                        return Some(SchedulerAction::SetVolume(volume));
+                    }
+                },
+            }
+        }
+    }
+
+    fn handle_player_event(&mut self, event: PlayerEvent) {
+        match event {
+            PlayerEvent::Playing { .. } => self.is_playing = true,
+            PlayerEvent::Paused { .. }
+            | PlayerEvent::Stopped { .. }
+            | PlayerEvent::EndOfTrack { .. }
+            | PlayerEvent::Unavailable { .. } => {
+                self.is_playing = false;
+                if self.ramp.take().is_some() {
+                    debug!("alarm volume ramp stopped, playback no longer playing");
+                }
+            }
+            PlayerEvent::VolumeChanged { volume } => {
+                if matches!(&self.ramp, Some(ramp) if !ramp.sent_volume(volume)) {
+                    debug!("alarm volume ramp stopped, volume changed to {volume}");
+                    self.ramp = None;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_alarm(&mut self) -> Option<SchedulerAction> {
+        let now = Local::now();
+        let (time, _) = self.next_alarm.as_ref()?;
+        if now < *time {
+            // the clock changed while we were waiting
+            return None;
+        }
+
+        let (time, context_uri) = self.next_alarm.take()?;
+        if now - time > MISSED_ALARM_GRACE {
+            debug!("skipping alarm at {time}, it was missed");
+            return None;
+        }
+
+        if self.config.only_if_idle && self.is_playing {
+            info!("skipping alarm for {context_uri}, already playing");
+            return None;
+        }
+
+        info!("alarm: starting {context_uri}");
+
+        let volume = if self.config.ramp.is_zero() {
+            self.config.volume
+        } else {
+            let volume = self.limit_volume(RAMP_START_VOLUME);
+            self.ramp = Some(VolumeRamp {
+                started: Instant::now(),
+                sent: vec![volume],
+            });
+            volume
+        };
+
+        Some(SchedulerAction::Start {
+            context_uri,
+            volume,
+        })
+    }
+
+    fn limit_volume(&self, volume: u16) -> u16 {
+        volume.clamp(self.config.min_volume, self.config.max_volume)
+    }
+
+    fn next_ramp_volume(&mut self) -> Option<u16> {
+        let start = f64::from(self.limit_volume(RAMP_START_VOLUME));
+        let end = f64::from(self.limit_volume(self.config.volume));
+        let ramp = self.ramp.as_mut()?;
+
+        let progress =
+            (ramp.started.elapsed().as_secs_f64() / self.config.ramp.as_secs_f64()).clamp(0.0, 1.0);
+        let volume = (start + (end - start) * progress) as u16;
+
+        if progress >= 1.0 {
+            self.ramp = None;
+        } else {
+            ramp.sent.push(volume);
+        }
+
+        Some(volume)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_alarm() {
+        let alarm: Alarm = "30 7 * * mon-fri spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"
+            .parse()
+            .unwrap();
+        assert_eq!(alarm.context_uri, "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M");
+        assert_eq!(alarm.schedule, "30 7 * * 1-5".parse().unwrap());
+
+        assert_eq!(
+            "0 7 * * *".parse::<Alarm>(),
+            Err(ScheduleError::FieldCount {
+                expected: 6,
+                actual: 5
+            })
+        );
+        assert!(matches!(
+            "0 7 * * * playlist".parse::<Alarm>(),
+            Err(ScheduleError::InvalidUri(_))
+        ));
+    }
+
+    #[test]
+    fn parse_schedule() {
+        assert_eq!(
+            "0 7 * * sun".parse::<Schedule>(),
+            "0 7 * * 7".parse::<Schedule>()
+        );
+        assert_eq!(
+            "*/20 * * * *".parse::<Schedule>().unwrap().minutes,
+            1 | 1 << 20 | 1 << 40
+        );
+        assert_eq!(
+            "5/30,1 * * * *".parse::<Schedule>().unwrap().minutes,
+            1 << 1 | 1 << 5 | 1 << 35
+        );
+
+        for invalid in [
+            "60 * * * *",
+            "* 7-5 * * *",
+            "* * 0 * *",
+            "* * * * funday",
+            "*/0 * * * *",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<Schedule>(),
+                    Err(ScheduleError::InvalidField { .. })
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn next_after() {
+        // 2024-06-07 is a Friday
+        let weekdays: Schedule = "30 7 * * mon-fri".parse().unwrap();
+        assert_eq!(
+            weekdays.next_after(local(2024, 6, 7, 6, 0)),
+            Some(local(2024, 6, 7, 7, 30))
+        );
+        assert_eq!(
+            weekdays.next_after(local(2024, 6, 7, 7, 30)),
+            Some(local(2024, 6, 10, 7, 30))
+        );
+
+        let leap_day: Schedule = "0 9 29 feb *".parse().unwrap();
+        assert_eq!(
+            leap_day.next_after(local(2024, 3, 1, 0, 0)),
+            Some(local(2028, 2, 29, 9, 0))
+        );
+
+        // either the day of month or the weekday matches
+        let first_or_sunday: Schedule = "0 8 1 * sun".parse().unwrap();
+        assert_eq!(
+            first_or_sunday.next_after(local(2024, 6, 7, 0, 0)),
+            Some(local(2024, 6, 9, 8, 0))
+        );
+        assert_eq!(
+            first_or_sunday.next_after(local(2024, 6, 30, 9, 0)),
+            Some(local(2024, 7, 1, 8, 0))
+        );
+    }
+
+    #[test]
+    fn volume_ramp_within_the_volume_limits() {
+        let (_, player_events) = tokio::sync::mpsc::unbounded_channel();
+        let mut scheduler = Scheduler {
+            config: SchedulerConfig {
+                alarms: Vec::new(),
+                volume: u16::MAX,
+                ramp: Duration::from_secs(60),
+                min_volume: 10000,
+                max_volume: 30000,
+                only_if_idle: false,
+            },
+            player_events,
+            is_playing: false,
+            next_alarm: Some((Local::now(), "spotify:album:0".to_string())),
+            ramp: None,
+        };
+
+        assert!(matches!(
+            scheduler.handle_alarm(),
+            Some(SchedulerAction::Start { volume: 10000, .. })
+        ));
+        scheduler.handle_player_event(PlayerEvent::VolumeChanged { volume: 10000 });
+        assert!(scheduler.ramp.is_some());
+
+        let ramp = scheduler.ramp.as_mut().unwrap();
+        ramp.started = Instant::now() - Duration::from_secs(30);
+        let volume = scheduler.next_ramp_volume().unwrap();
+        assert!((19900..=20100).contains(&volume), "{volume}");
+
+        // rounded by the mixer
+        scheduler.handle_player_event(PlayerEvent::VolumeChanged {
+            volume: volume + 100,
+        });
+        assert!(scheduler.ramp.is_some());
+
+        let ramp = scheduler.ramp.as_mut().unwrap();
+        ramp.started = Instant::now() - Duration::from_secs(60);
+        assert_eq!(scheduler.next_ramp_volume(), Some(30000));
+        assert!(scheduler.ramp.is_none());
+    }
+
+    #[test]
+    fn volume_ramp_stops_when_the_user_changes_the_volume() {
+        let (_, player_events) = tokio::sync::mpsc::unbounded_channel();
+        let mut scheduler = Scheduler {
+            config: SchedulerConfig {
+                alarms: Vec::new(),
+                volume: 40000,
+                ramp: Duration::from_secs(60),
+                min_volume: 0,
+                max_volume: u16::MAX,
+                only_if_idle: false,
+            },
+            player_events,
+            is_playing: false,
+            next_alarm: Some((Local::now(), "spotify:album:0".to_string())),
+            ramp: None,
+        };
+
+        scheduler.handle_alarm();
+        let volume = scheduler.next_ramp_volume().unwrap();
+        scheduler.handle_player_event(PlayerEvent::VolumeChanged { volume });
+        assert!(scheduler.ramp.is_some());
+
+        scheduler.handle_player_event(PlayerEvent::VolumeChanged { volume: 20000 });
+        assert!(scheduler.ramp.is_none());
+    }
+}
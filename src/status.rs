@@ -0,0 +1,493 @@
+use log::debug;
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use spotipi::{
+    metadata::audio::{AudioItem, UniqueFields},
+    playback::{
+        config::VolumeCtrl,
+        mixer::Mixer,
+        player::{Player, PlayerEvent, PlayerEventChannel, SleepTimer},
+    },
+};
+use spotipi_playback::cec::CecClient;
+
+// Updates that subscribers have yet to receive before they start to miss some.
+const UPDATE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    Stopped,
+    Loading,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackStatus {
+    pub uri: String,
+    pub name: String,
+    // "track" or "episode"
+    pub item_type: &'static str,
+    pub artists: Vec<String>,
+    // the album of a track, the show of an episode
+    pub album: String,
+    pub duration_ms: u32,
+    pub is_explicit: bool,
+    pub covers: Vec<String>,
+}
+
+impl From<&AudioItem> for TrackStatus {
+    fn from(audio_item: &AudioItem) -> Self {
+        let (item_type, artists, album) = match &audio_item.unique_fields {
+            UniqueFields::Track { artists, album, .. } => (
+                "track",
+                artists.0.iter().map(|artist| artist.name.clone()).collect(),
+                album.clone(),
+            ),
+            UniqueFields::Episode { show_name, .. } => ("episode", Vec::new(), show_name.clone()),
+        };
+
+        Self {
+            uri: audio_item.uri.clone(),
+            name: audio_item.name.clone(),
+            item_type,
+            artists,
+            album,
+            duration_ms: audio_item.duration_ms,
+            is_explicit: audio_item.is_explicit,
+            covers: audio_item
+                .covers
+                .iter()
+                .map(|cover| cover.url.clone())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RepeatStatus {
+    pub context: bool,
+    pub track: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SleepTimerStatus {
+    // "duration", "end_of_track" or "end_of_context"
+    pub mode: &'static str,
+    // until the playback pauses, `None` while that can't be told yet
+    pub remaining_ms: Option<u64>,
+}
+
+// What the daemon is doing, as reported to control surfaces.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Status {
+    pub state: PlaybackState,
+    pub track: Option<TrackStatus>,
+    pub position_ms: u32,
+    pub playback_speed: f64,
+    // in % from 0 - 100
+    pub volume: u16,
+    pub shuffle: bool,
+    pub repeat: RepeatStatus,
+    pub active_user: Option<String>,
+    pub cec_power: bool,
+    pub sleep_timer: Option<SleepTimerStatus>,
+}
+
+struct TrackedStatus {
+    status: Status,
+    // when `status.position_ms` was reported, while playing
+    position_at: Option<Instant>,
+    // the sleep timer and when it was set
+    sleep_timer: Option<(SleepTimer, Instant)>,
+}
+
+impl TrackedStatus {
+    fn new(volume: u16) -> Self {
+        Self {
+            status: Status {
+                state: PlaybackState::Stopped,
+                track: None,
+                position_ms: 0,
+                playback_speed: 1.0,
+                volume: to_percent(volume),
+                shuffle: false,
+                repeat: RepeatStatus::default(),
+                active_user: None,
+                cec_power: false,
+                sleep_timer: None,
+            },
+            position_at: None,
+            sleep_timer: None,
+        }
+    }
+
+    fn set_position(&mut self, position_ms: u32) {
+        self.status.position_ms = position_ms;
+        self.position_at = match self.status.state {
+            PlaybackState::Playing => Some(Instant::now()),
+            _ => None,
+        };
+    }
+
+    fn set_state(&mut self, state: PlaybackState, position_ms: u32) {
+        self.status.state = state;
+        self.set_position(position_ms);
+    }
+
+    fn handle_player_event(&mut self, event: PlayerEvent) {
+        match event {
+            PlayerEvent::TrackChanged { audio_item } => {
+                self.status.track = Some(TrackStatus::from(audio_item.as_ref()));
+            }
+            PlayerEvent::Loading { position_ms, .. } => {
+                self.set_state(PlaybackState::Loading, position_ms)
+            }
+            PlayerEvent::Playing { position_ms, .. } => {
+                self.set_state(PlaybackState::Playing, position_ms)
+            }
+            PlayerEvent::Paused { position_ms, .. } => {
+                self.set_state(PlaybackState::Paused, position_ms)
+            }
+            PlayerEvent::Stopped { .. } => {
+                self.status.track = None;
+                self.set_state(PlaybackState::Stopped, 0);
+            }
+            PlayerEvent::Seeked { position_ms, .. }
+            | PlayerEvent::PositionCorrection { position_ms, .. } => self.set_position(position_ms),
+            PlayerEvent::PlaybackSpeedChanged {
+                position_ms, speed, ..
+            } => {
+                self.status.playback_speed = speed;
+                self.set_position(position_ms);
+            }
+            PlayerEvent::VolumeChanged { volume } => self.status.volume = to_percent(volume),
+            PlayerEvent::ShuffleChanged { shuffle } => self.status.shuffle = shuffle,
+            PlayerEvent::RepeatChanged { context, track } => {
+                self.status.repeat = RepeatStatus { context, track }
+            }
+            PlayerEvent::SessionConnected { user_name, .. } => {
+                self.status.active_user = Some(user_name)
+            }
+            PlayerEvent::SessionDisconnected { .. } => self.status.active_user = None,
+            PlayerEvent::SleepTimerChanged { sleep_timer } => {
+                self.sleep_timer = sleep_timer.map(|sleep_timer| (sleep_timer, Instant::now()))
+            }
+            _ => (),
+        }
+    }
+
+    fn snapshot(&self) -> Status {
+        let mut status = self.status.clone();
+
+        if let Some(position_at) = self.position_at {
+            let elapsed_ms = position_at.elapsed().as_secs_f64() * 1000.0 * status.playback_speed;
+            let duration_ms = status
+                .track
+                .as_ref()
+                .map_or(u32::MAX, |track| track.duration_ms);
+            status.position_ms =
+                (status.position_ms as f64 + elapsed_ms).min(duration_ms as f64) as u32;
+        }
+
+        status.sleep_timer = self
+            .sleep_timer
+            .map(|(sleep_timer, set_at)| sleep_timer_status(&status, sleep_timer, set_at));
+
+        status
+    }
+}
+
+fn sleep_timer_status(
+    status: &Status,
+    sleep_timer: SleepTimer,
+    set_at: Instant,
+) -> SleepTimerStatus {
+    match sleep_timer {
+        SleepTimer::Duration(duration) => SleepTimerStatus {
+            mode: "duration",
+            remaining_ms: Some(duration.saturating_sub(set_at.elapsed()).as_millis() as u64),
+        },
+        SleepTimer::EndOfTrack => SleepTimerStatus {
+            mode: "end_of_track",
+            // the end of a track is only known while it is playing
+            remaining_ms: match (&status.track, status.state) {
+                (Some(track), PlaybackState::Playing) => {
+                    let remaining_ms = track.duration_ms.saturating_sub(status.position_ms);
+                    Some((remaining_ms as f64 / status.playback_speed) as u64)
+                }
+                _ => None,
+            },
+        },
+        SleepTimer::EndOfContext => SleepTimerStatus {
+            mode: "end_of_context",
+            remaining_ms: None,
+        },
+    }
+}
+
+// A player event together with the status right after it.
+#[derive(Debug, Clone)]
+pub struct StatusUpdate {
+    pub event: PlayerEvent,
+    #[cfg_attr(not(any(feature = "mpris", feature = "mqtt")), allow(dead_code))]
+    pub status: Status,
+}
+
+// Keeps track of the status from the player events, for control surfaces to query at any
+// time or to follow as it changes.
+#[derive(Clone)]
+pub struct StatusTracker {
+    tracked: Arc<Mutex<TrackedStatus>>,
+    cec_client: Option<Arc<CecClient>>,
+    update_tx: broadcast::Sender<StatusUpdate>,
+}
+
+impl StatusTracker {
+    pub fn new(player: &Player, mixer: &dyn Mixer, cec_client: Arc<CecClient>) -> Self {
+        Self::spawn(
+            player.get_player_event_channel(),
+            mixer.volume(),
+            Some(cec_client),
+        )
+    }
+
+    fn spawn(
+        mut player_events: PlayerEventChannel,
+        volume: u16,
+        cec_client: Option<Arc<CecClient>>,
+    ) -> Self {
+        let (update_tx, _) = broadcast::channel(UPDATE_CAPACITY);
+        let tracker = Self {
+            tracked: Arc::new(Mutex::new(TrackedStatus::new(volume))),
+            cec_client,
+            update_tx,
+        };
+
+        let events_tracker = tracker.clone();
+        tokio::spawn(async move {
+            while let Some(event) = player_events.recv().await {
+                events_tracker.lock().handle_player_event(event.clone());
+
+                if events_tracker.update_tx.receiver_count() > 0 {
+                    let status = events_tracker.snapshot();
+                    let _ = events_tracker
+                        .update_tx
+                        .send(StatusUpdate { event, status });
+                }
+            }
+
+            debug!("Status tracker stopped, the player is gone");
+        });
+
+        tracker
+    }
+
+    // For tests, without a player or CEC.
+    #[cfg(test)]
+    pub fn from_events(player_events: PlayerEventChannel, volume: u16) -> Self {
+        Self::spawn(player_events, volume, None)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TrackedStatus> {
+        self.tracked
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn snapshot(&self) -> Status {
+        let mut status = self.lock().snapshot();
+        status.cec_power = self
+            .cec_client
+            .as_ref()
+            .is_some_and(|cec_client| cec_client.get_power_status());
+        status
+    }
+
+    // Follows every player event with the status after it. Updates are dropped for
+    // receivers that fall too far behind.
+    pub fn subscribe(&self) -> broadcast::Receiver<StatusUpdate> {
+        self.update_tx.subscribe()
+    }
+}
+
+pub fn to_percent(volume: u16) -> u16 {
+    (volume as f64 / VolumeCtrl::MAX_VOLUME as f64 * 100.0).round() as u16
+}
+
+pub fn from_percent(volume: u16) -> u16 {
+    (volume as f64 / 100.0 * VolumeCtrl::MAX_VOLUME as f64) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spotipi::core::{spotify_id::SpotifyItemType, SpotifyId};
+    use std::time::Duration;
+
+    const TRACK_ID: SpotifyId = SpotifyId {
+        id: 1,
+        item_type: SpotifyItemType::Track,
+    };
+
+    fn playing(position_ms: u32) -> PlayerEvent {
+        PlayerEvent::Playing {
+            play_request_id: 0,
+            track_id: TRACK_ID,
+            position_ms,
+        }
+    }
+
+    fn paused(position_ms: u32) -> PlayerEvent {
+        PlayerEvent::Paused {
+            play_request_id: 0,
+            track_id: TRACK_ID,
+            position_ms,
+        }
+    }
+
+    #[test]
+    fn position_advances_only_while_playing() {
+        let mut tracked = TrackedStatus::new(0);
+
+        tracked.handle_player_event(paused(1000));
+        assert_eq!(tracked.snapshot().state, PlaybackState::Paused);
+        assert_eq!(tracked.snapshot().position_ms, 1000);
+        assert!(tracked.position_at.is_none());
+
+        tracked.handle_player_event(playing(2000));
+        tracked.position_at = Some(Instant::now() - Duration::from_millis(500));
+        let status = tracked.snapshot();
+        assert_eq!(status.state, PlaybackState::Playing);
+        assert!((2500..2600).contains(&status.position_ms));
+
+        tracked.handle_player_event(paused(2500));
+        assert_eq!(tracked.snapshot().position_ms, 2500);
+    }
+
+    #[test]
+    fn position_advances_at_playback_speed() {
+        let mut tracked = TrackedStatus::new(0);
+
+        tracked.handle_player_event(playing(0));
+        tracked.handle_player_event(PlayerEvent::PlaybackSpeedChanged {
+            play_request_id: 0,
+            track_id: TRACK_ID,
+            position_ms: 1000,
+            speed: 2.0,
+        });
+        tracked.position_at = Some(Instant::now() - Duration::from_millis(500));
+
+        assert!((2000..2100).contains(&tracked.snapshot().position_ms));
+    }
+
+    #[test]
+    fn reports_volume_in_percent() {
+        let mut tracked = TrackedStatus::new(VolumeCtrl::MAX_VOLUME);
+        assert_eq!(tracked.snapshot().volume, 100);
+
+        tracked.handle_player_event(PlayerEvent::VolumeChanged {
+            volume: VolumeCtrl::MAX_VOLUME / 2,
+        });
+        assert_eq!(tracked.snapshot().volume, 50);
+    }
+
+    #[test]
+    fn stopping_clears_the_track() {
+        let mut tracked = TrackedStatus::new(0);
+
+        tracked.handle_player_event(playing(1000));
+        tracked.handle_player_event(PlayerEvent::Stopped {
+            play_request_id: 0,
+            track_id: TRACK_ID,
+        });
+
+        let status = tracked.snapshot();
+        assert_eq!(status.state, PlaybackState::Stopped);
+        assert_eq!(status.position_ms, 0);
+        assert!(status.track.is_none());
+    }
+
+    #[test]
+    fn reports_the_remaining_sleep_timer() {
+        let mut tracked = TrackedStatus::new(0);
+        assert_eq!(tracked.snapshot().sleep_timer, None);
+
+        tracked.handle_player_event(PlayerEvent::SleepTimerChanged {
+            sleep_timer: Some(SleepTimer::Duration(Duration::from_secs(60))),
+        });
+        tracked.sleep_timer = tracked
+            .sleep_timer
+            .map(|(sleep_timer, _)| (sleep_timer, Instant::now() - Duration::from_secs(20)));
+        let sleep_timer = tracked.snapshot().sleep_timer.unwrap();
+        assert_eq!(sleep_timer.mode, "duration");
+        assert!((39_900..=40_000).contains(&sleep_timer.remaining_ms.unwrap()));
+
+        tracked.handle_player_event(PlayerEvent::SleepTimerChanged {
+            sleep_timer: Some(SleepTimer::EndOfContext),
+        });
+        assert_eq!(
+            tracked.snapshot().sleep_timer,
+            Some(SleepTimerStatus {
+                mode: "end_of_context",
+                remaining_ms: None,
+            })
+        );
+
+        tracked.handle_player_event(PlayerEvent::SleepTimerChanged { sleep_timer: None });
+        assert_eq!(tracked.snapshot().sleep_timer, None);
+    }
+
+    #[test]
+    fn sleep_timer_ends_with_the_playing_track() {
+        let mut tracked = TrackedStatus::new(0);
+
+        tracked.handle_player_event(PlayerEvent::SleepTimerChanged {
+            sleep_timer: Some(SleepTimer::EndOfTrack),
+        });
+        tracked.handle_player_event(paused(1000));
+        assert_eq!(tracked.snapshot().sleep_timer.unwrap().remaining_ms, None);
+
+        tracked.status.track = Some(TrackStatus {
+            uri: String::new(),
+            name: String::new(),
+            item_type: "track",
+            artists: Vec::new(),
+            album: String::new(),
+            duration_ms: 5000,
+            is_explicit: false,
+            covers: Vec::new(),
+        });
+        tracked.handle_player_event(playing(1000));
+        tracked.position_at = Some(Instant::now());
+        let remaining_ms = tracked
+            .snapshot()
+            .sleep_timer
+            .unwrap()
+            .remaining_ms
+            .unwrap();
+        assert!((3900..=4000).contains(&remaining_ms));
+    }
+
+    #[tokio::test]
+    async fn updates_follow_the_events() {
+        let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let tracker = StatusTracker::from_events(event_rx, 0);
+        let mut updates = tracker.subscribe();
+
+        event_tx.send(playing(1000)).unwrap();
+
+        let update = updates.recv().await.unwrap();
+        assert!(matches!(update.event, PlayerEvent::Playing { .. }));
+        assert_eq!(update.status.state, PlaybackState::Playing);
+        assert_eq!(tracker.snapshot().state, PlaybackState::Playing);
+    }
+}
@@ -0,0 +1,187 @@
+use log::{debug, info};
+
+use std::time::Duration;
+
+use tokio::{
+    task::JoinHandle,
+    time::{sleep_until, Instant},
+};
+
+use spotipi::{
+    discovery::UserLock,
+    playback::player::{Player, PlayerEvent},
+};
+
+#[derive(Debug, Clone)]
+pub struct UserLockConfig {
+    // the users that may take over the device even while it is locked
+    pub allowed_users: Vec<String>,
+    // releases the lock after nothing played for this long, zero keeps it until the session ends
+    pub idle_timeout: Duration,
+}
+
+// Whom the lock belongs to and since when nothing is playing, driven by the player events.
+#[derive(Debug, Default)]
+struct LockState {
+    user: Option<String>,
+    idle_since: Option<Instant>,
+}
+
+impl LockState {
+    fn handle_player_event(&mut self, event: PlayerEvent, now: Instant, lock: &UserLock) {
+        match event {
+            PlayerEvent::SessionConnected { user_name, .. } => {
+                lock.lock(user_name.clone());
+                self.user = Some(user_name);
+                self.idle_since = Some(now);
+            }
+            PlayerEvent::SessionDisconnected { .. } => {
+                lock.unlock();
+                self.user = None;
+                self.idle_since = None;
+            }
+            PlayerEvent::Playing { .. } => {
+                // takes the lock back if it was released while idle
+                if let Some(user) = &self.user {
+                    lock.lock(user.clone());
+                }
+                self.idle_since = None;
+            }
+            PlayerEvent::Paused { .. } | PlayerEvent::Stopped { .. } if self.user.is_some() => {
+                self.idle_since.get_or_insert(now);
+            }
+            _ => (),
+        }
+    }
+
+    fn release_idle(&mut self, lock: &UserLock) {
+        lock.unlock();
+        self.idle_since = None;
+    }
+
+    fn idle_deadline(&self, idle_timeout: Duration) -> Option<Instant> {
+        if idle_timeout.is_zero() {
+            return None;
+        }
+
+        self.idle_since.map(|since| since + idle_timeout)
+    }
+}
+
+// Holds the discovery lock for the user whose session is active, until the session ends
+// or nothing played for the idle timeout.
+pub struct UserLockKeeper {
+    task_handle: JoinHandle<()>,
+}
+
+impl UserLockKeeper {
+    pub fn new(config: UserLockConfig, lock: UserLock, player: &Player) -> Self {
+        let mut player_events = player.get_player_event_channel();
+
+        let task_handle = tokio::spawn(async move {
+            let mut state = LockState::default();
+
+            loop {
+                let deadline = state.idle_deadline(config.idle_timeout);
+
+                tokio::select! {
+                    event = player_events.recv() => match event {
+                        Some(event) => state.handle_player_event(event, Instant::now(), &lock),
+                        None => break,
+                    },
+                    _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                        info!("Nothing played for {:?}, releasing the lock", config.idle_timeout);
+                        state.release_idle(&lock);
+                    },
+                }
+            }
+
+            debug!("User lock stopped, the player is gone");
+        });
+
+        Self { task_handle }
+    }
+
+    pub async fn shutdown(self) {
+        debug!("Shutting down the user lock");
+        self.task_handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use spotipi::core::{spotify_id::SpotifyItemType, SpotifyId};
+
+    const TRACK_ID: SpotifyId = SpotifyId {
+        id: 1,
+        item_type: SpotifyItemType::Track,
+    };
+
+    fn session_connected(user_name: &str) -> PlayerEvent {
+        PlayerEvent::SessionConnected {
+            connection_id: String::new(),
+            user_name: user_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn the_lock_follows_the_session() {
+        let lock = UserLock::default();
+        let mut state = LockState::default();
+        let now = Instant::now();
+
+        state.handle_player_event(session_connected("alice"), now, &lock);
+        assert_eq!(lock.holder().as_deref(), Some("alice"));
+
+        state.handle_player_event(
+            PlayerEvent::SessionDisconnected {
+                connection_id: String::new(),
+                user_name: "alice".to_string(),
+            },
+            now,
+            &lock,
+        );
+        assert_eq!(lock.holder(), None);
+        assert_eq!(state.idle_deadline(Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn idling_releases_the_lock_until_playing_again() {
+        let lock = UserLock::default();
+        let mut state = LockState::default();
+        let timeout = Duration::from_secs(60);
+        let now = Instant::now();
+
+        state.handle_player_event(session_connected("alice"), now, &lock);
+        assert_eq!(state.idle_deadline(timeout), Some(now + timeout));
+        assert_eq!(state.idle_deadline(Duration::ZERO), None);
+
+        let playing = PlayerEvent::Playing {
+            play_request_id: 0,
+            track_id: TRACK_ID,
+            position_ms: 0,
+        };
+        state.handle_player_event(playing.clone(), now, &lock);
+        assert_eq!(state.idle_deadline(timeout), None);
+
+        let later = now + Duration::from_secs(10);
+        state.handle_player_event(
+            PlayerEvent::Paused {
+                play_request_id: 0,
+                track_id: TRACK_ID,
+                position_ms: 0,
+            },
+            later,
+            &lock,
+        );
+        assert_eq!(state.idle_deadline(timeout), Some(later + timeout));
+
+        state.release_idle(&lock);
+        assert_eq!(lock.holder(), None);
+
+        state.handle_player_event(playing, later, &lock);
+        assert_eq!(lock.holder().as_deref(), Some("alice"));
+    }
+}